@@ -0,0 +1,142 @@
+//! Generates the perfect-play table for the standard board, see
+//! `src/table.rs`. The game is solved here on its own, without the crate's
+//! types, as build scripts can't use the crate they build.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Cells are 0 (empty), 1 (X) or 2 (O), row by row
+type Cells = [u8; 9];
+
+const LINES: [[usize; 3]; 8] = [
+    [0, 1, 2],
+    [3, 4, 5],
+    [6, 7, 8],
+    [0, 3, 6],
+    [1, 4, 7],
+    [2, 5, 8],
+    [0, 4, 8],
+    [2, 4, 6],
+];
+
+/// Maps a cell's row and column onto its new ones
+type Map = fn(usize, usize) -> (usize, usize);
+
+/// Where each cell goes under the eight symmetries of the board
+fn symmetries() -> Vec<[usize; 9]> {
+    let maps: [Map; 8] = [
+        |r, c| (r, c),
+        |r, c| (c, 2 - r),
+        |r, c| (2 - r, 2 - c),
+        |r, c| (2 - c, r),
+        |r, c| (r, 2 - c),
+        |r, c| (2 - r, c),
+        |r, c| (c, r),
+        |r, c| (2 - c, 2 - r),
+    ];
+
+    maps.iter()
+        .map(|map| {
+            let mut permutation = [0; 9];
+            for (cell, target) in permutation.iter_mut().enumerate() {
+                let (r, c) = map(cell / 3, cell % 3);
+                *target = r * 3 + c;
+            }
+            permutation
+        })
+        .collect()
+}
+
+/// The cells as a base-3 number, the same as `Board::key`
+fn key(cells: &Cells) -> u32 {
+    cells.iter().fold(0, |key, &cell| key * 3 + u32::from(cell))
+}
+
+fn canonical(cells: &Cells, symmetries: &[[usize; 9]]) -> Cells {
+    symmetries
+        .iter()
+        .map(|permutation| {
+            let mut transformed = [0; 9];
+            for (cell, &target) in permutation.iter().enumerate() {
+                transformed[target] = cells[cell];
+            }
+            transformed
+        })
+        .min_by_key(key)
+        .unwrap()
+}
+
+fn winner(cells: &Cells) -> Option<u8> {
+    LINES
+        .iter()
+        .find(|line| cells[line[0]] != 0 && line.iter().all(|&c| cells[c] == cells[line[0]]))
+        .map(|line| cells[line[0]])
+}
+
+struct Solver {
+    symmetries: Vec<[usize; 9]>,
+    /// Value for the player to move and the best moves, by canonical key and
+    /// player to move
+    table: BTreeMap<u32, (i8, u16)>,
+}
+
+impl Solver {
+    /// The value of `cells` for `player` to move, recording every unfinished
+    /// canonical position on the way
+    fn solve(&mut self, cells: Cells, player: u8) -> i8 {
+        if winner(&cells).is_some() {
+            // The previous player just completed a line
+            return -1;
+        }
+        if cells.iter().all(|&c| c != 0) {
+            return 0;
+        }
+
+        let cells = canonical(&cells, &self.symmetries);
+        let index = key(&cells) * 2 + u32::from(player == 2);
+        if let Some(&(value, _)) = self.table.get(&index) {
+            return value;
+        }
+
+        let mut values = [None; 9];
+        for cell in (0..9).filter(|&c| cells[c] == 0) {
+            let mut child = cells;
+            child[cell] = player;
+            values[cell] = Some(-self.solve(child, 3 - player));
+        }
+
+        let value = values.iter().flatten().cloned().max().unwrap();
+        let best = (0..9)
+            .filter(|&c| values[c] == Some(value))
+            .fold(0, |mask, c| mask | 1 << c);
+
+        self.table.insert(index, (value, best));
+        value
+    }
+}
+
+fn main() {
+    let mut solver = Solver {
+        symmetries: symmetries(),
+        table: BTreeMap::new(),
+    };
+
+    // Either player may start
+    solver.solve([0; 9], 1);
+    solver.solve([0; 9], 2);
+
+    let mut out = String::new();
+    writeln!(out, "/// Generated by build.rs: (key * 2 + 1 if O moves, value, best moves)").unwrap();
+    writeln!(out, "pub static ENTRIES: [(u32, i8, u16); {}] = [", solver.table.len()).unwrap();
+    for (index, &(value, best)) in &solver.table {
+        writeln!(out, "    ({}, {}, {:#011b}),", index, value, best).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("table.rs");
+    fs::write(path, out).unwrap();
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+use cli;
+use engine::Engine;
+use game::{Board, Coord, GameStatus, Player};
+use mcts::{Config, Limit, StopToken, MCTS};
+use minimax;
+use pns;
+use qlearning::{self, QTable};
+use record::GameRecord;
+use render::{Cli, Renderer};
+use table;
+
+/// How often the interactive shell reports the search state
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+const SHELL_HELP: &str = "Commands:
+    <move>            Play a move for the side to move, e.g. 2b
+    undo              Take back the last move
+    new               Start over from the empty board
+    position <fen>    Set up a position
+    board             Show the board
+    annotate <text>   Comment on the last move
+    save <file>       Save the game with its annotations
+    load <file>       Continue a saved game
+    games             List the open games
+    game new          Open another game from the empty board
+    game <n>          Switch to game n
+    quit              Leave the shell";
+
+/// Search a single position and print the engine's findings
+pub fn run(args: &[String]) {
+    let mut board = Board::new(Player::X);
+    let mut movetime = Duration::from_secs(5);
+    let mut interactive = false;
+    let mut tree_file: Option<String> = None;
+    let mut tree_depth = 2;
+    let mut engine = Engine::Mcts;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--position" => board = cli::position(args.next()),
+            "--movetime" => movetime = cli::duration(arg, args.next()),
+            "--interactive" => interactive = true,
+            "--dump-tree" => tree_file = Some(cli::value(arg, args.next())),
+            "--tree-depth" => tree_depth = cli::value(arg, args.next()),
+            "--engine" => engine = cli::engine(args.next()),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    if engine != Engine::Mcts && (interactive || tree_file.is_some()) {
+        cli::fail("--interactive and --dump-tree need the MCTS engine");
+    }
+
+    if interactive {
+        return shell(board);
+    }
+
+    board.print();
+    println!();
+
+    match board.status() {
+        GameStatus::InProgress => {}
+        GameStatus::Won(player, _) => {
+            println!("Game is over, winner is Player {}", player);
+            return;
+        }
+        GameStatus::Draw => {
+            println!("Game is over, it's a draw");
+            return;
+        }
+    }
+
+    let player = board.next_player();
+
+    match engine {
+        Engine::Mcts => {}
+        Engine::Pns => {
+            let proof = pns::solve(&board);
+            let action = proof.action.expect("Unfinished game without moves");
+
+            println!("Best move: {}", action);
+            println!("Solved: {} for Player {}", minimax::shared().describe(&board), player);
+            println!("Nodes: {} in {:.2}s", proof.nodes, proof.elapsed.as_secs_f64());
+            return;
+        }
+        Engine::Negamax => {
+            let start = Instant::now();
+            let mut cache = minimax::shared();
+            let actions: Vec<String> =
+                cache.best_actions(&board).into_iter().map(|action| action.to_string()).collect();
+
+            println!("Best moves: {}", actions.join(" "));
+            println!("Solved: {} for Player {}", cache.describe(&board), player);
+            println!(
+                "Positions: {} in {:.2}s",
+                cache.positions(),
+                start.elapsed().as_secs_f64()
+            );
+            return;
+        }
+        Engine::Perfect => {
+            let (_, actions) = table::perfect(&board);
+            let actions: Vec<String> = actions.iter().map(|action| action.to_string()).collect();
+
+            println!("Best moves: {}", actions.join(" "));
+            println!("Solved: {} for Player {}", minimax::shared().describe(&board), player);
+            return;
+        }
+        Engine::QLearning => {
+            let table = QTable::load_or_learn(qlearning::DEFAULT_TABLE)
+                .unwrap_or_else(|e| cli::fail(&e));
+            let (action, value) = table.best_action(&board).expect("Unfinished game without moves");
+
+            println!("Best move: {}", action);
+            println!("Q-value: {:+.2} for Player {}", value, player);
+            println!();
+            println!("Move  Q-value");
+            for action in board.get_actions() {
+                println!("{:<4}  {:>+7.2}", action, table.value(&board, action));
+            }
+            return;
+        }
+    }
+
+    let mut engine = MCTS::from_board(player, board, Config::default());
+
+    let result = engine.search(Limit::Time(movetime), &StopToken::new());
+
+    let variation: Vec<String> = result
+        .principal_variation
+        .iter()
+        .cloned()
+        .map(|action| action.to_string())
+        .collect();
+
+    println!("Best move: {}", variation[0]);
+    match result.proven {
+        Some(_) => println!("Solved: {} for Player {}", minimax::shared().describe(&board), player),
+        None => println!("Evaluation: {:+.2} for Player {}", result.value, player),
+    }
+    println!("Principal variation: {}", variation.join(" "));
+    println!(
+        "Iterations: {} in {:.2}s",
+        result.nodes,
+        result.elapsed.as_secs_f64()
+    );
+
+    let tree = engine.stats();
+    println!(
+        "Tree: {} nodes (~{} KiB), {} levels deep, {} root visits",
+        tree.nodes,
+        tree.memory / 1024,
+        tree.depth,
+        tree.root_visits
+    );
+    println!();
+    println!("Move  Visits    Value");
+
+    for stats in result.stats {
+        println!(
+            "{:<4}  {:>6}  {:>+7.2}",
+            stats.action.to_string(),
+            stats.visits,
+            stats.value
+        );
+    }
+
+    // Dump the search tree, "-" meaning stdout
+    match tree_file.as_deref() {
+        Some("-") => println!("{}", engine.tree_json(tree_depth)),
+        Some(file) => fs::write(file, engine.tree_json(tree_depth))
+            .unwrap_or_else(|e| cli::fail(&format!("Could not write {}: {}", file, e))),
+        None => {}
+    }
+}
+
+pub fn value_name(value: i32) -> &'static str {
+    match value {
+        1 => "win",
+        -1 => "loss",
+        _ => "draw",
+    }
+}
+
+/// Analysis board: moves for both sides are entered freely while the engine
+/// keeps searching the current position in the background
+fn shell(board: Board) {
+    let (tx, rx) = channel();
+    let stop = StopToken::new();
+    let searcher = {
+        let stop = stop.clone();
+        thread::spawn(move || search_forever(&rx, &stop))
+    };
+
+    // Open games, each searched by its own engine
+    let mut games = vec![GameRecord::new(board)];
+    let mut current = 0;
+
+    println!("{}", SHELL_HELP);
+    println!();
+    board.print();
+    tx.send((current, board)).unwrap();
+
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => continue,
+        };
+        let rest = words.collect::<Vec<_>>().join(" ");
+        let record = &mut games[current];
+        let mut board = record.board();
+
+        match command {
+            "quit" => break,
+            "board" => {
+                board.print();
+                continue;
+            }
+            "undo" if record.moves.pop().is_some() => board = record.board(),
+            "undo" => {
+                println!("Nothing to undo");
+                continue;
+            }
+            "new" => {
+                board = Board::new(Player::X);
+                *record = GameRecord::new(board);
+            }
+            "position" => match Board::from_fen(&rest) {
+                Ok(position) => {
+                    board = position;
+                    *record = GameRecord::new(board);
+                }
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
+            },
+            "annotate" => {
+                if let Err(e) = record.annotate(&rest) {
+                    println!("{}", e);
+                }
+                continue;
+            }
+            "save" => {
+                match record.save(&rest) {
+                    Ok(()) => println!("Saved to {}", rest),
+                    Err(e) => println!("{}", e),
+                }
+                continue;
+            }
+            "load" => match GameRecord::load(&rest) {
+                Ok(loaded) => {
+                    *record = loaded;
+                    board = record.board();
+                }
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
+            },
+            "games" => {
+                for (i, game) in games.iter().enumerate() {
+                    let board = game.board();
+                    let status = match board.status() {
+                        GameStatus::Won(player, _) => format!("won by {}", player),
+                        GameStatus::Draw => "drawn".to_string(),
+                        GameStatus::InProgress => format!("{} to move", board.next_player()),
+                    };
+
+                    let marker = if i == current { '*' } else { ' ' };
+                    println!("{} {}: {} moves, {}", marker, i + 1, game.moves.len(), status);
+                }
+                continue;
+            }
+            "game" if rest == "new" => {
+                board = Board::new(Player::X);
+                games.push(GameRecord::new(board));
+                current = games.len() - 1;
+                println!("Game {}", current + 1);
+            }
+            "game" => match rest.parse::<usize>() {
+                Ok(n) if n >= 1 && n <= games.len() => {
+                    current = n - 1;
+                    board = games[current].board();
+                    println!("Game {}", n);
+                }
+                _ => {
+                    println!("No game {}, see games", rest);
+                    continue;
+                }
+            },
+            _ => match command.parse::<Coord>().ok() {
+                Some(action) if board.is_legal_action(action) && !board.is_ended() => {
+                    board.perform_action(action);
+                    record.push(action);
+                }
+                Some(_) => {
+                    println!("Illegal action");
+                    continue;
+                }
+                None => {
+                    println!("Unknown command: {}", command);
+                    println!("{}", SHELL_HELP);
+                    continue;
+                }
+            },
+        }
+
+        let mut renderer = Cli::plain();
+        renderer.draw_board(&board);
+        if board.is_ended() {
+            renderer.announce_result(&board);
+        }
+
+        // Interrupt the search of the previous position
+        tx.send((current, board)).unwrap();
+        stop.stop();
+    }
+
+    drop(tx);
+    stop.stop();
+    searcher.join().unwrap();
+}
+
+/// The action leading from `from` to `to`, if they are a single move apart
+fn played_action(from: &Board, to: &Board) -> Option<Coord> {
+    from.actions_iter().find(|&action| {
+        let mut board = *from;
+        board.perform_action(action);
+        board == *to
+    })
+}
+
+/// Search the position of whatever game was received last and report
+/// whenever the result changes, until the channel is closed. `stop`
+/// interrupts the search to look for a new position.
+fn search_forever(rx: &Receiver<(usize, Board)>, stop: &StopToken) {
+    // Engines by game, so switching back to a game continues its search
+    let mut engines: HashMap<usize, MCTS> = HashMap::new();
+    let mut current = 0;
+    let mut last_report = Instant::now();
+    let mut last_result = None;
+
+    loop {
+        stop.reset();
+
+        // Wait for the next position when there is nothing (left) to search
+        let idle = engines.get(&current).is_none_or(|engine| engine.solved().is_some());
+        let next = if idle {
+            rx.recv().map_err(|_| TryRecvError::Disconnected)
+        } else {
+            rx.try_recv()
+        };
+
+        match next {
+            Ok((game, board)) => {
+                current = game;
+                let engine = engines.remove(&game);
+
+                if !board.is_ended() {
+                    // Keep searching the subtree of a move that was just made
+                    let played = engine
+                        .as_ref()
+                        .and_then(|engine| played_action(&engine.board(), &board));
+
+                    let engine = match (engine, played) {
+                        (Some(engine), _) if engine.board() == board => engine,
+                        (Some(mut engine), Some(action)) => {
+                            let freed = engine.freed_nodes();
+                            engine.perform_action(action);
+                            let freed = engine.freed_nodes() - freed;
+
+                            println!(
+                                "[reusing the search tree, freed {} nodes (~{} KiB)]",
+                                freed,
+                                MCTS::node_memory(freed) / 1024
+                            );
+                            engine
+                        }
+                        _ => MCTS::from_board(board.next_player(), board, Config::default()),
+                    };
+                    engines.insert(game, engine);
+                }
+                last_result = None;
+                continue;
+            }
+            Err(TryRecvError::Disconnected) => break,
+            Err(TryRecvError::Empty) => {}
+        }
+
+        let engine = engines.get_mut(&current).expect("No position to search");
+        let limit = Limit::Time(REPORT_INTERVAL.saturating_sub(last_report.elapsed()));
+        let search = engine.search(limit, stop);
+        let variation: Vec<String> = search
+            .principal_variation
+            .iter()
+            .cloned()
+            .map(|action| action.to_string())
+            .collect();
+
+        if search.proven.is_some() {
+            let board = engine.board();
+            println!(
+                "[solved: {} for Player {}, pv {}]",
+                minimax::shared().describe(&board),
+                board.next_player(),
+                variation.join(" ")
+            );
+            continue;
+        }
+
+        if last_report.elapsed() >= REPORT_INTERVAL {
+            last_report = Instant::now();
+
+            let evaluation = (search.value * 100.).round() / 100.;
+            let result = Some((variation.clone(), evaluation));
+
+            if result != last_result {
+                println!(
+                    "[eval {:+.2}, pv {}, {} iterations]",
+                    evaluation,
+                    variation.join(" "),
+                    engine.iterations()
+                );
+                last_result = result;
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! URL-safe base64 (RFC 4648 §5) without padding
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+pub fn encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |bits, (i, &byte)| bits | u32::from(byte) << (16 - 8 * i));
+
+        // Every byte needs at least two characters
+        for i in 0..=chunk.len() {
+            out.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+
+    out
+}
+
+pub fn decode(input: &str) -> Option<Vec<u8>> {
+    let values = input
+        .bytes()
+        .map(|c| ALPHABET.iter().position(|&a| a == c).map(|v| v as u32))
+        .collect::<Option<Vec<u32>>>()?;
+    let mut out = Vec::with_capacity(values.len() * 3 / 4);
+
+    for chunk in values.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, &value)| bits | value << (18 - 6 * i));
+
+        for i in 0..chunk.len() - 1 {
+            out.push((bits >> (16 - 8 * i)) as u8);
+        }
+    }
+
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode};
+
+    #[test]
+    fn encodes_rfc_4648_test_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg"),
+            ("fo", "Zm8"),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg"),
+            ("fooba", "Zm9vYmE"),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for &(data, encoded) in &vectors {
+            assert_eq!(encode(data.as_bytes()), encoded);
+            assert_eq!(decode(encoded), Some(data.as_bytes().to_vec()));
+        }
+
+        // The URL-safe characters instead of + and /
+        assert_eq!(encode(&[0xfb, 0xff]), "-_8");
+    }
+
+    #[test]
+    fn round_trips_every_byte() {
+        let data: Vec<u8> = (0..=255).collect();
+        for len in 0..data.len() {
+            assert_eq!(decode(&encode(&data[..len])).as_deref(), Some(&data[..len]));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert_eq!(decode("Z"), None);
+        assert_eq!(decode("Zm9vY"), None);
+        assert_eq!(decode("Zg=="), None);
+        assert_eq!(decode("+/8"), None);
+    }
+}
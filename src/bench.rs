@@ -0,0 +1,234 @@
+//! Benchmarks of the board operations and the search, for changes meant to
+//! make them faster. Save the numbers before the change with `--save` and
+//! compare against them after it with `--baseline`:
+//!
+//! ```text
+//! cargo run --release -- bench --save before.txt
+//! cargo run --release -- bench --baseline before.txt
+//! ```
+//!
+//! Each benchmark is run for a while to warm up, then timed in a number of
+//! samples of many runs each. The median sample is reported, along with the
+//! fastest and the slowest to show how noisy the numbers are.
+
+use std::fmt::Write;
+use std::fs;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use rand::{Rng, XorShiftRng};
+use cli;
+use game::{Board, Player};
+use mcts::{Config, Limit, StopToken, MCTS};
+
+/// Samples taken of every benchmark
+const SAMPLES: u32 = 20;
+/// Positions that board operations are timed on
+const POSITIONS: usize = 1000;
+/// Iterations of a timed search
+const ITERATIONS: u64 = 1000;
+/// Changes against the baseline smaller than this are taken as noise
+const NOISE: f64 = 0.05;
+
+struct Benchmark {
+    name: &'static str,
+    /// What a result is given per
+    unit: &'static str,
+    /// How many of `unit` one run does
+    count: u64,
+    run: Box<dyn FnMut()>,
+}
+
+/// The positions of random games, the same every time
+fn positions() -> Vec<Board> {
+    let mut rng = XorShiftRng::new_unseeded();
+    let mut positions = Vec::with_capacity(POSITIONS);
+
+    while positions.len() < POSITIONS {
+        let mut board = Board::new(Player::X);
+        positions.push(board);
+
+        while !board.is_ended() && positions.len() < POSITIONS {
+            let mut actions = board.actions_iter();
+            let pick = rng.gen_range(0, actions.len());
+            board.perform_action(actions.nth(pick).expect("No actions left"));
+            positions.push(board);
+        }
+    }
+
+    positions
+}
+
+fn benchmarks() -> Vec<Benchmark> {
+    let positions = positions();
+    let status_positions = positions.clone();
+    let mut rng = XorShiftRng::new_unseeded();
+
+    vec![
+        Benchmark {
+            name: "status",
+            unit: "position",
+            count: positions.len() as u64,
+            run: Box::new(move || {
+                for board in &status_positions {
+                    black_box(black_box(board).status());
+                }
+            }),
+        },
+        Benchmark {
+            name: "actions",
+            unit: "position",
+            count: positions.len() as u64,
+            run: Box::new(move || {
+                for board in &positions {
+                    black_box(black_box(board).get_actions());
+                }
+            }),
+        },
+        Benchmark {
+            name: "rollout",
+            unit: "game",
+            count: 1,
+            run: Box::new(move || {
+                let mut board = Board::new(Player::X);
+                while !board.is_ended() {
+                    let mut actions = board.actions_iter();
+                    let pick = rng.gen_range(0, actions.len());
+                    board.perform_action(actions.nth(pick).expect("No actions left"));
+                }
+                black_box(board);
+            }),
+        },
+        Benchmark {
+            name: "mcts",
+            unit: "iteration",
+            count: ITERATIONS,
+            run: Box::new(|| {
+                let board = Board::new(Player::X);
+                let mut mcts = MCTS::from_board(Player::X, board, Config::default());
+                black_box(mcts.search(Limit::Iterations(ITERATIONS), &StopToken::new()));
+            }),
+        },
+    ]
+}
+
+/// Nanoseconds per unit of the fastest, the median and the slowest sample,
+/// spending about `time` on them
+fn measure(benchmark: &mut Benchmark, time: Duration) -> [f64; 3] {
+    // Warm up, and find out how many runs fit into a sample
+    let warm_up = time / 10;
+    let start = Instant::now();
+    let mut runs = 0u32;
+    while start.elapsed() < warm_up || runs == 0 {
+        (benchmark.run)();
+        runs += 1;
+    }
+    let per_run = start.elapsed() / runs;
+    let batch = ((time - warm_up) / SAMPLES).as_nanos() / per_run.as_nanos().max(1);
+    let batch = batch.max(1) as u32;
+
+    let mut samples: Vec<f64> = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..batch {
+                (benchmark.run)();
+            }
+            start.elapsed().as_nanos() as f64 / f64::from(batch) / benchmark.count as f64
+        })
+        .collect();
+
+    samples.sort_by(f64::total_cmp);
+    [samples[0], samples[samples.len() / 2], samples[samples.len() - 1]]
+}
+
+/// Nanoseconds as the unit that reads best
+fn format_time(nanos: f64) -> String {
+    if nanos < 1e3 {
+        format!("{:.1} ns", nanos)
+    } else if nanos < 1e6 {
+        format!("{:.2} µs", nanos / 1e3)
+    } else {
+        format!("{:.2} ms", nanos / 1e6)
+    }
+}
+
+/// A saved run: the median in nanoseconds by benchmark, one per line
+fn load_baseline(path: &str) -> Result<Vec<(String, f64)>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let invalid = || format!("{}: invalid line {}", path, line);
+            let (name, nanos) = line.split_once(' ').ok_or_else(invalid)?;
+            Ok((name.to_string(), nanos.trim().parse().map_err(|_| invalid())?))
+        })
+        .collect()
+}
+
+/// Compare the median to the baseline's, e.g. `-12.3% (faster)`
+fn compare(median: f64, baseline: f64) -> String {
+    let change = median / baseline - 1.;
+    let verdict = if change <= -NOISE {
+        "faster"
+    } else if change >= NOISE {
+        "slower"
+    } else {
+        "no change"
+    };
+
+    format!("{:+.1}% ({})", change * 100., verdict)
+}
+
+/// Time the board operations and the search
+pub fn run(args: &[String]) {
+    let mut filter: Option<&String> = None;
+    let mut time = Duration::from_secs(2);
+    let mut save: Option<String> = None;
+    let mut baseline: Option<String> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--time" => time = cli::duration(arg, args.next()),
+            "--save" => save = Some(cli::value(arg, args.next())),
+            "--baseline" => baseline = Some(cli::value(arg, args.next())),
+            _ if filter.is_none() && !arg.starts_with("--") => filter = Some(arg),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    let baseline = baseline.map(|path| load_baseline(&path).unwrap_or_else(|e| cli::fail(&e)));
+    let mut benchmarks: Vec<Benchmark> = benchmarks()
+        .into_iter()
+        .filter(|b| filter.is_none_or(|filter| b.name.contains(filter.as_str())))
+        .collect();
+
+    if benchmarks.is_empty() {
+        cli::fail("No benchmark matches the filter");
+    }
+
+    let mut saved = String::new();
+    for benchmark in &mut benchmarks {
+        let [fastest, median, slowest] = measure(benchmark, time);
+        let previous = baseline
+            .as_ref()
+            .and_then(|baseline| baseline.iter().find(|(name, _)| name == benchmark.name));
+
+        println!(
+            "{:<8} {:>10} per {:<9}  [{} .. {}]{}",
+            benchmark.name,
+            format_time(median),
+            benchmark.unit,
+            format_time(fastest),
+            format_time(slowest),
+            previous.map_or(String::new(), |&(_, nanos)| format!("  {}", compare(median, nanos)))
+        );
+        writeln!(saved, "{} {}", benchmark.name, median).unwrap();
+    }
+
+    if let Some(path) = save {
+        fs::write(&path, saved)
+            .unwrap_or_else(|e| cli::fail(&format!("Could not write {}: {}", path, e)));
+        println!("Saved the results to {}", path);
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashSet;
+use std::process;
+use cli;
+use game::{Board, GameStatus, Player};
+use rules::Variant;
+
+/// Positions reachable from the empty board with X starting, by result: all
+/// of them and up to symmetry
+const REFERENCE: Census = Census {
+    ongoing: Count { positions: 4520, canonical: 627 },
+    x_wins: Count { positions: 626, canonical: 91 },
+    o_wins: Count { positions: 316, canonical: 44 },
+    draws: Count { positions: 16, canonical: 3 },
+};
+
+/// Distinct positions, counted as they are and up to rotating and mirroring
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct Count {
+    pub positions: usize,
+    pub canonical: usize,
+}
+
+/// The positions reachable from a start position, by result
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct Census {
+    pub ongoing: Count,
+    pub x_wins: Count,
+    pub o_wins: Count,
+    pub draws: Count,
+}
+
+impl Census {
+    pub fn total(&self) -> Count {
+        let counts = [self.ongoing, self.x_wins, self.o_wins, self.draws];
+
+        Count {
+            positions: counts.iter().map(|c| c.positions).sum(),
+            canonical: counts.iter().map(|c| c.canonical).sum(),
+        }
+    }
+
+    fn count(&mut self, board: &Board) -> &mut Count {
+        match board.status() {
+            GameStatus::InProgress => &mut self.ongoing,
+            GameStatus::Won(Player::X, _) => &mut self.x_wins,
+            GameStatus::Won(Player::O, _) => &mut self.o_wins,
+            GameStatus::Draw => &mut self.draws,
+        }
+    }
+}
+
+/// Enumerate every position reachable from `start`, including it
+pub fn census(start: &Board) -> Census {
+    let mut seen = HashSet::new();
+    let mut canonical = HashSet::new();
+    let mut census = Census::default();
+    let mut stack = vec![*start];
+
+    while let Some(board) = stack.pop() {
+        if !seen.insert(board) {
+            continue;
+        }
+
+        census.count(&board).positions += 1;
+        if canonical.insert(board.canonical().0) {
+            census.count(&board).canonical += 1;
+        }
+
+        for action in board.actions_iter() {
+            let mut child = board;
+            child.perform_action(action);
+            stack.push(child);
+        }
+    }
+
+    census
+}
+
+pub fn run(args: &[String]) {
+    let mut board = Board::new(Player::X);
+    let mut variant = Variant::STANDARD;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--position" => board = cli::position(args.next()),
+            "--variant" => variant = cli::variant(args.next()),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    board.variant = variant;
+    let census = census(&board);
+
+    println!("Position: {} ({})", board.fen(), variant.name());
+    println!("{:<10} {:>9} {:>9}", "", "Positions", "Symmetry");
+
+    let rows = [
+        ("Ongoing", census.ongoing),
+        ("X wins", census.x_wins),
+        ("O wins", census.o_wins),
+        ("Draws", census.draws),
+        ("Total", census.total()),
+    ];
+    for &(name, count) in &rows {
+        println!("{:<10} {:>9} {:>9}", name, count.positions, count.canonical);
+    }
+
+    // Reference values are only known for the start position
+    if board == Board::new(Player::X) && census != REFERENCE {
+        println!("Counts differ from the known ones: {:?}", REFERENCE);
+        process::exit(1);
+    }
+}
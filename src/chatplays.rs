@@ -0,0 +1,309 @@
+use std::cmp;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use rand::{self, Rng};
+use cli;
+use game::{Board, Coord, Player};
+use input::{Input, InputSource};
+use mcts::{Config, Limit, StopToken, MCTS};
+use render::{self, Renderer};
+use terminal;
+use theme;
+
+const DEFAULT_SERVER: &str = "irc.chat.twitch.tv:6667";
+const DEFAULT_WINDOW: Duration = Duration::from_secs(30);
+const DEFAULT_AI_TIME: Duration = Duration::from_secs(1);
+
+/// The side played by the chat, X always starting
+const CHAT_PLAYER: Player = Player::X;
+
+/// Pause between two games so viewers can see the final board
+const GAME_PAUSE: Duration = Duration::from_secs(10);
+
+/// A chat message as (nick, text)
+type Message = (String, String);
+
+struct Irc {
+    stream: TcpStream,
+    channel: String,
+}
+
+impl Irc {
+    fn send(&mut self, line: &str) {
+        // A lost connection shows up in the reader thread
+        let _ = write!(self.stream, "{}\r\n", line);
+    }
+
+    fn say(&mut self, text: &str) {
+        let line = format!("PRIVMSG {} :{}", self.channel, text);
+        self.send(&line);
+    }
+}
+
+/// Extract sender and text from a `:nick!user@host PRIVMSG #channel :text`
+/// line
+fn parse_privmsg(line: &str) -> Option<Message> {
+    let line = line.strip_prefix(':')?;
+    let (prefix, rest) = line.split_once(' ')?;
+    let (command, rest) = rest.split_once(' ')?;
+    let (_, text) = rest.split_once(" :")?;
+
+    if command != "PRIVMSG" {
+        return None;
+    }
+
+    let nick = prefix.split('!').next()?;
+    Some((nick.to_lowercase(), text.trim().to_string()))
+}
+
+/// Read the server's lines, answer pings and forward chat messages
+fn read_chat(stream: TcpStream, messages: &Sender<Message>) {
+    let mut writer = stream.try_clone().expect("Could not clone IRC stream");
+
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        if let Some(server) = line.strip_prefix("PING ") {
+            let _ = write!(writer, "PONG {}\r\n", server);
+        } else if let Some(message) = parse_privmsg(&line) {
+            if messages.send(message).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Votes of the current window, one per viewer
+#[derive(Default)]
+struct Ballot {
+    votes: HashMap<String, Coord>,
+}
+
+impl Ballot {
+    /// Count a message as a vote if it names a legal move, like `2b` or
+    /// `!2b`. Viewers may change their vote.
+    fn cast(&mut self, board: &Board, (nick, text): Message) -> bool {
+        let text = text.strip_prefix('!').unwrap_or(&text).to_lowercase();
+
+        match text.parse::<Coord>() {
+            Ok(action) if board.is_legal_action(action) => {
+                self.votes.insert(nick, action);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Votes per move, most votes first
+    fn tally(&self) -> Vec<(Coord, usize)> {
+        let mut counts: HashMap<Coord, usize> = HashMap::new();
+        for &action in self.votes.values() {
+            *counts.entry(action).or_insert(0) += 1;
+        }
+
+        let mut tally: Vec<_> = counts.into_iter().collect();
+        tally.sort_by_key(|&(action, count)| (usize::MAX - count, action));
+        tally
+    }
+
+    /// The move with most votes, ties broken at random
+    fn winner(&self) -> Option<Coord> {
+        let tally = self.tally();
+        let (_, most) = *tally.first()?;
+        let tied: Vec<Coord> = tally
+            .iter()
+            .filter(|&&(_, count)| count == most)
+            .map(|&(action, _)| action)
+            .collect();
+
+        rand::thread_rng().choose(&tied).cloned()
+    }
+}
+
+struct Stream {
+    irc: Irc,
+    messages: Receiver<Message>,
+    window: Duration,
+    ai_time: Duration,
+    renderer: Box<dyn Renderer>,
+}
+
+impl Stream {
+    fn render(&mut self, board: &Board, status: &str, ballot: Option<&Ballot>) {
+        self.renderer.draw_board(board);
+        println!();
+        self.renderer.show_stats(status);
+
+        if let Some(ballot) = ballot {
+            for (action, count) in ballot.tally() {
+                let bar = "#".repeat(count);
+                self.renderer
+                    .show_stats(&format!("  {}  {:>3}  {}", action, count, bar));
+            }
+        }
+
+        println!();
+    }
+
+    /// Collect votes until a window closes with at least one vote
+    fn vote(&mut self, board: &Board) -> Coord {
+        self.irc.say(&format!(
+            "Vote for the next move of {}, e.g. !2b ({}s)",
+            CHAT_PLAYER,
+            self.window.as_secs()
+        ));
+
+        let mut ballot = Ballot::default();
+        let mut deadline = Instant::now() + self.window;
+
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                if let Some(action) = ballot.winner() {
+                    return action;
+                }
+
+                // Nobody voted, keep the poll open
+                deadline = now + self.window;
+                continue;
+            }
+
+            let left = deadline - now;
+            let status = format!("Chat is voting ({}s left)", left.as_secs() + 1);
+            self.render(board, &status, Some(&ballot));
+
+            // Refresh the countdown at least every second
+            match self.messages.recv_timeout(cmp::min(left, Duration::from_secs(1))) {
+                Ok(message) => {
+                    ballot.cast(board, message);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => cli::fail("Lost connection to the chat server"),
+            }
+        }
+    }
+
+    fn think(&self, board: &Board) -> Coord {
+        let mut engine = MCTS::from_board(board.next_player(), *board, Config::default());
+
+        engine
+            .search(Limit::Time(self.ai_time), &StopToken::new())
+            .action
+            .expect("No action in a running game")
+    }
+
+    fn play(&mut self) {
+        let mut board = Board::new(CHAT_PLAYER);
+
+        while !board.is_ended() {
+            let action = if board.next_player() == CHAT_PLAYER {
+                match self.next_input(&board) {
+                    Input::Action(action) => action,
+                    Input::Forfeit | Input::Quit => return,
+                    // Not a chat command
+                    Input::Heatmap => continue,
+                }
+            } else {
+                self.render(&board, "AI is thinking...", None);
+                self.think(&board)
+            };
+
+            board.perform_action(action);
+            self.irc.say(&format!(
+                "{} played {}",
+                if board.next_player() == CHAT_PLAYER { "AI" } else { "Chat" },
+                action
+            ));
+        }
+
+        let result = match board.status().winner() {
+            Some(CHAT_PLAYER) => "Chat wins!".to_string(),
+            Some(_) => "AI wins!".to_string(),
+            None => "It's a draw".to_string(),
+        };
+
+        self.render(&board, &result, None);
+        self.irc.say(&result);
+    }
+}
+
+/// The chat plays by majority vote
+impl InputSource for Stream {
+    fn next_input(&mut self, board: &Board) -> Input {
+        Input::Action(self.vote(board))
+    }
+}
+
+/// Let an IRC (e.g. Twitch) chat play X against the AI by voting
+pub fn run(args: &[String]) {
+    let mut server = DEFAULT_SERVER.to_string();
+    let mut channel: Option<String> = None;
+    // Twitch allows anonymous read-only logins as justinfan<number>
+    let mut nick = format!("justinfan{}", rand::thread_rng().gen_range(1000, 100000));
+    let mut pass: Option<String> = None;
+    let mut window = DEFAULT_WINDOW;
+    let mut ai_time = DEFAULT_AI_TIME;
+    let mut clear_screen = false;
+    let mut theme = theme::PLAIN;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--server" => server = cli::value(arg, args.next()),
+            "--channel" => channel = Some(cli::value(arg, args.next())),
+            "--nick" => nick = cli::value(arg, args.next()),
+            "--pass" => pass = Some(cli::value(arg, args.next())),
+            "--window" => window = cli::duration(arg, args.next()),
+            "--ai-time" => ai_time = cli::duration(arg, args.next()),
+            "--clear" => clear_screen = true,
+            "--theme" => theme = cli::theme(args.next()),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    let channel = match channel {
+        Some(ref name) if name.starts_with('#') => name.to_lowercase(),
+        Some(name) => format!("#{}", name.to_lowercase()),
+        None => cli::fail("Missing --channel"),
+    };
+
+    if (clear_screen || theme != theme::PLAIN) && !terminal::supports_ansi() {
+        clear_screen = false;
+        theme = theme::PLAIN;
+    }
+
+    let stream = TcpStream::connect(&server)
+        .unwrap_or_else(|e| cli::fail(&format!("Could not connect to {}: {}", server, e)));
+    let reader = stream.try_clone().expect("Could not clone IRC stream");
+
+    let (tx, messages) = mpsc::channel();
+    thread::spawn(move || read_chat(reader, &tx));
+
+    let mut irc = Irc { stream, channel };
+    if let Some(pass) = pass {
+        irc.send(&format!("PASS {}", pass));
+    }
+    irc.send(&format!("NICK {}", nick));
+    let join = format!("JOIN {}", irc.channel);
+    irc.send(&join);
+
+    let mut stream = Stream {
+        irc,
+        messages,
+        window,
+        ai_time,
+        renderer: render::for_terminal(clear_screen, theme, false, None),
+    };
+
+    loop {
+        stream.play();
+        thread::sleep(GAME_PAUSE);
+    }
+}
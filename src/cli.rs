@@ -0,0 +1,129 @@
+use std::process;
+use std::str::FromStr;
+use std::time::Duration;
+use engine::Engine;
+use game::Board;
+use input::Timeout;
+use rules::Variant;
+use svg::Style;
+use theme::Theme;
+
+pub const USAGE: &str = "Usage:
+    mcts [--no-adaptive] [--clear] [--theme <name>] [--accessible] [--commentary]
+         [--pie-rule] [--variant <name>] [--explain] [--save <file>] [--engine <name>] [--q-table <file>]
+         [--eval-bar] [--blunder-alert] [--discount <factor>] [--random-opening]
+         [--first me|ai|random|alternate] [--moves <list>] [--human-movetime <time>]
+         [--on-timeout random|hint|forfeit] [--log-events <file>] [--handicap <spec>]
+    mcts --async-move [<token>]
+    mcts tune [--games <n>] [--iterations <n>] [--output <file.csv|file.json>]
+    mcts tournament [--entrant <name>:<config>]... [--external <name>:<command>]...
+                    [--games <n>] [--iterations <n>] [--swiss <rounds>] [--jobs <n>]
+                    [--output <file.csv|file.json>] [--handicap <weaker>/<stronger>:<spec>]...
+    mcts simulate [--games <n>] [--engine-a <engine>] [--engine-b <engine>]
+                  [--iterations <n>] [--variant <name>] [--jobs <n>]
+    mcts plot (win-rate <tournament.csv>... | elo <tune.csv> --parameter <name>)
+              [--out <file.svg>]
+    mcts engine
+    mcts learn [--episodes <n>] [--table <file>]
+    mcts train [--games <n>] [--iterations <n>] [--buffer <n>] [--batch-size <n>]
+               [--out <dir>] [--jobs <n>] [--output <file.csv|file.json>]
+    mcts verify-engine [--iterations <n>]
+    mcts perft <depth> [--position <fen>]
+    mcts bench [<filter>] [--time <time>] [--save <file>] [--baseline <file>]
+    mcts census [--position <fen>] [--variant <name>]
+    mcts solve [--size <rows>x<cols>] [-k <n>]
+    mcts analyze [--position <fen>] [--movetime <time>] [--interactive]
+                 [--dump-tree <file|->] [--tree-depth <n>] [--engine <name>]
+    mcts heatmap [--position <fen>] [--visits] [--iterations <n>] [--theme <name>]
+    mcts replay <file> [--step]
+    mcts verify <file>
+    mcts report <file> [--format html|markdown] [--out <path>]
+    mcts share (<file> [--base <url>] | --decode <link|code> [--save <file>])
+    mcts export-svg (<file> | --position <fen>) [--out <path>] [--style <name>]
+                    [--size <pixels>]
+    mcts export-gif <file> [--out <path>] [--style <name>] [--size <pixels>]
+                    [--delay <time>]
+    mcts serve [--port <port>] [--grace <time>] [--max-connections <n>] [--max-rate <n>]
+               [--idle-timeout <time>] [--move-timeout <time>] [--metrics-port <port>]
+               [--data <dir>] [--admin-port <port>] [--keys <file>]
+    mcts admin [--port <port>] <command>
+    mcts chat-plays --channel <name> [--server <host:port>] [--nick <nick>]
+                    [--pass <password>] [--window <time>] [--ai-time <time>]
+                    [--clear] [--theme <name>]";
+
+/// Print an error along with the usage and exit
+pub fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    eprintln!("{}", USAGE);
+    process::exit(1);
+}
+
+/// Parse the value following an option, e.g. `--games 100`
+pub fn value<T: FromStr>(option: &str, value: Option<&String>) -> T {
+    match value.and_then(|v| v.parse().ok()) {
+        Some(value) => value,
+        None => fail(&format!("Invalid value for {}", option)),
+    }
+}
+
+/// Parse a duration following an option, e.g. `--movetime 5s` or `200ms`
+pub fn duration(option: &str, value: Option<&String>) -> Duration {
+    match value.and_then(|v| parse_duration(v)) {
+        Some(duration) => duration,
+        None => fail(&format!("Invalid value for {}", option)),
+    }
+}
+
+/// Parse a duration like `5s`, `200ms` or `1.5` (seconds)
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let (number, scale) = if let Some(ms) = value.strip_suffix("ms") {
+        (ms, 0.001)
+    } else {
+        (value.strip_suffix('s').unwrap_or(value), 1.)
+    };
+
+    match number.parse::<f64>().ok().map(|n| n * scale) {
+        Some(s) if s.is_finite() && s >= 0. => Some(Duration::from_millis((s * 1000.) as u64)),
+        _ => None,
+    }
+}
+
+/// Parse a position following an option, e.g. `--position x.o/.x./... o`
+pub fn position(value: Option<&String>) -> Board {
+    Board::from_fen(value.map(|s| s.as_str()).unwrap_or("")).unwrap_or_else(|e| fail(&e))
+}
+
+/// Parse a theme name following `--theme`
+pub fn theme(value: Option<&String>) -> Theme {
+    value.and_then(|name| Theme::by_name(name)).unwrap_or_else(|| {
+        fail(&format!("Unknown theme, available: {}", Theme::names().join(", ")))
+    })
+}
+
+/// Parse a style name following `--style`
+pub fn style(value: Option<&String>) -> Style {
+    value.and_then(|name| Style::by_name(name)).unwrap_or_else(|| {
+        fail(&format!("Unknown style, available: {}", Style::names().join(", ")))
+    })
+}
+
+/// Parse an engine name following `--engine`
+pub fn engine(value: Option<&String>) -> Engine {
+    value.and_then(|name| Engine::by_name(name)).unwrap_or_else(|| {
+        fail(&format!("Unknown engine, available: {}", Engine::names().join(", ")))
+    })
+}
+
+/// Parse what to do when a human's time runs out, following `--on-timeout`
+pub fn timeout(value: Option<&String>) -> Timeout {
+    value
+        .and_then(|name| Timeout::by_name(name))
+        .unwrap_or_else(|| fail("Unknown value for --on-timeout, available: random, hint, forfeit"))
+}
+
+/// Parse a variant name following `--variant`
+pub fn variant(value: Option<&String>) -> Variant {
+    value.and_then(|name| Variant::by_name(name)).unwrap_or_else(|| {
+        fail(&format!("Unknown variant, available: {}", Variant::names().join(", ")))
+    })
+}
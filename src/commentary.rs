@@ -0,0 +1,113 @@
+use game::{Board, Coord, GameStatus, Line, Player, Threat};
+
+/// Names of the lines used in commentary, by the indices of their cells
+const LINE_NAMES: [(&str, [usize; 3]); 8] = [
+    ("the top row", [0, 1, 2]),
+    ("the middle row", [3, 4, 5]),
+    ("the bottom row", [6, 7, 8]),
+    ("the left column", [0, 3, 6]),
+    ("the middle column", [1, 4, 7]),
+    ("the right column", [2, 5, 8]),
+    ("the diagonal", [0, 4, 8]),
+    ("the anti-diagonal", [2, 4, 6]),
+];
+
+fn line_name(line: Line) -> &'static str {
+    let cells = line.map(|cell| cell.index());
+
+    LINE_NAMES
+        .iter()
+        .find(|&&(_, l)| l == cells)
+        .map(|&(name, _)| name)
+        .unwrap_or("a line across the edge")
+}
+
+fn threats(board: &Board, player: Player) -> Vec<Threat> {
+    board.threats().into_iter().filter(|t| t.player == player).collect()
+}
+
+fn join(lines: &[&str]) -> String {
+    match lines.split_last() {
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+/// Describe the move `action` played on `board` in one sentence, e.g.
+/// "O blocks X's threat on the top row"
+pub fn describe(board: &Board, action: Coord) -> String {
+    let player = board.next_player();
+    let opponent = player.opponent();
+
+    let mut after = *board;
+    after.perform_action(action);
+
+    let own_before = threats(board, player);
+    let theirs_before = threats(board, opponent);
+    let own_after = threats(&after, player);
+
+    if let Some(threat) = own_before.iter().find(|t| t.square == action) {
+        let result = if after.status().winner() == Some(player) { "wins" } else { "loses" };
+        return format!("{} completes {} and {}", player, line_name(threat.line), result);
+    }
+
+    let mut parts = Vec::new();
+
+    // Variants where completing a line loses have no wins to miss
+    match own_before.first() {
+        Some(threat) if !board.immediate_wins(player).is_empty() => {
+            parts.push(format!("misses the win on {}", line_name(threat.line)))
+        }
+        _ => {}
+    }
+
+    let blocked: Vec<&str> = theirs_before
+        .iter()
+        .filter(|t| t.square == action)
+        .map(|t| line_name(t.line))
+        .collect();
+    let ignored: Vec<&str> = theirs_before
+        .iter()
+        .filter(|t| t.square != action)
+        .map(|t| line_name(t.line))
+        .collect();
+
+    if !blocked.is_empty() {
+        parts.push(format!("blocks {}'s threat on {}", opponent, join(&blocked)));
+    }
+
+    if !ignored.is_empty() && !board.immediate_wins(opponent).is_empty() {
+        parts.push(format!("leaves {} free to win on {}", opponent, join(&ignored)));
+    }
+
+    // Only threats created by this move, not the ones already on the board
+    let created: Vec<&str> = own_after
+        .iter()
+        .filter(|t| !own_before.iter().any(|b| b.line == t.line))
+        .map(|t| line_name(t.line))
+        .collect();
+
+    if after.immediate_wins(player).len() >= 2 && !created.is_empty() {
+        parts.push(format!("creates a double threat on {}", join(&created)));
+    } else if !created.is_empty() {
+        parts.push(format!("threatens {}", join(&created)));
+    }
+
+    if parts.is_empty() {
+        parts.push(match (action.row(), action.col()) {
+            (1, 1) => "takes the center".to_string(),
+            (0, 0) | (0, 2) | (2, 0) | (2, 2) => "takes a corner".to_string(),
+            _ => "takes an edge".to_string(),
+        });
+    }
+
+    let parts: Vec<&str> = parts.iter().map(|p| p.as_str()).collect();
+    let mut sentence = format!("{} {}", player, join(&parts));
+
+    if after.status() == GameStatus::Draw {
+        sentence.push_str(", the game is drawn");
+    }
+
+    sentence
+}
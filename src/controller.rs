@@ -0,0 +1,189 @@
+use std::time::Instant;
+use commentary;
+use events::{self, EventBus, GameEvent};
+use game::{Board, Coord, Player};
+use heatmap::Heatmap;
+use input::{Input, InputSource};
+use menu;
+use record::GameRecord;
+use render::Renderer;
+
+/// One side of a game, e.g. a human or an engine
+pub trait Participant {
+    /// The move to play on `board`, or `Input::Quit` to leave the game
+    fn next_move(&mut self, board: &Board, renderer: &mut dyn Renderer) -> Input;
+
+    /// A move was played on `board`, by either side
+    fn observe(&mut self, _board: &Board, _action: Coord) {}
+
+    /// Under the pie rule, whether to swap sides after the opponent's first
+    /// move on `board`
+    fn offer_swap(&mut self, _board: &Board, _renderer: &mut dyn Renderer) -> bool {
+        false
+    }
+
+    /// The sides were swapped, `board` being the position after the swap
+    fn observe_swap(&mut self, _board: &Board) {}
+}
+
+/// A human entering moves through an input source
+pub struct Human<'a> {
+    pub input: &'a mut dyn InputSource,
+}
+
+impl<'a> Participant for Human<'a> {
+    fn next_move(&mut self, board: &Board, _renderer: &mut dyn Renderer) -> Input {
+        self.input.next_input(board)
+    }
+
+    fn offer_swap(&mut self, _board: &Board, _renderer: &mut dyn Renderer) -> bool {
+        menu::confirm("Swap sides, taking over the first move?")
+    }
+}
+
+/// How a game played by a `GameController` ended
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Ending {
+    /// A participant left before the end
+    Quit,
+    /// The game was played out, `None` meaning a draw
+    Winner(Option<Player>),
+    /// The player gave up, their opponent winning
+    Forfeit(Player),
+}
+
+impl Ending {
+    /// The winner of the game, `None` on a draw or if it was left unfinished
+    pub fn winner(&self) -> Option<Player> {
+        match *self {
+            Ending::Quit => None,
+            Ending::Winner(winner) => winner,
+            Ending::Forfeit(player) => Some(player.opponent()),
+        }
+    }
+}
+
+/// The flow of a game, independent of who plays and how it is shown: asks
+/// the participants for their moves in turn, rejects illegal ones, tells
+/// both sides about every move and announces the result
+pub struct GameController<'a> {
+    renderer: &'a mut dyn Renderer,
+    /// Describe every move in a sentence
+    commentary: bool,
+    /// Let the second player swap sides after the first move
+    pie_rule: bool,
+    events: EventBus,
+    /// Draw the board with the value of every move, toggled by the players
+    heatmap: bool,
+}
+
+impl<'a> GameController<'a> {
+    pub fn new(
+        renderer: &'a mut dyn Renderer,
+        commentary: bool,
+        pie_rule: bool,
+        events: EventBus,
+    ) -> GameController<'a> {
+        GameController {
+            renderer,
+            commentary,
+            pie_rule,
+            events,
+            heatmap: false,
+        }
+    }
+
+    /// Show an event and pass it on to the subscribers
+    fn emit(&mut self, event: GameEvent) {
+        // Subscribers first, so whatever they keep track of is shown up to date
+        self.events.publish(&event);
+        events::render(&mut *self.renderer, &event);
+    }
+
+    /// Play the game in `record` to its end, adding the moves
+    pub fn play(
+        &mut self,
+        record: &mut GameRecord,
+        x: &mut dyn Participant,
+        o: &mut dyn Participant,
+    ) -> Ending {
+        let mut board = record.board();
+
+        while !board.is_ended() {
+            if self.pie_rule && record.moves.len() == 1 && !record.swapped {
+                self.renderer.draw_board(&board);
+
+                let player = board.next_player();
+                let swap = match player {
+                    Player::X => x.offer_swap(&board, &mut *self.renderer),
+                    Player::O => o.offer_swap(&board, &mut *self.renderer),
+                };
+
+                if swap {
+                    board.swap();
+                    record.swapped = true;
+                    x.observe_swap(&board);
+                    o.observe_swap(&board);
+                }
+
+                self.emit(GameEvent::SwapDecided {
+                    player,
+                    swapped: swap,
+                });
+                if swap {
+                    continue;
+                }
+            }
+
+            if self.heatmap {
+                self.events.publish(&GameEvent::TurnStarted { board });
+                self.renderer.draw_heatmap(&board, &Heatmap::exact(&board));
+                self.renderer.show_prompt(&board);
+            } else {
+                self.emit(GameEvent::TurnStarted { board });
+            }
+
+            let start = Instant::now();
+            let input = match board.next_player() {
+                Player::X => x.next_move(&board, &mut *self.renderer),
+                Player::O => o.next_move(&board, &mut *self.renderer),
+            };
+            let player = board.next_player();
+            let action = match input {
+                Input::Action(action) if board.is_legal_action(action) => action,
+                Input::Action(action) => {
+                    self.emit(GameEvent::IllegalMove { player, action });
+                    continue;
+                }
+                Input::Forfeit => return self.end(Ending::Forfeit(player), board),
+                Input::Quit => return self.end(Ending::Quit, board),
+                Input::Heatmap => {
+                    self.heatmap = !self.heatmap;
+                    continue;
+                }
+            };
+
+            if self.commentary {
+                self.emit(GameEvent::Commentary(commentary::describe(&board, action)));
+            }
+
+            x.observe(&board, action);
+            o.observe(&board, action);
+            board.perform_action(action);
+            record.push_timed(action, start.elapsed());
+
+            self.emit(GameEvent::MoveMade {
+                player,
+                action,
+                board,
+            });
+        }
+
+        self.end(Ending::Winner(board.status().winner()), board)
+    }
+
+    fn end(&mut self, ending: Ending, board: Board) -> Ending {
+        self.emit(GameEvent::GameEnded { ending, board });
+        ending
+    }
+}
@@ -0,0 +1,152 @@
+use base64;
+use cli;
+use game::{Board, Coord, Player};
+use heatmap::Heatmap;
+use input::{Input, InputSource, Stdin};
+use render::{Cli, Renderer};
+
+/// Version of the token format
+const TOKEN_VERSION: u8 = 1;
+
+/// Encode a game (its moves, X starting) into a short token that can be
+/// pasted into an email or chat
+pub fn encode(moves: &[Coord]) -> String {
+    let cells: Vec<u8> = moves.iter().map(|action| action.index() as u8).collect();
+
+    let mut data = vec![TOKEN_VERSION, checksum(&cells)];
+    data.extend(cells);
+
+    base64::encode(&data)
+}
+
+/// Decode a token, checking that it describes a legal game
+pub fn decode(token: &str) -> Result<Vec<Coord>, String> {
+    let data = base64::decode(token.trim()).ok_or("Token is not valid base64")?;
+
+    match data.first() {
+        Some(&TOKEN_VERSION) => {}
+        Some(version) => return Err(format!("Unsupported token version {}", version)),
+        None => return Err("Token is empty".to_string()),
+    }
+
+    if data.len() < 2 || data[1] != checksum(&data[2..]) {
+        return Err("Token is damaged (checksum mismatch)".to_string());
+    }
+
+    let moves = data[2..]
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            Coord::from_index(c as usize)
+                .ok_or_else(|| format!("Token contains an illegal move {}", i + 1))
+        })
+        .collect::<Result<Vec<Coord>, String>>()?;
+    replay(&moves).map(|_| moves)
+}
+
+/// Play the moves from the start position
+fn replay(moves: &[Coord]) -> Result<Board, String> {
+    let mut board = Board::new(Player::X);
+
+    for (i, &action) in moves.iter().enumerate() {
+        if board.is_ended() || !board.is_legal_action(action) {
+            return Err(format!("Token contains an illegal move {}", i + 1));
+        }
+
+        board.perform_action(action);
+    }
+
+    Ok(board)
+}
+
+/// Catches typos when copying tokens by hand
+fn checksum(cells: &[u8]) -> u8 {
+    cells.iter().fold(0x5a, |sum, &cell| sum.rotate_left(3) ^ cell)
+}
+
+/// Make one move in a correspondence game: read the opponent's token (or
+/// start a new game), ask for a move and print the token to send back
+pub fn run(args: &[String]) {
+    let mut moves = match args {
+        [] => Vec::new(),
+        [token] => decode(token).unwrap_or_else(|e| cli::fail(&e)),
+        _ => cli::fail("Expected a single token"),
+    };
+    let mut board = replay(&moves).expect("Decoded an illegal game");
+
+    board.print();
+
+    if let Some(&last) = moves.last() {
+        println!(
+            "Last move: {} by Player {}",
+            last,
+            board.next_player().opponent()
+        );
+    }
+
+    if board.is_ended() {
+        return Cli::plain().announce_result(&board);
+    }
+
+    println!("You play {}", board.next_player());
+
+    let action = loop {
+        match Stdin.next_input(&board) {
+            Input::Action(action) => break action,
+            Input::Forfeit | Input::Quit => return,
+            Input::Heatmap => Cli::plain().draw_heatmap(&board, &Heatmap::exact(&board)),
+        }
+    };
+    board.perform_action(action);
+    moves.push(action);
+
+    println!();
+    board.print();
+
+    if board.is_ended() {
+        Cli::plain().announce_result(&board);
+    }
+
+    println!("Send this token to your opponent: {}", encode(&moves));
+}
+
+#[cfg(test)]
+mod tests {
+    use base64;
+    use game::Coord;
+    use super::{checksum, decode, encode, TOKEN_VERSION};
+
+    fn moves(cells: &[&str]) -> Vec<Coord> {
+        cells.iter().map(|cell| cell.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn tokens_round_trip() {
+        for game in &[vec![], vec!["2b"], vec!["2b", "1a", "3c", "1c", "1b"]] {
+            let moves = moves(game);
+            assert_eq!(decode(&encode(&moves)), Ok(moves));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_tokens() {
+        assert_eq!(decode("a!"), Err("Token is not valid base64".to_string()));
+        assert_eq!(decode(""), Err("Token is empty".to_string()));
+        assert_eq!(
+            decode(&base64::encode(&[TOKEN_VERSION + 1, checksum(&[]), 4])),
+            Err(format!("Unsupported token version {}", TOKEN_VERSION + 1))
+        );
+        assert_eq!(
+            decode(&base64::encode(&[TOKEN_VERSION, checksum(&[4]), 3])),
+            Err("Token is damaged (checksum mismatch)".to_string())
+        );
+        assert_eq!(
+            decode(&base64::encode(&[TOKEN_VERSION, checksum(&[4, 9]), 4, 9])),
+            Err("Token contains an illegal move 2".to_string())
+        );
+        assert_eq!(
+            decode(&encode(&moves(&["2b", "2b"]))),
+            Err("Token contains an illegal move 2".to_string())
+        );
+    }
+}
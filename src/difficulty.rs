@@ -0,0 +1,103 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Number of recent games the adaptive difficulty looks at
+const HISTORY: usize = 4;
+
+/// The human's score we try to keep the games at (win = 1, draw = 0.5, loss = 0)
+const TARGET_SCORE: f64 = 0.5;
+
+/// Available AI strengths, from weakest to strongest
+const LEVELS: [Difficulty; 5] = [
+    Difficulty { think_time: Duration::from_millis(5), blunder_rate: 0.6 },
+    Difficulty { think_time: Duration::from_millis(10), blunder_rate: 0.4 },
+    Difficulty { think_time: Duration::from_millis(25), blunder_rate: 0.25 },
+    Difficulty { think_time: Duration::from_millis(50), blunder_rate: 0.1 },
+    Difficulty { think_time: Duration::from_millis(100), blunder_rate: 0. },
+];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Difficulty {
+    /// Time the AI may search before it has to move
+    pub think_time: Duration,
+    /// Probability of playing a random move instead of the searched one
+    pub blunder_rate: f64,
+}
+
+impl Difficulty {
+    pub fn strongest() -> Difficulty {
+        LEVELS[LEVELS.len() - 1]
+    }
+
+    /// The fixed strength of `level`, from 1 up to `levels()`
+    pub fn level(level: usize) -> Difficulty {
+        LEVELS[level - 1]
+    }
+
+    pub fn levels() -> usize {
+        LEVELS.len()
+    }
+}
+
+/// Result of a game from the human's point of view
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Outcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+impl Outcome {
+    fn score(&self) -> f64 {
+        match *self {
+            Outcome::Win => 1.,
+            Outcome::Draw => 0.5,
+            Outcome::Loss => 0.,
+        }
+    }
+}
+
+/// Scales the AI's strength so the human scores roughly `TARGET_SCORE`
+#[derive(Debug)]
+pub struct AdaptiveDifficulty {
+    level: usize,
+    results: VecDeque<Outcome>,
+}
+
+impl AdaptiveDifficulty {
+    pub fn new() -> AdaptiveDifficulty {
+        AdaptiveDifficulty {
+            level: LEVELS.len() / 2,
+            results: VecDeque::with_capacity(HISTORY),
+        }
+    }
+
+    pub fn level(&self) -> usize {
+        self.level + 1
+    }
+
+    pub fn max_level(&self) -> usize {
+        LEVELS.len()
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        LEVELS[self.level]
+    }
+
+    /// Record a finished game and move one level up or down if the human's
+    /// recent score is off target
+    pub fn record(&mut self, outcome: Outcome) {
+        if self.results.len() == HISTORY {
+            self.results.pop_front();
+        }
+        self.results.push_back(outcome);
+
+        let score = self.results.iter().map(|o| o.score()).sum::<f64>() / self.results.len() as f64;
+
+        if score > TARGET_SCORE && self.level < LEVELS.len() - 1 {
+            self.level += 1;
+        } else if score < TARGET_SCORE && self.level > 0 {
+            self.level -= 1;
+        }
+    }
+}
@@ -0,0 +1,32 @@
+/// The search algorithms the AI can play with
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Engine {
+    /// Monte Carlo tree search, improving the longer it thinks
+    Mcts,
+    /// Proof-number search, playing perfectly on small boards
+    Pns,
+    /// Memoized negamax over the shared evaluation cache
+    Negamax,
+    /// Perfect play looked up in a table built at compile time
+    Perfect,
+    /// Tabular Q-learning, playing what it learned from self-play
+    QLearning,
+}
+
+const ENGINES: [(&str, Engine); 5] = [
+    ("mcts", Engine::Mcts),
+    ("pns", Engine::Pns),
+    ("negamax", Engine::Negamax),
+    ("perfect", Engine::Perfect),
+    ("qlearning", Engine::QLearning),
+];
+
+impl Engine {
+    pub fn by_name(name: &str) -> Option<Engine> {
+        ENGINES.iter().find(|&&(n, _)| n == name).map(|&(_, engine)| engine)
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        ENGINES.iter().map(|&(name, _)| name).collect()
+    }
+}
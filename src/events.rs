@@ -0,0 +1,151 @@
+use std::fmt::{self, Display};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use controller::Ending;
+use game::{Board, Coord, Player};
+use render::Renderer;
+
+/// Something that happened in a game, published to everyone following it
+#[derive(Debug, Clone)]
+pub enum GameEvent {
+    /// The player to move on `board` is asked for a move
+    TurnStarted { board: Board },
+    /// `board` is the position after the move
+    MoveMade {
+        player: Player,
+        action: Coord,
+        board: Board,
+    },
+    IllegalMove { player: Player, action: Coord },
+    /// Under the pie rule, whether the second player took over the first move
+    SwapDecided { player: Player, swapped: bool },
+    /// A sentence describing the move about to be made
+    Commentary(String),
+    /// What an engine found before moving. The value is for the side to move,
+    /// if the engine reports one.
+    SearchUpdate {
+        iterations: u64,
+        best: Option<Coord>,
+        value: Option<f64>,
+    },
+    /// A player's clock was looked at, with `remaining` time for the move
+    ClockTick { player: Player, remaining: Duration },
+    GameEnded { ending: Ending, board: Board },
+}
+
+/// One line per event, as written to event logs
+impl Display for GameEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GameEvent::TurnStarted { ref board } => {
+                write!(f, "turn {} {}", board.next_player(), board.fen())
+            }
+            GameEvent::MoveMade {
+                player,
+                action,
+                ref board,
+            } => write!(f, "move {} {} {}", player, action, board.fen()),
+            GameEvent::IllegalMove { player, action } => {
+                write!(f, "illegal {} {}", player, action)
+            }
+            GameEvent::SwapDecided { player, swapped } => {
+                write!(f, "swap {} {}", player, if swapped { "yes" } else { "no" })
+            }
+            GameEvent::Commentary(ref text) => write!(f, "commentary {}", text),
+            GameEvent::SearchUpdate {
+                iterations,
+                best,
+                value,
+            } => {
+                let best = best.map_or("-".to_string(), |action| action.to_string());
+                let value = value.map_or("-".to_string(), |v| format!("{:+.2}", v));
+                write!(f, "search {} {} {}", iterations, best, value)
+            }
+            GameEvent::ClockTick { player, remaining } => {
+                write!(f, "clock {} {:.1}s", player, remaining.as_secs_f64())
+            }
+            GameEvent::GameEnded { ending, ref board } => match ending {
+                Ending::Quit => write!(f, "end quit {}", board.fen()),
+                Ending::Winner(Some(player)) => write!(f, "end {} {}", player, board.fen()),
+                Ending::Winner(None) => write!(f, "end draw {}", board.fen()),
+                Ending::Forfeit(player) => write!(f, "end forfeit {} {}", player, board.fen()),
+            },
+        }
+    }
+}
+
+/// Show an event at the terminal, the way the game loop always did
+pub fn render(renderer: &mut dyn Renderer, event: &GameEvent) {
+    match *event {
+        GameEvent::TurnStarted { ref board } => {
+            renderer.draw_board(board);
+            renderer.show_prompt(board);
+        }
+        GameEvent::MoveMade { .. } => renderer.show_stats(""),
+        GameEvent::IllegalMove { action, .. } => {
+            renderer.show_stats(&format!("Illegal action: {}", action))
+        }
+        GameEvent::SwapDecided { player, swapped: true } => renderer.show_stats(&format!(
+            "Player {} swaps, taking over the first move",
+            player
+        )),
+        GameEvent::SwapDecided { player, swapped: false } => {
+            renderer.show_stats(&format!("Player {} keeps their side", player))
+        }
+        GameEvent::Commentary(ref text) => renderer.show_stats(text),
+        GameEvent::GameEnded {
+            ending: Ending::Forfeit(player),
+            ..
+        } => renderer.show_stats(&format!(
+            "Player {} forfeits, Player {} wins",
+            player,
+            player.opponent()
+        )),
+        GameEvent::GameEnded {
+            ending: Ending::Winner(_),
+            ref board,
+        } => {
+            renderer.draw_board(board);
+            renderer.announce_result(board);
+        }
+        // Shown by whoever produces them, if at all
+        GameEvent::SearchUpdate { .. } | GameEvent::ClockTick { .. } => {}
+        GameEvent::GameEnded { ending: Ending::Quit, .. } => {}
+    }
+}
+
+type Listener = Box<dyn FnMut(&GameEvent) + Send>;
+
+/// Hands every published event to all subscribers. Clones share their
+/// subscribers, so any part of the program can publish.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    listeners: Arc<Mutex<Vec<Listener>>>,
+}
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EventBus({} listeners)", self.listeners.lock().unwrap().len())
+    }
+}
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        EventBus::default()
+    }
+
+    /// Call `listener` for every event from now on. Listeners must not
+    /// publish themselves.
+    pub fn subscribe<F>(&self, listener: F)
+    where
+        F: FnMut(&GameEvent) + Send + 'static,
+    {
+        self.listeners.lock().unwrap().push(Box::new(listener));
+    }
+
+    pub fn publish(&self, event: &GameEvent) {
+        for listener in self.listeners.lock().unwrap().iter_mut() {
+            listener(event);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! The engine protocol: a line-based text protocol over stdin and stdout,
+//! letting engines outside the crate play against the built-in ones.
+//!
+//! ```text
+//! > POSITION <fen>      The position to move in
+//! > GO <iterations>     Search, the budget being a hint
+//! < MOVE <move>         The engine's answer, e.g. `MOVE 2b`
+//! > QUIT                Exit
+//! ```
+//!
+//! An engine that doesn't understand a line answers `ERROR <message>`.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use game::{Board, Coord, Player};
+use mcts::{Config, Limit, StopToken, MCTS};
+
+/// An engine running as a child process
+pub struct External {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl External {
+    /// Start `command`, which is split into the executable and its arguments
+    /// at whitespace
+    pub fn spawn(command: &str) -> Result<External, String> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or("Empty engine command")?;
+
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Could not start {}: {}", program, e))?;
+
+        let stdin = child.stdin.take().expect("Engine without stdin");
+        let stdout = BufReader::new(child.stdout.take().expect("Engine without stdout"));
+
+        Ok(External {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    /// Ask the engine for its move on `board`. Fails if it exits, answers
+    /// something else than a move or picks an illegal one.
+    pub fn best_move(&mut self, board: &Board, iterations: u32) -> Result<Coord, String> {
+        writeln!(self.stdin, "POSITION {}", board.fen())
+            .and_then(|_| writeln!(self.stdin, "GO {}", iterations))
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| format!("Could not write to the engine: {}", e))?;
+
+        let mut line = String::new();
+        match self.stdout.read_line(&mut line) {
+            Ok(0) => return Err("Engine exited".to_string()),
+            Ok(_) => {}
+            Err(e) => return Err(format!("Could not read from the engine: {}", e)),
+        }
+
+        let action = match line.trim().split_once(' ') {
+            Some(("MOVE", action)) => action.parse().ok(),
+            Some(("ERROR", message)) => return Err(format!("Engine error: {}", message)),
+            _ => None,
+        };
+
+        match action {
+            Some(action) if board.is_legal_action(action) => Ok(action),
+            Some(action) => Err(format!("Illegal move {}", action)),
+            None => Err(format!("Unexpected answer: {}", line.trim())),
+        }
+    }
+}
+
+impl Drop for External {
+    fn drop(&mut self) {
+        // The engine may already be gone, nothing to do about it then
+        let _ = writeln!(self.stdin, "QUIT").and_then(|_| self.stdin.flush());
+        let _ = self.child.wait();
+    }
+}
+
+/// Speak the engine protocol on stdin and stdout, searching with MCTS
+pub fn run(_args: &[String]) {
+    let stdin = io::stdin();
+    let mut board = Board::new(Player::X);
+
+    for line in stdin.lock().lines() {
+        let line = line.expect("Could not read stdin");
+        let (command, argument) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+
+        match command {
+            "" => {}
+            "POSITION" => match Board::from_fen(argument) {
+                Ok(position) => board = position,
+                Err(e) => println!("ERROR {}", e),
+            },
+            "GO" if board.is_ended() => println!("ERROR Game is over"),
+            "GO" => match argument.parse() {
+                Ok(iterations) => {
+                    let mut engine =
+                        MCTS::from_board(board.next_player(), board, Config::default());
+                    let result = engine.search(Limit::Iterations(iterations), &StopToken::new());
+                    let action = result.action.expect("Unfinished game without moves");
+
+                    println!("MOVE {}", action);
+                }
+                Err(_) => println!("ERROR Expected the number of iterations"),
+            },
+            "QUIT" => break,
+            _ => println!("ERROR Unknown command: {}", command),
+        }
+
+        io::stdout().flush().unwrap();
+    }
+}
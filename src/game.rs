@@ -1,6 +1,11 @@
 use std::fmt::{self, Debug, Display};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use render::{self, Cli, Renderer};
+use rules::Variant;
+use theme;
 
-#[derive(Display, Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Display, Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum Player {
     X,
     O,
@@ -15,14 +20,310 @@ impl Player {
     }
 }
 
-#[derive(Copy, Clone)]
+/// A cell of the board. Only cells on the board can be made, so a `Coord`
+/// is always in range.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Coord {
+    row: u8,
+    col: u8,
+}
+
+impl Coord {
+    /// All cells, row by row
+    pub const ALL: [Coord; 9] = [
+        Coord { row: 0, col: 0 },
+        Coord { row: 0, col: 1 },
+        Coord { row: 0, col: 2 },
+        Coord { row: 1, col: 0 },
+        Coord { row: 1, col: 1 },
+        Coord { row: 1, col: 2 },
+        Coord { row: 2, col: 0 },
+        Coord { row: 2, col: 1 },
+        Coord { row: 2, col: 2 },
+    ];
+
+    /// The cell at `row` and `col`, counted from zero, if it's on the board
+    pub fn new(row: usize, col: usize) -> Option<Coord> {
+        if row < 3 && col < 3 {
+            Some(Coord {
+                row: row as u8,
+                col: col as u8,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The cell at `index` when counting row by row
+    pub fn from_index(index: usize) -> Option<Coord> {
+        Coord::ALL.get(index).cloned()
+    }
+
+    pub fn row(&self) -> usize {
+        self.row as usize
+    }
+
+    pub fn col(&self) -> usize {
+        self.col as usize
+    }
+
+    /// The position of the cell when counting row by row
+    pub fn index(&self) -> usize {
+        self.row() * 3 + self.col()
+    }
+}
+
+/// The way actions are entered, e.g. `1a` (row digit, then column letter)
+impl Display for Coord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Padded as a whole, so the action lines up in tables
+        f.pad(&format!("{}{}", (b'1' + self.row) as char, (b'a' + self.col) as char))
+    }
+}
+
+impl Debug for Coord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Coord({})", self)
+    }
+}
+
+impl FromStr for Coord {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Coord, String> {
+        let invalid = || format!("Invalid action: {}", input);
+
+        match *input.as_bytes() {
+            [row @ b'1'..=b'9', col @ b'a'..=b'z'] => {
+                Coord::new((row - b'1') as usize, (col - b'a') as usize).ok_or_else(invalid)
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Three cells in a row, column or diagonal
+pub type Line = [Coord; 3];
+
+/// The legal actions of a board, in the order of `Coord::ALL`. See
+/// `Board::actions_iter`.
+#[derive(Debug, Clone)]
+pub struct Actions {
+    /// Bit i is set while cell i of `Coord::ALL` is still to come
+    cells: u16,
+}
+
+impl Iterator for Actions {
+    type Item = Coord;
+
+    fn next(&mut self) -> Option<Coord> {
+        if self.cells == 0 {
+            return None;
+        }
+
+        let i = self.cells.trailing_zeros() as usize;
+        self.cells &= self.cells - 1;
+        Some(Coord::ALL[i])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.cells.count_ones() as usize;
+        (len, Some(len))
+    }
+}
+
+impl DoubleEndedIterator for Actions {
+    fn next_back(&mut self) -> Option<Coord> {
+        if self.cells == 0 {
+            return None;
+        }
+
+        let i = 15 - self.cells.leading_zeros() as usize;
+        self.cells &= !(1 << i);
+        Some(Coord::ALL[i])
+    }
+}
+
+impl ExactSizeIterator for Actions {}
+
+/// Where a game stands
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GameStatus {
+    InProgress,
+    Draw,
+    /// The winner and the completed line. When completing a line loses, the
+    /// winner is the other player.
+    Won(Player, Line),
+}
+
+impl GameStatus {
+    pub fn winner(&self) -> Option<Player> {
+        match *self {
+            GameStatus::Won(player, _) => Some(player),
+            GameStatus::InProgress | GameStatus::Draw => None,
+        }
+    }
+}
+
+/// The lines of the board, generated once by `lines`
+static LINES: OnceLock<Vec<Line>> = OnceLock::new();
+
+/// Directions a line can run in: horizontally, vertically and both diagonals
+const DIRECTIONS: [(i32, i32); 4] = [(0, 1), (1, 0), (1, 1), (1, -1)];
+
+/// Iterator over all lines of `k` cells on a `rows`×`cols` board, see `lines`
+pub struct Lines {
+    rows: i32,
+    cols: i32,
+    k: i32,
+    /// Start cell (as row * cols + col) and direction of the next candidate
+    cell: i32,
+    direction: usize,
+}
+
+impl Iterator for Lines {
+    type Item = Vec<(i32, i32)>;
+
+    fn next(&mut self) -> Option<Vec<(i32, i32)>> {
+        while self.cell < self.rows * self.cols {
+            let (row, col) = (self.cell / self.cols, self.cell % self.cols);
+            let (dr, dc) = DIRECTIONS[self.direction];
+
+            self.direction += 1;
+            if self.direction == DIRECTIONS.len() {
+                self.direction = 0;
+                self.cell += 1;
+            }
+
+            let (end_row, end_col) = (row + dr * (self.k - 1), col + dc * (self.k - 1));
+            if end_row < self.rows && end_col >= 0 && end_col < self.cols {
+                return Some((0..self.k).map(|i| (row + dr * i, col + dc * i)).collect());
+            }
+        }
+
+        None
+    }
+}
+
+/// All lines of `k` cells in a row, column or diagonal on a `rows`×`cols`
+/// board, each from its top (or left) end
+pub fn lines(rows: usize, cols: usize, k: usize) -> Lines {
+    Lines {
+        rows: rows as i32,
+        cols: cols as i32,
+        k: k as i32,
+        cell: 0,
+        direction: 0,
+    }
+}
+
+/// The eight lines of the standard board
+pub fn standard_lines() -> Vec<Line> {
+    let cell = |(row, col): (i32, i32)| Coord::new(row as usize, col as usize).unwrap();
+
+    lines(3, 3, 3).map(|l| [cell(l[0]), cell(l[1]), cell(l[2])]).collect()
+}
+
+/// A line a player can complete with one more move
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Threat {
+    pub player: Player,
+    pub line: Line,
+    /// The empty cell completing the line
+    pub square: Coord,
+}
+
+/// The eight ways to rotate and mirror the board onto itself
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Symmetry {
+    Identity,
+    /// Clockwise
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    /// Left to right
+    MirrorColumns,
+    /// Top to bottom
+    MirrorRows,
+    /// Along the diagonal from 1a to 3c
+    Transpose,
+    /// Along the diagonal from 1c to 3a
+    AntiTranspose,
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 8] = [
+        Symmetry::Identity,
+        Symmetry::Rotate90,
+        Symmetry::Rotate180,
+        Symmetry::Rotate270,
+        Symmetry::MirrorColumns,
+        Symmetry::MirrorRows,
+        Symmetry::Transpose,
+        Symmetry::AntiTranspose,
+    ];
+
+    /// Where the cell `action` ends up
+    pub fn apply(&self, action: Coord) -> Coord {
+        let (row, col) = (action.row, action.col);
+        let (row, col) = match *self {
+            Symmetry::Identity => (row, col),
+            Symmetry::Rotate90 => (col, 2 - row),
+            Symmetry::Rotate180 => (2 - row, 2 - col),
+            Symmetry::Rotate270 => (2 - col, row),
+            Symmetry::MirrorColumns => (row, 2 - col),
+            Symmetry::MirrorRows => (2 - row, col),
+            Symmetry::Transpose => (col, row),
+            Symmetry::AntiTranspose => (2 - col, 2 - row),
+        };
+
+        Coord { row, col }
+    }
+
+    /// The symmetry undoing this one
+    pub fn inverse(&self) -> Symmetry {
+        match *self {
+            Symmetry::Rotate90 => Symmetry::Rotate270,
+            Symmetry::Rotate270 => Symmetry::Rotate90,
+            other => other,
+        }
+    }
+}
+
+/// A cell that differs between two boards
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct CellChange {
+    pub cell: Coord,
+    pub from: Option<Player>,
+    pub to: Option<Player>,
+}
+
+/// The cell and what it holds now, like in a FEN: `2b x`, `1a .`
+impl Display for CellChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let to = match self.to {
+            Some(Player::X) => 'x',
+            Some(Player::O) => 'o',
+            None => '.',
+        };
+        write!(f, "{} {}", self.cell, to)
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
 pub struct Board {
     pub fields: [[Option<Player>; 3]; 3],
     pub next_player: Player,
+    pub variant: Variant,
 }
 
 impl Board {
     pub fn new(first_player: Player) -> Board {
+        Board::with_variant(first_player, Variant::STANDARD)
+    }
+
+    /// An empty board played by the rules of `variant`
+    pub fn with_variant(first_player: Player, variant: Variant) -> Board {
         Board {
             fields: [
                 [None, None, None],
@@ -30,129 +331,363 @@ impl Board {
                 [None, None, None]
             ],
             next_player: first_player,
+            variant,
         }
     }
 
-    pub fn next_player(&self) -> Player {
-        self.next_player
-    }
+    /// Parse a position like `x.o/.x./... o`: the cells row by row, followed
+    /// by the player to move. If the player is missing, X is assumed to have
+    /// started.
+    pub fn from_fen(fen: &str) -> Result<Board, String> {
+        let mut parts = fen.split_whitespace();
+        let rows: Vec<&str> = parts.next().unwrap_or("").split('/').collect();
 
-    pub fn is_ended(&self) -> bool {
-        if let Some(_) = self.get_winner() {
-            true
-        } else {
-            // All cells used: a draw (ended)
-            self.fields.iter().all(|row| {
-                row.iter().all(|cell| cell.is_some())
-            })
+        if rows.len() != 3 {
+            return Err(format!("Expected 3 rows, found {}", rows.len()));
         }
-    }
 
-    pub fn get_winner(&self) -> Option<Player> {
-        macro_rules! has {
-            ($player:expr, $x:expr, $y:expr) => {
-                self.fields[$x][$y] == Some(*$player)
-            };
-        }
+        let mut board = Board::new(Player::X);
+        let mut count = (0, 0);
 
-        for player in &[Player::X, Player::O] {
-            // Three in a row: horizontally
-            for row in 0..2 {
-                if has!(player, row, 0) && has!(player, row, 1) && has!(player, row, 2) {
-                    return Some(*player);
-                }
+        for (row, cells) in rows.iter().enumerate() {
+            if cells.chars().count() != 3 {
+                return Err(format!("Expected 3 cells in row {}", row + 1));
             }
 
-            // Three in a row: vertically
-            for col in 0..2 {
-                if has!(player, 0, col) && has!(player, 1, col) && has!(player, 2, col) {
-                    return Some(*player);
-                }
+            for (col, cell) in cells.chars().enumerate() {
+                board.fields[row][col] = match cell.to_ascii_lowercase() {
+                    'x' => {
+                        count.0 += 1;
+                        Some(Player::X)
+                    }
+                    'o' => {
+                        count.1 += 1;
+                        Some(Player::O)
+                    }
+                    '.' => None,
+                    _ => return Err(format!("Invalid cell: {}", cell)),
+                };
             }
+        }
 
-            // Three in a row: diagonally (top-left to bottom-right)
-            if has!(player, 0, 0) && has!(player, 1, 1) && has!(player, 2, 2) {
-                return Some(*player);
-            }
+        board.next_player = match parts.next() {
+            Some(player) if player.eq_ignore_ascii_case("x") => Player::X,
+            Some(player) if player.eq_ignore_ascii_case("o") => Player::O,
+            Some(player) => return Err(format!("Invalid player: {}", player)),
+            None if count.0 > count.1 => Player::O,
+            None => Player::X,
+        };
 
-            // Three in a row: diagonally (top-right to bottom-left)
-            if has!(player, 0, 2) && has!(player, 1, 1) && has!(player, 2, 0) {
-                return Some(*player);
+        if parts.next().is_some() {
+            return Err("Unexpected input after the player to move".to_string());
+        }
+
+        board.validate()?;
+        Ok(board)
+    }
+
+    /// Check that the position can come up in a game, with either player
+    /// having started: the players took turns, nobody moved after the game
+    /// was over and the player to move fits the move counts
+    pub fn validate(&self) -> Result<(), String> {
+        let cells = self.fields.iter().flat_map(|row| row.iter());
+        let x = cells.clone().filter(|&&c| c == Some(Player::X)).count();
+        let o = cells.filter(|&&c| c == Some(Player::O)).count();
+
+        // Whoever started has either made as many moves as the other player
+        // or one more, in which case it's the other player's turn
+        match (x as i32 - o as i32, self.next_player) {
+            (0, _) | (1, Player::O) | (-1, Player::X) => {}
+            (-1..=1, player) => {
+                return Err(format!("Player {} can't be to move with {} X and {} O", player, x, o))
             }
+            _ => return Err(format!("Players didn't take turns: {} X and {} O", x, o)),
         }
 
-        None
+        let completed: Vec<Line> = self
+            .lines()
+            .filter(|line| line.iter().all(|&c| self.cell(c).is_some()))
+            .filter(|line| line.iter().all(|&c| self.cell(c) == self.cell(line[0])))
+            .collect();
+
+        let completers: Vec<Player> =
+            completed.iter().filter_map(|line| self.cell(line[0])).collect();
+        let player = match completers.first() {
+            Some(&player) => player,
+            None => return Ok(()),
+        };
+
+        if completers.iter().any(|&p| p != player) {
+            return Err("Both players completed a line".to_string());
+        }
+
+        // The line was completed by the last move, so the other player is to
+        // move and all lines share that move's cell
+        if self.next_player == player {
+            return Err(format!("Player {} moved after the game was over", player.opponent()));
+        }
+
+        let last_move = completed[0]
+            .iter()
+            .any(|cell| completed.iter().all(|line| line.contains(cell)));
+        if !last_move {
+            return Err(format!("Player {} completed a line after the game was over", player));
+        }
+
+        Ok(())
+    }
+
+    /// The position in the notation read by `from_fen`
+    pub fn fen(&self) -> String {
+        let rows: Vec<String> = self
+            .fields
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| match *cell {
+                        Some(Player::X) => 'x',
+                        Some(Player::O) => 'o',
+                        None => '.',
+                    })
+                    .collect()
+            })
+            .collect();
+
+        format!("{} {}", rows.join("/"), self.next_player.to_string().to_lowercase())
+    }
+
+    pub fn next_player(&self) -> Player {
+        self.next_player
     }
 
-    pub fn is_legal_action(&self, action: (i32, i32)) -> bool {
-        if action.0 < 0 || action.0 > 2 || action.1 < 0 || action.1 > 2 {
-            return false;
+    /// Whether the game is won, drawn or still going, found in one pass
+    /// over the lines
+    pub fn status(&self) -> GameStatus {
+        let completed = self.lines().find_map(|line| match self.cell(line[0]) {
+            Some(player) if line.iter().all(|&c| self.cell(c) == Some(player)) => {
+                Some((player, line))
+            }
+            _ => None,
+        });
+
+        match completed {
+            Some((player, line)) if self.variant == Variant::STANDARD => {
+                GameStatus::Won(player, line)
+            }
+            Some((player, line)) => GameStatus::Won(self.variant.winner(player), line),
+            // All cells used: a draw
+            None if self.fields.iter().flatten().all(|cell| cell.is_some()) => GameStatus::Draw,
+            None => GameStatus::InProgress,
         }
+    }
+
+    pub fn is_ended(&self) -> bool {
+        self.status() != GameStatus::InProgress
+    }
+
+    /// All lines of three on the board
+    pub fn lines(&self) -> impl Iterator<Item = Line> {
+        // The variant lookup is skipped in the common case
+        let lines: &'static [Line] = if self.variant == Variant::STANDARD {
+            LINES.get_or_init(standard_lines)
+        } else {
+            self.variant.lines()
+        };
+
+        lines.iter().cloned()
+    }
+
+    pub fn cell(&self, cell: Coord) -> Option<Player> {
+        self.fields[cell.row()][cell.col()]
+    }
 
-        self.fields[action.0 as usize][action.1 as usize].is_none()
+    pub fn is_legal_action(&self, action: Coord) -> bool {
+        self.cell(action).is_none()
     }
 
-    pub fn perform_action(&mut self, action: (i32, i32)) {
+    pub fn perform_action(&mut self, action: Coord) {
         debug_assert!(self.is_legal_action(action));
 
         // Perform...
-        self.fields[action.0 as usize][action.1 as usize] = Some(self.next_player);
+        self.fields[action.row()][action.col()] = Some(self.next_player);
 
         // Next player's turn
         self.next_player = self.next_player.opponent();
     }
 
-    pub fn get_actions(&self) -> Vec<(i32, i32)> {
-        if self.is_ended() {
-            return Vec::new();
+    /// Put a stone of `player` on the empty cell `cell` outside the turns,
+    /// e.g. as a handicap, leaving the player to move as it is
+    pub fn place(&mut self, cell: Coord, player: Player) {
+        debug_assert!(self.cell(cell).is_none(), "Placing on an occupied cell");
+        self.fields[cell.row()][cell.col()] = Some(player);
+    }
+
+    /// The cells that differ on `other`, row by row, to update a view of
+    /// this board without redrawing all of it. The player to move isn't
+    /// compared.
+    pub fn diff(&self, other: &Board) -> Vec<CellChange> {
+        (0..9)
+            .filter_map(Coord::from_index)
+            .filter(|&cell| self.cell(cell) != other.cell(cell))
+            .map(|cell| CellChange {
+                cell,
+                from: self.cell(cell),
+                to: other.cell(cell),
+            })
+            .collect()
+    }
+
+    /// The pie rule's swap: every stone changes colour and the other player
+    /// moves, so the second player takes over the position the first player
+    /// made
+    pub fn swap(&mut self) {
+        for cell in self.fields.iter_mut().flat_map(|row| row.iter_mut()) {
+            *cell = cell.map(|player| player.opponent());
         }
 
-        let mut actions = Vec::with_capacity(9);
+        self.next_player = self.next_player.opponent();
+    }
 
-        // Calculate possible moves
-        for row in 0..3 {
-            for col in 0..3 {
-                if self.is_legal_action((row, col)) {
-                    actions.push((row, col));
-                }
-            }
+    /// The legal actions without allocating, none once the game is over
+    pub fn actions_iter(&self) -> Actions {
+        if self.is_ended() {
+            return Actions { cells: 0 };
         }
 
-        actions
+        let cells = Coord::ALL
+            .iter()
+            .filter(|&&cell| self.is_legal_action(cell))
+            .fold(0, |cells, cell| cells | 1 << cell.index());
+
+        Actions { cells }
+    }
+
+    /// The legal actions as a vector, e.g. to pick a random one
+    pub fn get_actions(&self) -> Vec<Coord> {
+        self.actions_iter().collect()
     }
 
     pub fn get_reward(&self, player: Player) -> Option<i32> {
-        if self.is_ended() {
-            if let Some(winner) = self.get_winner() {
-                if winner == player {
-                    return Some(1);
-                } else {
-                    return Some(-1);
+        match self.status() {
+            GameStatus::InProgress => None,
+            GameStatus::Draw => Some(0),
+            GameStatus::Won(winner, _) if winner == player => Some(1),
+            GameStatus::Won(..) => Some(-1),
+        }
+    }
+
+    /// The board rotated or mirrored by `symmetry`
+    pub fn transform(&self, symmetry: Symmetry) -> Board {
+        let mut board = *self;
+
+        for &cell in &Coord::ALL {
+            let target = symmetry.apply(cell);
+            board.fields[target.row()][target.col()] = self.cell(cell);
+        }
+
+        board
+    }
+
+    /// The board under each of the eight symmetries, in the order of
+    /// `Symmetry::ALL`. Symmetric boards may appear more than once.
+    pub fn symmetries(&self) -> [Board; 8] {
+        let mut boards = [*self; 8];
+
+        for (board, &symmetry) in boards.iter_mut().zip(&Symmetry::ALL) {
+            *board = self.transform(symmetry);
+        }
+
+        boards
+    }
+
+    /// The representative of all boards symmetric to this one, along with the
+    /// symmetry mapping this board onto it. Symmetric boards share the same
+    /// representative.
+    pub fn canonical(&self) -> (Board, Symmetry) {
+        self.symmetries()
+            .iter()
+            .zip(&Symmetry::ALL)
+            .map(|(&board, &symmetry)| (board, symmetry))
+            .min_by_key(|&(board, _)| board.key())
+            .expect("No symmetries")
+    }
+
+    /// The cells as a base-3 number, row by row with 0 for empty cells, 1
+    /// for X and 2 for O
+    pub fn key(&self) -> u32 {
+        self.fields.iter().flatten().fold(0, |key, cell| {
+            key * 3 + match *cell {
+                None => 0,
+                Some(Player::X) => 1,
+                Some(Player::O) => 2,
+            }
+        })
+    }
+
+    /// All lines either player could complete with their next move
+    pub fn threats(&self) -> Vec<Threat> {
+        let mut threats = Vec::new();
+
+        for line in self.lines() {
+            let cells: Vec<Option<Player>> = line.iter().map(|&c| self.cell(c)).collect();
+            let empty: Vec<Coord> = line
+                .iter()
+                .zip(&cells)
+                .filter(|&(_, cell)| cell.is_none())
+                .map(|(&square, _)| square)
+                .collect();
+
+            for &player in &[Player::X, Player::O] {
+                if let (2, &[square]) = (cells.iter().filter(|&&c| c == Some(player)).count(), &empty[..]) {
+                    threats.push(Threat { player, line, square });
                 }
-            } else {
-                return Some(0);
             }
         }
 
-        return None;
+        threats
     }
 
-    pub fn print(&self) {
-        println!("  a b c");
+    /// The cells where `player` would win right away, regardless of whose
+    /// turn it is
+    pub fn immediate_wins(&self, player: Player) -> Vec<Coord> {
+        // Where completing a line loses, nobody wins by it
+        if self.variant != Variant::STANDARD && self.variant.winner(player) != player {
+            return Vec::new();
+        }
 
-        for (i, row) in self.fields.iter().enumerate() {
-            print!("{} ", i + 1);
+        let mut squares: Vec<Coord> = self
+            .threats()
+            .into_iter()
+            .filter(|t| t.player == player)
+            .map(|t| t.square)
+            .collect();
 
-            for cell in row {
-                match *cell {
-                    Some(Player::X) => print!("x "),
-                    Some(Player::O) => print!("o "),
-                    None => print!(". "),
-                };
-            }
+        squares.sort();
+        squares.dedup();
+        squares
+    }
 
-            println!();
+    /// The cells where a move by `player` would leave at least two different
+    /// immediate wins, so that a single move can't block them all
+    pub fn forks(&self, player: Player) -> Vec<Coord> {
+        if self.is_ended() {
+            return Vec::new();
         }
+
+        self.actions_iter()
+            .filter(|&action| {
+                let mut board = *self;
+                board.next_player = player;
+                board.perform_action(action);
+
+                board.status().winner().is_none() && board.immediate_wins(player).len() >= 2
+            })
+            .collect()
+    }
+
+    /// Print the board in the plain theme
+    pub fn print(&self) {
+        Cli::plain().draw_board(self);
     }
 }
 
@@ -161,22 +696,54 @@ impl Debug for Board {
         writeln!(f, "Board {{")?;
         writeln!(f, "    Next player: {}", self.next_player)?;
         writeln!(f, "    Board:")?;
-        writeln!(f, "      a b c")?;
+        for line in render::board_lines(self, &theme::PLAIN) {
+            writeln!(f, "    {}", line)?;
+        }
 
-        for (i, row) in self.fields.iter().enumerate() {
-            write!(f, "    {} ", i + 1)?;
+        write!(f, "}}")
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::Board;
 
-            for cell in row {
-                match *cell {
-                    Some(Player::X) => write!(f, "x ")?,
-                    Some(Player::O) => write!(f, "o ")?,
-                    None => write!(f, ". ")?,
-                };
-            }
+    /// Parsing a position validates it
+    fn validate(fen: &str) -> Result<(), String> {
+        Board::from_fen(fen).map(|_| ())
+    }
 
-            writeln!(f)?;
-        }
+    #[test]
+    fn accepts_positions_from_a_game() {
+        assert_eq!(validate(".../.../... x"), Ok(()));
+        assert_eq!(validate("x../.../... o"), Ok(()));
+        // O may have started
+        assert_eq!(validate("o../.../... x"), Ok(()));
+        assert_eq!(validate("xxx/oo./... o"), Ok(()));
+        // One move completing two lines
+        assert_eq!(validate("xxx/xoo/xoo o"), Ok(()));
+    }
 
-        write!(f, "}}")
+    #[test]
+    fn rejects_players_not_taking_turns() {
+        assert_eq!(
+            validate("xx./.../... o"),
+            Err("Players didn't take turns: 2 X and 0 O".to_string())
+        );
+        assert_eq!(
+            validate("x../.../... x"),
+            Err("Player X can't be to move with 1 X and 0 O".to_string())
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn rejects_moves_after_the_game_is_over() {
+        assert_eq!(
+            validate("xxx/ooo/x.. o"),
+            Err("Both players completed a line".to_string())
+        );
+        assert_eq!(
+            validate("xxx/oo./o.. x"),
+            Err("Player O moved after the game was over".to_string())
+        );
+    }
+}
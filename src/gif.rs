@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::fs;
+use std::iter;
+use std::time::Duration;
+use cli;
+use game::{Board, Coord, Player};
+use record::GameRecord;
+use svg::{self, Style};
+
+/// Palette indices of the bitmap colors
+const BACKGROUND: u8 = 0;
+const GRID: u8 = 1;
+const X: u8 = 2;
+const O: u8 = 3;
+const HIGHLIGHT: u8 = 4;
+
+/// Bits per pixel of the palette, which holds 2^3 colors
+const COLOR_BITS: u8 = 3;
+/// Largest code of the GIF variant of LZW
+const MAX_CODE: u16 = 4095;
+
+/// Parse a color like `#d62728` into its red, green and blue components
+fn rgb(color: &str) -> [u8; 3] {
+    let value = u32::from_str_radix(color.trim_start_matches('#'), 16).unwrap_or(0);
+    [(value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
+
+/// Distance of `point` from the segment between `a` and `b`
+fn segment_distance(point: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let t = (((point.0 - a.0) * dx + (point.1 - a.1) * dy) / (dx * dx + dy * dy)).clamp(0., 1.);
+
+    (point.0 - a.0 - t * dx).hypot(point.1 - a.1 - t * dy)
+}
+
+/// Draw `board` as a bitmap of palette indices, row by row, looking like
+/// `svg::board` with the same size
+pub fn bitmap(board: &Board, size: u32, last_move: Option<Coord>) -> Vec<u8> {
+    let width = size * 3;
+    let stroke = f64::from((size / 20).max(1));
+    let margin = f64::from(size / 5);
+    let cell = f64::from(size);
+
+    let mut pixels = Vec::with_capacity((width * width) as usize);
+    for y in 0..width {
+        for x in 0..width {
+            let square = Coord::new((y / size) as usize, (x / size) as usize).unwrap();
+            // Position within the cell, measured at the pixel's center
+            let point = (f64::from(x % size) + 0.5, f64::from(y % size) + 0.5);
+            let on_grid = [x, y].iter().any(|&v| {
+                (1..3).any(|i| (f64::from(v) + 0.5 - f64::from(i * size)).abs() <= stroke / 2.)
+            });
+
+            let (low, high) = (margin, cell - margin);
+            let stone = match board.cell(square) {
+                Some(Player::X) => {
+                    let first = segment_distance(point, (low, low), (high, high));
+                    let second = segment_distance(point, (low, high), (high, low));
+                    first.min(second) <= stroke
+                }
+                Some(Player::O) => {
+                    let center = cell / 2.;
+                    let distance = (point.0 - center).hypot(point.1 - center);
+                    (distance - (center - margin)).abs() <= stroke
+                }
+                None => false,
+            };
+
+            pixels.push(match board.cell(square) {
+                _ if on_grid => GRID,
+                Some(Player::X) if stone => X,
+                Some(Player::O) if stone => O,
+                _ if last_move == Some(square) => HIGHLIGHT,
+                _ => BACKGROUND,
+            });
+        }
+    }
+
+    pixels
+}
+
+/// Packs variable-length codes into bytes, least significant bit first
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= u32::from(code) << self.bits;
+        self.bits += size;
+
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Compress palette indices with the LZW flavor of GIF
+fn lzw(pixels: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << COLOR_BITS;
+    let end = clear + 1;
+
+    let mut out = BitWriter::default();
+    let mut codes: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut size = COLOR_BITS + 1;
+    let mut next = end + 1;
+
+    out.write(clear, size);
+
+    let mut prefix = match pixels.first() {
+        Some(&pixel) => u16::from(pixel),
+        None => {
+            out.write(end, size);
+            return out.finish();
+        }
+    };
+
+    for &pixel in &pixels[1..] {
+        if let Some(&code) = codes.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+
+        out.write(prefix, size);
+        // The decoder learns about a code one step later, the code size
+        // grows once it could need the next one
+        if next > (1 << size) - 1 && size < 12 {
+            size += 1;
+        }
+
+        if next <= MAX_CODE {
+            codes.insert((prefix, pixel), next);
+            next += 1;
+        } else {
+            out.write(clear, size);
+            codes.clear();
+            size = COLOR_BITS + 1;
+            next = end + 1;
+        }
+
+        prefix = u16::from(pixel);
+    }
+
+    out.write(prefix, size);
+    if next > (1 << size) - 1 && size < 12 {
+        size += 1;
+    }
+    out.write(end, size);
+    out.finish()
+}
+
+/// Encode frames of palette indices as a looping animated GIF, showing each
+/// frame for its delay
+pub fn encode(width: u32, palette: &[[u8; 3]], frames: &[(Vec<u8>, Duration)]) -> Vec<u8> {
+    let mut gif = b"GIF89a".to_vec();
+
+    // Screen with a global palette of 2^COLOR_BITS colors
+    gif.extend(&(width as u16).to_le_bytes());
+    gif.extend(&(width as u16).to_le_bytes());
+    gif.extend(&[0xf0 | (COLOR_BITS - 1), BACKGROUND, 0]);
+    for i in 0..1 << COLOR_BITS {
+        gif.extend(palette.get(i).unwrap_or(&[0, 0, 0]));
+    }
+
+    // Loop forever
+    gif.extend(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+
+    for &(ref pixels, delay) in frames {
+        let centiseconds = (delay.as_millis() / 10).min(u128::from(u16::MAX)) as u16;
+        gif.extend(&[0x21, 0xf9, 0x04, 0x00]);
+        gif.extend(&centiseconds.to_le_bytes());
+        gif.extend(&[0x00, 0x00]);
+
+        gif.push(0x2c);
+        gif.extend(&[0, 0, 0, 0]);
+        gif.extend(&(width as u16).to_le_bytes());
+        gif.extend(&(width as u16).to_le_bytes());
+        gif.push(0);
+
+        gif.push(COLOR_BITS);
+        for block in lzw(pixels).chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend(block);
+        }
+        gif.push(0);
+    }
+
+    gif.push(0x3b);
+    gif
+}
+
+/// Export a saved game as an animated GIF, one frame per move with the last
+/// move highlighted
+pub fn run(args: &[String]) {
+    let mut file: Option<&String> = None;
+    let mut out = "game.gif".to_string();
+    let mut style = svg::LIGHT;
+    let mut size = 100;
+    let mut delay = Duration::from_secs(1);
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out = cli::value(arg, args.next()),
+            "--style" => style = cli::style(args.next()),
+            "--size" => size = cli::value(arg, args.next()),
+            "--delay" => delay = cli::duration(arg, args.next()),
+            _ if file.is_none() && !arg.starts_with("--") => file = Some(arg),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    let file = file.unwrap_or_else(|| cli::fail("Missing game file"));
+    if !(20..=5000).contains(&size) {
+        cli::fail("--size must be between 20 and 5000 pixels");
+    }
+
+    let record = GameRecord::load(file).unwrap_or_else(|e| cli::fail(&e));
+    let boards = record.boards();
+    let last_moves = iter::once(None).chain(record.moves.iter().map(|m| Some(m.action)));
+
+    let frames: Vec<(Vec<u8>, Duration)> = boards
+        .iter()
+        .zip(last_moves)
+        .enumerate()
+        .map(|(i, (board, last_move))| {
+            // Linger on the final position before looping
+            let delay = if i + 1 == boards.len() { delay * 3 } else { delay };
+            (bitmap(board, size, last_move), delay)
+        })
+        .collect();
+
+    let palette = palette(&style);
+    fs::write(&out, encode(size * 3, &palette, &frames))
+        .unwrap_or_else(|e| cli::fail(&format!("Could not write {}: {}", out, e)));
+    println!("Wrote {} frames to {}", frames.len(), out);
+}
+
+/// The colors of `style` by palette index
+fn palette(style: &Style) -> Vec<[u8; 3]> {
+    let mut palette = vec![[0; 3]; 5];
+    palette[BACKGROUND as usize] = rgb(style.background);
+    palette[GRID as usize] = rgb(style.grid);
+    palette[X as usize] = rgb(style.x);
+    palette[O as usize] = rgb(style.o);
+    palette[HIGHLIGHT as usize] = rgb(style.highlight);
+    palette
+}
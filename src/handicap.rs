@@ -0,0 +1,140 @@
+use std::fmt;
+use game::{Board, Coord, Player};
+
+/// Rating points a handicap stone is taken to be worth
+const STONE_ELO: f64 = 200.;
+/// Rating points of having no search at all on every move, scaled down for
+/// a partial budget or only some moves
+const BUDGET_ELO: f64 = 400.;
+/// Moves a side makes in a full game, at most
+const MOVES: f64 = 5.;
+
+/// Advantages evening out a match between sides of different strength: the
+/// weaker side holds some cells before the first move, the stronger side
+/// searches less on some of its moves
+#[derive(Debug, Clone, PartialEq)]
+pub struct Handicap {
+    /// Cells the weaker side holds from the start
+    pub stones: Vec<Coord>,
+    /// Share of its usual search the stronger side gets on `moves`
+    pub budget: f64,
+    /// The stronger side's moves with the smaller budget, counted from 1,
+    /// all of them if empty
+    pub moves: Vec<u32>,
+}
+
+impl Default for Handicap {
+    fn default() -> Handicap {
+        Handicap {
+            stones: Vec::new(),
+            budget: 1.,
+            moves: Vec::new(),
+        }
+    }
+}
+
+impl Handicap {
+    /// Parse a handicap like `stones=2b+1a,budget=0.5,moves=1+2`
+    pub fn parse(spec: &str) -> Result<Handicap, String> {
+        let mut handicap = Handicap::default();
+
+        for param in spec.split(',').filter(|p| !p.is_empty()) {
+            let (key, value) = param
+                .split_once('=')
+                .ok_or_else(|| format!("Expected <key>=<value>: {}", param))?;
+            let invalid = || format!("Invalid value for {}: {}", key, value);
+
+            match key {
+                "stones" => {
+                    handicap.stones = value
+                        .split('+')
+                        .map(|cell| cell.parse::<Coord>())
+                        .collect::<Result<_, _>>()?;
+                }
+                "budget" => {
+                    handicap.budget = value.parse().map_err(|_| invalid())?;
+                    if handicap.budget <= 0. || handicap.budget > 1. {
+                        return Err(invalid());
+                    }
+                }
+                "moves" => {
+                    handicap.moves = value
+                        .split('+')
+                        .map(|n| n.parse().ok().filter(|&n| n > 0).ok_or_else(invalid))
+                        .collect::<Result<_, _>>()?;
+                }
+                _ => return Err(format!("Unknown handicap parameter: {}", key)),
+            }
+        }
+
+        handicap.place(&mut Board::new(Player::X), Player::X)?;
+        Ok(handicap)
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.stones.is_empty() && self.budget == 1.
+    }
+
+    /// The share of its search the stronger side gets on its `number`th
+    /// move, counted from 1
+    pub fn budget(&self, number: u32) -> f64 {
+        if self.moves.is_empty() || self.moves.contains(&number) {
+            self.budget
+        } else {
+            1.
+        }
+    }
+
+    /// Put the stones on `board` for the weaker side `player`. The stones
+    /// must fit on empty cells without completing a line.
+    pub fn place(&self, board: &mut Board, player: Player) -> Result<(), String> {
+        for &stone in &self.stones {
+            if !board.is_legal_action(stone) {
+                return Err(format!("Handicap stone {} on an occupied cell", stone));
+            }
+            board.place(stone, player);
+        }
+
+        if board.is_ended() {
+            return Err("The handicap stones end the game".to_string());
+        }
+        Ok(())
+    }
+
+    /// Roughly what the handicap is worth in rating points for the weaker
+    /// side, so ratings can be compared across handicapped games
+    pub fn elo(&self) -> f64 {
+        let moves = if self.moves.is_empty() { MOVES } else { self.moves.len() as f64 };
+        let search = BUDGET_ELO * (1. - self.budget) * moves.min(MOVES) / MOVES;
+
+        STONE_ELO * self.stones.len() as f64 + search
+    }
+}
+
+/// E.g. `stones 2b 1a, 50% search on moves 1 2`
+impl fmt::Display for Handicap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+
+        if !self.stones.is_empty() {
+            let stones: Vec<String> = self.stones.iter().map(|s| s.to_string()).collect();
+            parts.push(format!("stones {}", stones.join(" ")));
+        }
+        if self.budget < 1. {
+            let moves = match self.moves.len() {
+                0 => "every move".to_string(),
+                _ => {
+                    let moves: Vec<String> = self.moves.iter().map(|m| m.to_string()).collect();
+                    format!("moves {}", moves.join(" "))
+                }
+            };
+            parts.push(format!("{:.0}% search on {}", self.budget * 100., moves));
+        }
+
+        if parts.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
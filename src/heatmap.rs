@@ -0,0 +1,195 @@
+use std::io::{self, IsTerminal};
+use cli;
+use game::{Board, Coord, GameStatus, Player};
+use mcts::{Config, Limit, StopToken, MCTS};
+use minimax;
+use terminal;
+use theme::{self, Theme};
+
+/// Background colors of a loss, a draw and a win
+const EXACT_COLORS: [u8; 3] = [160, 178, 34];
+/// Background colors from the least to the most visited moves
+const VISIT_COLORS: [u8; 5] = [24, 31, 37, 178, 202];
+
+/// What the cells of a heatmap are rated by
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Measure {
+    /// The game-theoretic value: -1 (loss), 0 (draw) or 1 (win)
+    Exact,
+    /// The share of the search's visits, from 0 to 1
+    Visits,
+}
+
+/// How good playing each empty cell is for the player to move
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heatmap {
+    pub measure: Measure,
+    pub cells: Vec<(Coord, f64)>,
+}
+
+impl Heatmap {
+    /// The exact value of every legal move
+    pub fn exact(board: &Board) -> Heatmap {
+        let mut cache = minimax::shared();
+        let cells = board
+            .actions_iter()
+            .map(|action| {
+                let mut child = *board;
+                child.perform_action(action);
+
+                (action, -f64::from(cache.value(&child)))
+            })
+            .collect();
+
+        Heatmap {
+            measure: Measure::Exact,
+            cells,
+        }
+    }
+
+    /// Each legal move's share of a search of `iterations` iterations
+    pub fn visits(board: &Board, iterations: u64) -> Heatmap {
+        let mut engine = MCTS::from_board(board.next_player(), *board, Config::default());
+        let stats = engine.search(Limit::Iterations(iterations), &StopToken::new()).stats;
+        let total = stats.iter().map(|s| s.visits).sum::<i32>().max(1);
+
+        let cells = board
+            .actions_iter()
+            .map(|action| {
+                let visits = stats.iter().find(|s| s.action == action).map_or(0, |s| s.visits);
+                (action, f64::from(visits) / f64::from(total))
+            })
+            .collect();
+
+        Heatmap {
+            measure: Measure::Visits,
+            cells,
+        }
+    }
+
+    fn heat(&self, action: Coord) -> Option<f64> {
+        self.cells.iter().find(|&&(a, _)| a == action).map(|&(_, heat)| heat)
+    }
+
+    /// A cell's heat in words, e.g. `win` or `42%`
+    pub fn label(&self, heat: f64) -> String {
+        match self.measure {
+            Measure::Exact if heat > 0. => "win".to_string(),
+            Measure::Exact if heat < 0. => "loss".to_string(),
+            Measure::Exact => "draw".to_string(),
+            Measure::Visits => format!("{:.0}%", heat * 100.),
+        }
+    }
+
+    /// A cell's heat in a single character: `+`, `=` or `-` for exact values,
+    /// tenths of the visits otherwise, on a background of its color
+    fn cell(&self, heat: f64, color: bool) -> String {
+        let (symbol, background) = match self.measure {
+            Measure::Exact => {
+                let index = (heat.round() as i32 + 1).clamp(0, 2) as usize;
+                (["-", "=", "+"][index].to_string(), EXACT_COLORS[index])
+            }
+            Measure::Visits => {
+                let tenths = ((heat * 10.) as u32).min(9);
+                let steps = VISIT_COLORS.len();
+                let index = ((heat * steps as f64) as usize).min(steps - 1);
+                (tenths.to_string(), VISIT_COLORS[index])
+            }
+        };
+
+        if color {
+            format!("\x1b[30;48;5;{}m{}\x1b[0m", background, symbol)
+        } else {
+            symbol
+        }
+    }
+
+    /// The board like `render::board_lines`, the empty cells showing their
+    /// heat instead, colored if `color` is set
+    pub fn lines(&self, board: &Board, theme: &Theme, color: bool) -> Vec<String> {
+        let mut lines = vec!["  a b c".to_string()];
+
+        for (i, row) in board.fields.iter().enumerate() {
+            let mut line = format!("{} ", i + 1);
+
+            for (j, &cell) in row.iter().enumerate() {
+                let heat = Coord::new(i, j).and_then(|action| self.heat(action));
+                match heat {
+                    Some(heat) if cell.is_none() => line.push_str(&self.cell(heat, color)),
+                    _ => line.push_str(theme.cell(cell)),
+                }
+                line.push(' ');
+            }
+
+            lines.push(line);
+        }
+
+        lines
+    }
+
+    /// What the cells show, for the player to move on `board`
+    pub fn legend(&self, board: &Board) -> String {
+        match self.measure {
+            Measure::Exact => {
+                format!("Moves for Player {}: + win, = draw, - loss", board.next_player())
+            }
+            Measure::Visits => format!(
+                "Moves for Player {}: tenths of the search's visits",
+                board.next_player()
+            ),
+        }
+    }
+
+    /// Every move with its heat, the best first, e.g. `2b win, 1a draw`
+    pub fn describe(&self) -> String {
+        let mut cells = self.cells.clone();
+        cells.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        cells
+            .iter()
+            .map(|&(action, heat)| format!("{} {}", action, self.label(heat)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Show how good every move in a position is
+pub fn run(args: &[String]) {
+    let mut board = Board::new(Player::X);
+    let mut measure = Measure::Exact;
+    let mut iterations = 10000;
+    let mut theme = theme::PLAIN;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--position" => board = cli::position(args.next()),
+            "--visits" => measure = Measure::Visits,
+            "--iterations" => iterations = cli::value(arg, args.next()),
+            "--theme" => theme = cli::theme(args.next()),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    if board.status() != GameStatus::InProgress {
+        cli::fail("The game is over, there are no moves to rate");
+    }
+
+    // Piped into a file, the symbols alone have to do
+    let color = io::stdout().is_terminal() && terminal::supports_ansi();
+    if !color {
+        theme = theme::PLAIN;
+    }
+
+    let heatmap = match measure {
+        Measure::Exact => Heatmap::exact(&board),
+        Measure::Visits => Heatmap::visits(&board, iterations),
+    };
+
+    for line in heatmap.lines(&board, &theme, color) {
+        println!("{}", line);
+    }
+    println!();
+    println!("{}", heatmap.legend(&board));
+    println!("{}", heatmap.describe());
+}
@@ -0,0 +1,291 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use rand::{self, Rng};
+use events::{EventBus, GameEvent};
+use game::{Board, Coord};
+use mcts;
+use menu;
+use table;
+
+/// What a player asks for on their turn
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Input {
+    /// A legal action on the board
+    Action(Coord),
+    /// Give up the game, the opponent winning it
+    Forfeit,
+    /// Leave the game
+    Quit,
+    /// Turn the heatmap of the moves on or off
+    Heatmap,
+}
+
+/// Where a player's moves come from: the terminal, a script, the network...
+pub trait InputSource {
+    fn next_input(&mut self, board: &Board) -> Input;
+}
+
+/// Lines of stdin, read on a thread of their own so waiting for one can time
+/// out. A line asked for but not yet typed when the wait timed out goes to
+/// the next reader.
+struct Lines {
+    requests: Sender<()>,
+    lines: Receiver<Option<String>>,
+    pending: bool,
+}
+
+static LINES: OnceLock<Mutex<Lines>> = OnceLock::new();
+
+/// The next line typed at the terminal, `Err(Timeout)` if none comes in
+/// `timeout` and `Ok(None)` once stdin is closed
+fn read_line_timeout(timeout: Option<Duration>) -> Result<Option<String>, RecvTimeoutError> {
+    let lines = LINES.get_or_init(|| {
+        let (requests, requested) = mpsc::channel::<()>();
+        let (send, lines) = mpsc::channel();
+
+        thread::spawn(move || {
+            for () in requested {
+                let mut line = String::new();
+                let line = match io::stdin().read_line(&mut line) {
+                    Ok(0) | Err(_) => None,
+                    Ok(_) => Some(line),
+                };
+
+                if send.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Mutex::new(Lines {
+            requests,
+            lines,
+            pending: false,
+        })
+    });
+    let mut lines = lines.lock().unwrap();
+
+    if !lines.pending {
+        lines.requests.send(()).expect("Stdin reader stopped");
+        lines.pending = true;
+    }
+
+    let line = match timeout {
+        Some(timeout) => lines.lines.recv_timeout(timeout),
+        None => lines.lines.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    };
+    if line.is_ok() {
+        lines.pending = false;
+    }
+
+    line
+}
+
+/// The next line typed at the terminal, `None` once stdin is closed. Use this
+/// instead of reading stdin directly, or lines may get lost to `Timed`.
+pub fn read_line() -> Option<String> {
+    read_line_timeout(None).unwrap_or(None)
+}
+
+/// Interpret a line entered on the player's turn, `None` if it doesn't ask
+/// for anything that can be done
+fn interpret(board: &Board, line: &str) -> Option<Input> {
+    let line = line.trim();
+
+    if line.is_empty() {
+        return None;
+    }
+
+    if line == "quit" {
+        return Some(Input::Quit);
+    }
+    if line == "heatmap" {
+        return Some(Input::Heatmap);
+    }
+
+    // Validate input
+    match line.parse::<Coord>().ok() {
+        Some(action) if board.is_legal_action(action) => Some(Input::Action(action)),
+        Some(_) => {
+            println!("Illegal action");
+            None
+        }
+        None => {
+            println!("Invalid action");
+            None
+        }
+    }
+}
+
+/// A human typing at the terminal. Closing stdin or entering `quit` leaves
+/// the game, `heatmap` shows how good every move is until entered again.
+pub struct Stdin;
+
+impl InputSource for Stdin {
+    fn next_input(&mut self, board: &Board) -> Input {
+        print!("Action [e.g. 1a]: ");
+        io::stdout().flush().unwrap();
+
+        loop {
+            // Read next player's action
+            let line = match read_line() {
+                Some(line) => line,
+                None => return Input::Quit,
+            };
+
+            if let Some(input) = interpret(board, &line) {
+                return input;
+            }
+
+            print!("> ");
+            io::stdout().flush().unwrap();
+        }
+    }
+}
+
+/// What happens when a human runs out of time for their move
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Timeout {
+    /// Play a random legal move
+    Random,
+    /// Play the move the rollout heuristic suggests, or a random one
+    Hint,
+    /// Lose the game
+    Forfeit,
+}
+
+impl Timeout {
+    pub fn by_name(name: &str) -> Option<Timeout> {
+        match name {
+            "random" => Some(Timeout::Random),
+            "hint" => Some(Timeout::Hint),
+            "forfeit" => Some(Timeout::Forfeit),
+            _ => None,
+        }
+    }
+}
+
+/// A human typing at the terminal with limited time for every move
+#[derive(Debug, Clone)]
+pub struct Timed {
+    pub movetime: Duration,
+    pub on_timeout: Timeout,
+    /// Where the clock is reported
+    pub events: EventBus,
+}
+
+impl InputSource for Timed {
+    fn next_input(&mut self, board: &Board) -> Input {
+        let deadline = Instant::now() + self.movetime;
+        let mut prompt = "Action [e.g. 1a]";
+
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            self.events.publish(&GameEvent::ClockTick {
+                player: board.next_player(),
+                remaining: left,
+            });
+            print!("{} ({}s left): ", prompt, left.as_secs_f64().ceil());
+            io::stdout().flush().unwrap();
+            prompt = ">";
+
+            match read_line_timeout(Some(left)) {
+                Ok(Some(line)) => {
+                    if let Some(input) = interpret(board, &line) {
+                        return input;
+                    }
+                }
+                Ok(None) | Err(RecvTimeoutError::Disconnected) => return Input::Quit,
+                Err(RecvTimeoutError::Timeout) => break,
+            }
+        }
+
+        println!();
+        let random = *rand::thread_rng()
+            .choose(&board.get_actions())
+            .expect("No actions in a running game");
+        let action = match self.on_timeout {
+            Timeout::Random => random,
+            Timeout::Hint => mcts::heuristic_action(board).unwrap_or(random),
+            Timeout::Forfeit => {
+                println!("Time is up");
+                return Input::Forfeit;
+            }
+        };
+
+        println!("Time is up, playing {}", action);
+        Input::Action(action)
+    }
+}
+
+/// A list of moves played in order, e.g. an opening to start from. Once
+/// the moves run out, or one doesn't fit the board, `fallback` takes over.
+pub struct Script {
+    moves: VecDeque<Coord>,
+    fallback: Box<dyn InputSource>,
+}
+
+impl Script {
+    /// Parse moves separated by commas or spaces, e.g. `2b,1a`
+    pub fn parse(moves: &str, fallback: Box<dyn InputSource>) -> Result<Script, String> {
+        let moves = moves
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|m| !m.is_empty())
+            .map(|m| m.parse::<Coord>())
+            .collect::<Result<_, _>>()?;
+
+        Ok(Script { moves, fallback })
+    }
+}
+
+impl InputSource for Script {
+    fn next_input(&mut self, board: &Board) -> Input {
+        match self.moves.pop_front() {
+            Some(action) if board.is_legal_action(action) => {
+                println!("Action: {}", action);
+                Input::Action(action)
+            }
+            Some(action) => {
+                println!("Scripted action {} is illegal", action);
+                self.moves.clear();
+                self.fallback.next_input(board)
+            }
+            None => self.fallback.next_input(board),
+        }
+    }
+}
+
+/// Training for `input`'s player: a move that throws away a won or drawn
+/// position, losing against perfect play, has to be confirmed
+pub struct BlunderAlert {
+    pub input: Box<dyn InputSource>,
+}
+
+/// Whether playing `action` turns `board` into a loss for the player to move
+fn blunders(board: &Board, action: Coord) -> bool {
+    let mut child = *board;
+    child.perform_action(action);
+
+    table::perfect(board).0 > -1 && table::perfect(&child).0 == 1
+}
+
+impl InputSource for BlunderAlert {
+    fn next_input(&mut self, board: &Board) -> Input {
+        loop {
+            let input = self.input.next_input(board);
+            let action = match input {
+                Input::Action(action) => action,
+                _ => return input,
+            };
+
+            let question = format!("{} loses against perfect play. Play it anyway?", action);
+            if !blunders(board, action) || menu::confirm(&question) {
+                return input;
+            }
+        }
+    }
+}
@@ -1,115 +1,969 @@
-#![feature(vec_remove_item)]
-
 #[macro_use]
 extern crate enum_display_derive;
 extern crate rand;
 
-use std::{char, thread, time};
-use std::io::{self, Write};
-use std::sync::{Arc, Mutex};
-use std::sync::mpsc::channel;
+use std::{cmp, env, fs, thread};
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Sender, TryRecvError};
+use std::time::{Duration, Instant};
+use rand::Rng;
 
+mod analyze;
+mod base64;
+mod bench;
+mod census;
+mod chatplays;
+mod cli;
+mod commentary;
+mod controller;
+mod correspondence;
+mod difficulty;
+mod engine;
+mod events;
+mod external;
 mod game;
+mod gif;
+mod handicap;
+mod heatmap;
+mod input;
 mod mcts;
+mod metrics;
+mod menu;
+mod minimax;
+mod openings;
+mod parallel;
+mod perft;
+mod plot;
+mod pns;
+mod qlearning;
+mod record;
+mod render;
+mod replay;
+mod report;
+mod results;
+mod rules;
+mod scoreboard;
+mod selfplay;
+mod server;
+mod share;
+mod simulate;
+mod solve;
+mod storage;
+mod svg;
+mod table;
+mod terminal;
+mod theme;
+mod tournament;
+mod train;
+mod tune;
+mod verify;
+
+use controller::{Ending, GameController, Human, Participant};
+use difficulty::{AdaptiveDifficulty, Difficulty, Outcome};
+use engine::Engine;
+use events::{EventBus, GameEvent};
+use input::{BlunderAlert, Input, InputSource, Script, Stdin, Timed, Timeout};
+use game::{Board, Coord, Player};
+use handicap::Handicap;
+use mcts::{ActionStats, Config, Limit, Observer, Outlook, StopToken, MCTS};
+use minimax::Exact;
+use qlearning::QTable;
+use record::GameRecord;
+use rules::Variant;
+use render::Renderer;
+use scoreboard::Scoreboard;
+use theme::Theme;
 
 const PLAYER_USER: game::Player = game::Player::X;
 const PLAYER_AI: game::Player = game::Player::O;
-const START_PLAYER: game::Player = PLAYER_USER;
+
+/// ANSI escape sequence clearing the terminal and moving the cursor home
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// Width of the thinking progress bar (in characters)
+const PROGRESS_WIDTH: usize = 20;
+/// How often the thinking progress bar is updated
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(20);
 
 
 fn main() {
-    let mut board = game::Board::new(START_PLAYER);
-    let ai_action = Arc::new(Mutex::new(None));
-    let (tx, rx) = channel();
+    let args: Vec<String> = env::args().skip(1).collect();
+    rules::register_builtins();
+    openings::load();
 
-    let _ai_action = ai_action.clone();
+    match args.first().map(|arg| arg.as_str()) {
+        Some("tune") => tune::run(&args[1..]),
+        Some("tournament") => tournament::run(&args[1..]),
+        Some("simulate") => simulate::run(&args[1..]),
+        Some("plot") => plot::run(&args[1..]),
+        Some("engine") => external::run(&args[1..]),
+        Some("learn") => qlearning::run(&args[1..]),
+        Some("train") => train::run(&args[1..]),
+        Some("verify-engine") => verify::run(&args[1..]),
+        Some("verify") => replay::verify(&args[1..]),
+        Some("perft") => perft::run(&args[1..]),
+        Some("bench") => bench::run(&args[1..]),
+        Some("census") => census::run(&args[1..]),
+        Some("solve") => solve::run(&args[1..]),
+        Some("analyze") => analyze::run(&args[1..]),
+        Some("heatmap") => heatmap::run(&args[1..]),
+        Some("replay") => replay::run(&args[1..]),
+        Some("export-svg") => svg::run(&args[1..]),
+        Some("export-gif") => gif::run(&args[1..]),
+        Some("report") => report::run(&args[1..]),
+        Some("share") => share::run(&args[1..]),
+        Some("serve") => server::run(&args[1..]),
+        Some("admin") => server::admin(&args[1..]),
+        Some("chat-plays") => chatplays::run(&args[1..]),
+        Some("--async-move") => correspondence::run(&args[1..]),
+        _ => play_session(&args),
+    }
+}
 
-    thread::spawn(move || {
-        let mut ai = mcts::MCTS::new(PLAYER_AI, PLAYER_AI == START_PLAYER);
+/// How games against the AI are shown
+struct Options {
+    /// Redraw the terminal every turn instead of scrolling
+    clear_screen: bool,
+    theme: Theme,
+    /// Spell out the board for screen readers
+    accessible: bool,
+    /// Describe every move in a sentence
+    commentary: bool,
+    /// Explain the AI's moves from its search statistics
+    explain: bool,
+    /// Show how the engine rates the position as a bar
+    eval_bar: bool,
+    engine: Engine,
+    /// Search parameters of the MCTS engine
+    config: Config,
+    /// Let the AI open with any of the equally good moves at random
+    random_opening: bool,
+    /// What the Q-learning engine has learned, empty with other engines
+    q_table: QTable,
+    /// Limits the time humans have for their moves
+    timer: Option<Timed>,
+    /// Let the second player swap sides after the first move
+    pie_rule: bool,
+    /// Ask humans to confirm moves that lose against perfect play
+    blunder_alert: bool,
+    /// Evens out games against the AI, the human being the weaker side
+    handicap: Handicap,
+    variant: Variant,
+    /// Everything that happens in the games, for whoever subscribes
+    events: EventBus,
+    /// The session's results, following `events`
+    scoreboard: Arc<Mutex<Scoreboard>>,
+}
 
-        loop {
-            // Process player action
-            if let Ok(action) = rx.try_recv() {
-                ai.perform_action(action);
-            }
+/// What the AI has found so far in its background search
+#[derive(Default)]
+struct Search {
+    action: Option<Coord>,
+    /// Root statistics, only kept up to date when explaining moves
+    stats: Vec<ActionStats>,
+    evaluation: f64,
+    /// The exact value once the AI has searched the whole game tree
+    proven: Option<Exact>,
+    /// Chances for the AI, only kept up to date in the background for the
+    /// evaluation bar
+    outlook: Option<Outlook>,
+}
+
+/// Passes the progress of the AI's search on to the game loop
+struct Progress {
+    iterations: Arc<AtomicUsize>,
+    search: Arc<Mutex<Search>>,
+}
+
+impl Observer for Progress {
+    fn on_iteration(&mut self, _iterations: u64) {
+        self.iterations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_best_move_change(&mut self, action: Coord) {
+        self.search.lock().unwrap().action = Some(action);
+    }
+}
 
-            // Perform MCTS
-            ai.run();
+/// Play games against the AI until the human has enough
+fn play_session(args: &[String]) {
+    // Without any options, let the user set up the games
+    let menu = args.is_empty() && io::stdin().is_terminal();
+    let mut adaptive = true;
+    let events = EventBus::new();
+    let mut options = Options {
+        clear_screen: false,
+        theme: theme::PLAIN,
+        accessible: false,
+        commentary: false,
+        explain: false,
+        eval_bar: false,
+        engine: Engine::Mcts,
+        config: Config::default(),
+        random_opening: false,
+        q_table: QTable::new(),
+        timer: None,
+        pie_rule: false,
+        blunder_alert: false,
+        handicap: Handicap::default(),
+        variant: Variant::STANDARD,
+        scoreboard: Scoreboard::follow(&events),
+        events,
+    };
+    let mut save: Option<String> = None;
+    let mut moves: Option<String> = None;
+    let mut movetime = None;
+    let mut on_timeout = Timeout::Hint;
+    let mut q_table = qlearning::DEFAULT_TABLE.to_string();
+    let mut event_log: Option<String> = None;
+    let mut first = First::Me;
 
-            // Update action
-            let mut action = _ai_action.lock().unwrap();
-            *action = ai.get_action();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--no-adaptive" => adaptive = false,
+            "--clear" => options.clear_screen = true,
+            "--theme" => options.theme = cli::theme(args.next()),
+            "--accessible" => options.accessible = true,
+            "--commentary" => options.commentary = true,
+            "--pie-rule" => options.pie_rule = true,
+            "--blunder-alert" => options.blunder_alert = true,
+            "--variant" => options.variant = cli::variant(args.next()),
+            "--handicap" => {
+                let spec: String = cli::value(arg, args.next());
+                options.handicap = Handicap::parse(&spec).unwrap_or_else(|e| cli::fail(&e));
+            }
+            "--explain" => options.explain = true,
+            "--eval-bar" => options.eval_bar = true,
+            "--save" => save = Some(cli::value(arg, args.next())),
+            "--moves" => moves = Some(cli::value(arg, args.next())),
+            "--human-movetime" => movetime = Some(cli::duration(arg, args.next())),
+            "--on-timeout" => on_timeout = cli::timeout(args.next()),
+            "--engine" => options.engine = cli::engine(args.next()),
+            "--random-opening" => options.random_opening = true,
+            "--first" => {
+                let name: String = cli::value(arg, args.next());
+                first = First::by_name(&name).unwrap_or_else(|| {
+                    cli::fail("Unknown value for --first, available: me, ai, random, alternate")
+                });
+            }
+            "--discount" => {
+                options.config.discount = cli::value(arg, args.next());
+                if options.config.discount <= 0. || options.config.discount > 1. {
+                    cli::fail("--discount must be above 0 and at most 1");
+                }
+            }
+            "--q-table" => q_table = cli::value(arg, args.next()),
+            "--log-events" => event_log = Some(cli::value(arg, args.next())),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
         }
+    }
+
+    if let Some(path) = event_log {
+        let mut file = fs::File::create(&path)
+            .unwrap_or_else(|e| cli::fail(&format!("Could not create {}: {}", path, e)));
+        let start = Instant::now();
+
+        options.events.subscribe(move |event| {
+            // A full disk shouldn't end the game, the log is only a record
+            let _ = writeln!(file, "{:.3} {}", start.elapsed().as_secs_f64(), event);
+        });
+    }
+
+    options.timer = movetime.map(|movetime| Timed {
+        movetime,
+        on_timeout,
+        events: options.events.clone(),
     });
 
-    while !board.is_ended() {
-        board.print();
-        let next_player = board.next_player();
+    let mut human = human_input(&options);
+    if let Some(moves) = moves {
+        human = Box::new(Script::parse(&moves, human).unwrap_or_else(|e| cli::fail(&e)));
+    }
 
-        println!("Turn: {}", next_player);
-        let mut action;
+    if options.engine == Engine::QLearning {
+        options.q_table = QTable::load_or_learn(&q_table).unwrap_or_else(|e| cli::fail(&e));
+    }
 
-        if next_player == PLAYER_USER {
-            print!("Action [e.g. 1a]: ");
-            io::stdout().flush().unwrap();
+    // Screen readers read the output as it scrolls by
+    if options.accessible {
+        options.clear_screen = false;
+    }
 
-            loop {
-                // Read next player's action
-                let mut player_action = String::new();
-                io::stdin().read_line(&mut player_action).unwrap();
+    // Fall back to plain output on terminals without escape sequences
+    if (options.clear_screen || options.theme != theme::PLAIN) && !terminal::supports_ansi() {
+        options.clear_screen = false;
+        options.theme = theme::PLAIN;
+    }
+
+    let mut session = Session {
+        options,
+        menu,
+        mode: Mode::Ai,
+        adaptive: if adaptive { Some(AdaptiveDifficulty::new()) } else { None },
+        difficulty: Difficulty::strongest(),
+        save,
+        human,
+        first,
+        last_start: None,
+    };
+    session.run();
+}
+
+/// The stages of a session of games against the AI
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum SessionState {
+    /// Starting up, before the first game
+    MainMenu,
+    /// Picking the settings of the next game
+    Configuring,
+    Playing(Difficulty),
+    GameOver(Outcome),
+    /// Asking whether to play another game
+    Rematch,
+    Quit,
+}
+
+/// Who plays the games of a session
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Mode {
+    /// The human against the AI
+    Ai,
+    /// Two humans taking turns at the same terminal
+    TwoPlayers,
+}
+
+/// Who starts the games of a session
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum First {
+    Me,
+    Ai,
+    /// A coin flip before every game
+    Random,
+    /// Taking turns, the human starting the first game
+    Alternate,
+}
+
+impl First {
+    fn by_name(name: &str) -> Option<First> {
+        match name {
+            "me" => Some(First::Me),
+            "ai" => Some(First::Ai),
+            "random" => Some(First::Random),
+            "alternate" => Some(First::Alternate),
+            _ => None,
+        }
+    }
+}
+
+/// Everything a session carries from one game to the next
+struct Session {
+    options: Options,
+    /// Set up the games in the main menu
+    menu: bool,
+    mode: Mode,
+    /// Keeps the games close, `None` to play at the fixed `difficulty`
+    adaptive: Option<AdaptiveDifficulty>,
+    difficulty: Difficulty,
+    /// Where to save the last game
+    save: Option<String>,
+    human: Box<dyn InputSource>,
+    first: First,
+    /// Who started the last game, for alternating
+    last_start: Option<game::Player>,
+}
+
+impl Session {
+    fn run(&mut self) {
+        let mut state = SessionState::MainMenu;
+
+        while state != SessionState::Quit {
+            state = self.step(state);
+        }
+    }
+
+    /// Do what `state` is about and move on to the next state
+    fn step(&mut self, state: SessionState) -> SessionState {
+        match state {
+            SessionState::MainMenu => {
+                if self.menu {
+                    self.main_menu();
+                }
+                SessionState::Configuring
+            }
+            SessionState::Configuring => SessionState::Playing(self.difficulty()),
+            SessionState::Playing(difficulty) => {
+                let variant = self.options.variant;
+                if variant != Variant::STANDARD {
+                    println!("Variant: {} ({})", variant.name(), variant.description());
+                }
 
-                let player_action = player_action.trim();
+                let start = self.start_player();
+                if self.first != First::Me {
+                    println!("Player {} starts", start);
+                }
 
-                if player_action.is_empty() {
-                    print!("> ");
-                    io::stdout().flush().unwrap();
-                    continue;
+                let mut record = GameRecord::new(Board::with_variant(start, variant));
+                let handicap = &self.options.handicap;
+                if self.mode == Mode::Ai && !handicap.is_none() {
+                    println!("Handicap: {}", handicap);
+                    if !handicap.stones.is_empty() {
+                        record.stones = Some((PLAYER_USER, handicap.stones.clone()));
+                    }
                 }
 
-                // Validate input
-                if player_action.len() != 2 {
-                    println!("Invalid action");
-                    print!("> ");
-                    io::stdout().flush().unwrap();
-                    continue;
+                let outcome = match self.mode {
+                    Mode::Ai => play(difficulty, &self.options, self.human.as_mut(), &mut record),
+                    Mode::TwoPlayers => play_two_players(&self.options, &mut record),
                 };
 
-                let mut chars = player_action.chars();
+                if outcome.is_some() {
+                    replay::print_thinking_times(&record);
+                }
 
-                let row = chars.next().unwrap() as i32 - '1' as i32;
-                let col = chars.next().unwrap() as i32 - 'a' as i32;
+                // Keep the last game
+                if let Some(ref file) = self.save {
+                    record.save(file).unwrap_or_else(|e| eprintln!("{}", e));
+                }
 
-                action = (row, col);
+                match outcome {
+                    Some(outcome) => SessionState::GameOver(outcome),
+                    None => SessionState::Quit,
+                }
+            }
+            SessionState::GameOver(outcome) => {
+                if let Some(ref mut adaptive) = self.adaptive {
+                    adaptive.record(outcome);
+                }
+                SessionState::Rematch
+            }
+            SessionState::Rematch if menu::confirm("Play again?") => SessionState::Configuring,
+            SessionState::Rematch | SessionState::Quit => SessionState::Quit,
+        }
+    }
 
-                if !board.is_legal_action(action) {
-                    println!("Illegal action");
-                    print!("> ");
-                    io::stdout().flush().unwrap();
-                    continue;
-                };
+    /// Who starts the next game, the human playing X and the AI O
+    fn start_player(&mut self) -> game::Player {
+        let player = match self.first {
+            First::Me => PLAYER_USER,
+            First::Ai => PLAYER_AI,
+            First::Random if rand::random() => PLAYER_USER,
+            First::Random => PLAYER_AI,
+            First::Alternate => self.last_start.map_or(PLAYER_USER, |last| last.opponent()),
+        };
 
-                break;
+        self.last_start = Some(player);
+        player
+    }
+
+    fn difficulty(&self) -> Difficulty {
+        match self.adaptive {
+            Some(ref adaptive) if self.mode == Mode::Ai => {
+                println!("AI level: {}/{}", adaptive.level(), adaptive.max_level());
+                adaptive.difficulty()
             }
-        } else {
-            thread::sleep(time::Duration::from_millis(100));
-
-            action = ai_action.lock().unwrap().unwrap();
-            println!(
-                "AI action: {}{}",
-                char::from_u32(action.0 as u32 + '1' as u32).unwrap(),
-                char::from_u32(action.1 as u32 + 'a' as u32).unwrap()
-            )
+            _ => self.difficulty,
         }
+    }
 
-        tx.send(action).unwrap();
-        board.perform_action(action);
+    /// Pick the game mode, the variant, the AI and the options
+    fn main_menu(&mut self) {
+        let mode = menu::choose("Game mode:", &["Play against the AI", "Two players"], 0);
+        println!();
 
+        let names = Variant::names();
+        let variants: Vec<String> = names
+            .iter()
+            .map(|&name| {
+                let variant = Variant::by_name(name).expect("Listed variant not found");
+                format!("{}: {}", name, variant.description())
+            })
+            .collect();
+        let variants: Vec<&str> = variants.iter().map(|v| v.as_str()).collect();
+        self.options.variant = Variant::by_name(names[menu::choose("Variant:", &variants, 0)])
+            .expect("Listed variant not found");
         println!();
+
+        if mode == 1 {
+            self.mode = Mode::TwoPlayers;
+        } else {
+            let mut levels = vec!["Adaptive, adjusting to how well you play".to_string()];
+            for level in 1..=Difficulty::levels() {
+                levels.push(format!("Level {}", level));
+            }
+            let levels: Vec<&str> = levels.iter().map(|l| l.as_str()).collect();
+
+            match menu::choose("AI difficulty:", &levels, 0) {
+                0 => {}
+                level => {
+                    self.adaptive = None;
+                    self.difficulty = Difficulty::level(level);
+                }
+            }
+            println!();
+
+            let engines = Engine::names();
+            self.options.engine = Engine::by_name(engines[menu::choose("AI engine:", &engines, 0)])
+                .expect("Listed engine not found");
+            println!();
+
+            if self.options.engine == Engine::QLearning {
+                self.options.q_table = QTable::load_or_learn(qlearning::DEFAULT_TABLE)
+                    .unwrap_or_else(|e| cli::fail(&e));
+            }
+
+            self.options.explain = menu::confirm("Explain the AI's moves?");
+        }
+
+        self.options.commentary = menu::confirm("Describe every move?");
+        println!();
+    }
+}
+
+/// Play a single game against the AI, adding the moves to `record`. Returns
+/// `None` if the human quit.
+fn play(
+    difficulty: Difficulty,
+    options: &Options,
+    human: &mut dyn InputSource,
+    record: &mut GameRecord,
+) -> Option<Outcome> {
+    let mut renderer = render::for_terminal(
+        options.clear_screen,
+        options.theme,
+        options.accessible,
+        Some(options.scoreboard.clone()),
+    );
+    let mut ai = Ai::start(difficulty, options, record.first_board());
+    let mut human = Human { input: human };
+
+    let mut controller =
+        GameController::new(
+            renderer.as_mut(),
+            options.commentary,
+            options.pie_rule,
+            options.events.clone(),
+        );
+    let ending = controller.play(record, &mut human, &mut ai);
+    if ending == Ending::Quit {
+        return None;
     }
 
-    if let Some(player) = board.get_winner() {
-        println!("Winner is Player {}", player);
+    Some(match ending.winner() {
+        Some(PLAYER_USER) => Outcome::Win,
+        Some(_) => Outcome::Loss,
+        None => Outcome::Draw,
+    })
+}
+
+/// The AI as a participant in a game
+struct Ai<'a> {
+    difficulty: Difficulty,
+    options: &'a Options,
+    search: Arc<Mutex<Search>>,
+    iterations: Arc<AtomicUsize>,
+    /// Moves for the background search, which ends once this is dropped
+    moves: Sender<Update>,
+    /// Moves the AI made in the game so far
+    made: u32,
+}
+
+/// What the background search is told about the game
+enum Update {
+    Move(Coord),
+    /// The position changed other than by a move, e.g. by a swap
+    Position(Board),
+}
+
+impl<'a> Ai<'a> {
+    /// Get ready for a new game, starting the background search
+    fn start(difficulty: Difficulty, options: &'a Options, start: Board) -> Ai<'a> {
+        let search = Arc::new(Mutex::new(Search::default()));
+        let iterations = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = channel();
+
+        let _search = search.clone();
+        let explain = options.explain;
+        let eval_bar = options.eval_bar;
+        let config = options.config;
+        let _iterations = iterations.clone();
+
+        // The other engines work out every move on their turn, only MCTS keeps
+        // thinking in the background
+        let background = options.engine == Engine::Mcts;
+
+        thread::spawn(move || {
+            if !background {
+                return;
+            }
+
+            let engine = |board: Board| {
+                let mut ai = MCTS::from_board(PLAYER_AI, board, config);
+                ai.add_observer(Box::new(Progress {
+                    iterations: _iterations.clone(),
+                    search: _search.clone(),
+                }));
+                ai
+            };
+            let mut ai = engine(start);
+
+            loop {
+                // Process player action
+                match rx.try_recv() {
+                    Ok(Update::Move(action)) => ai.perform_action(action),
+                    Ok(Update::Position(board)) => ai = engine(board),
+                    // Game is over
+                    Err(TryRecvError::Disconnected) => break,
+                    Err(TryRecvError::Empty) => {}
+                }
+
+                // Perform MCTS
+                ai.run();
+
+                if explain {
+                    let mut search = _search.lock().unwrap();
+                    search.stats = ai.action_stats();
+                    search.evaluation = ai.evaluation();
+                    search.proven = ai.exact();
+                }
+                if eval_bar {
+                    _search.lock().unwrap().outlook = Some(ai.outlook());
+                }
+
+                // Nothing left to search in a solved position, wait for the next move
+                if ai.solved().is_some() {
+                    match rx.recv() {
+                        Ok(Update::Move(action)) => ai.perform_action(action),
+                        Ok(Update::Position(board)) => ai = engine(board),
+                        Err(_) => break,
+                    }
+                }
+            }
+        });
+
+        Ai {
+            difficulty,
+            options,
+            search,
+            iterations,
+            moves: tx,
+            made: 0,
+        }
+    }
+}
+
+impl<'a> Ai<'a> {
+    /// How good `board` is for the player to move according to the engine,
+    /// from -1 (loss) to 1 (win)
+    fn value(&self, board: &Board) -> f64 {
+        match self.options.engine {
+            Engine::Mcts => {
+                let config = self.options.config;
+                let mut engine = MCTS::from_board(board.next_player(), *board, config);
+                let limit = Limit::Time(self.difficulty.think_time);
+                engine.search(limit, &StopToken::new()).value
+            }
+            Engine::Pns => f64::from(pns::solve(board).value),
+            Engine::Negamax => f64::from(minimax::shared().value(board)),
+            Engine::Perfect => f64::from(table::perfect(board).0),
+            Engine::QLearning => self.options.q_table.best_action(board).map_or(0., |(_, v)| v),
+        }
+    }
+}
+
+impl<'a> Participant for Ai<'a> {
+    fn next_move(&mut self, board: &Board, renderer: &mut dyn Renderer) -> Input {
+        let search = match self.options.engine {
+            Engine::Mcts => {
+                let bar = if self.options.eval_bar { Some(&*self.search) } else { None };
+                let budget = self.options.handicap.budget(self.made + 1);
+                let think_time = self.difficulty.think_time.mul_f64(budget);
+                think(think_time, &self.iterations, bar, board.next_player());
+                self.search.lock().unwrap()
+            }
+            Engine::Pns => {
+                let proof = pns::solve(board);
+                let exact = Exact {
+                    value: proof.value,
+                    moves: minimax::shared().exact(board).moves,
+                };
+                decided(&self.search, proof.action, f64::from(proof.value), Some(exact))
+            }
+            Engine::Negamax => {
+                let mut cache = minimax::shared();
+                let action = *rand::thread_rng()
+                    .choose(&cache.best_actions(board))
+                    .expect("No legal action");
+                let exact = cache.exact(board);
+                decided(&self.search, Some(action), f64::from(exact.value), Some(exact))
+            }
+            Engine::Perfect => {
+                let (value, actions) = table::perfect(board);
+                let action = *rand::thread_rng().choose(&actions).expect("No legal action");
+                let exact = Exact {
+                    value,
+                    moves: minimax::shared().exact(board).moves,
+                };
+                decided(&self.search, Some(action), f64::from(value), Some(exact))
+            }
+            Engine::QLearning => {
+                let (action, value) =
+                    self.options.q_table.best_action(board).expect("No legal action");
+                decided(&self.search, Some(action), value, None)
+            }
+        };
+        let mut action = search.action.expect("AI has no action");
+
+        // Weaker levels occasionally ignore the search result
+        let mut rng = rand::thread_rng();
+        let blunder = rng.gen::<f64>() < self.difficulty.blunder_rate;
+        if blunder {
+            action = *rng.choose(&board.get_actions()).expect("No legal action");
+        }
+
+        // Every opening of the standard board draws, vary them instead of
+        // always playing the one the search happens to like
+        let opening = self.options.random_opening && self.made == 0 && !blunder;
+        if opening {
+            action = *rng.choose(&table::perfect(board).1).expect("No legal action");
+        }
+        self.made += 1;
+
+        // The background search only keeps its evaluation when explaining
+        let known = self.options.engine != Engine::Mcts || self.options.explain;
+        self.options.events.publish(&GameEvent::SearchUpdate {
+            iterations: self.iterations.load(Ordering::Relaxed) as u64,
+            best: search.action,
+            value: if known { Some(search.evaluation) } else { None },
+        });
+
+        renderer.show_stats(&format!("AI action: {}", action));
+
+        if let (true, Some(outlook)) = (self.options.eval_bar, search.outlook) {
+            renderer.show_evaluation(&for_x(outlook, board.next_player()));
+        }
+
+        if self.options.explain {
+            renderer.show_stats(&explain_action(
+                board,
+                action,
+                &search,
+                blunder,
+                !self.options.commentary,
+            ));
+            if opening {
+                renderer.show_stats("  Opening picked at random among the equally good moves");
+            }
+        }
+
+        Input::Action(action)
+    }
+
+    fn observe(&mut self, _board: &Board, action: Coord) {
+        // Without a background search nobody is listening
+        self.moves.send(Update::Move(action)).ok();
+    }
+
+    /// Swap if the first player's position looks better than the own one
+    fn offer_swap(&mut self, board: &Board, _renderer: &mut dyn Renderer) -> bool {
+        let mut swapped = *board;
+        swapped.swap();
+
+        -self.value(&swapped) > self.value(board)
+    }
+
+    fn observe_swap(&mut self, board: &Board) {
+        self.moves.send(Update::Position(*board)).ok();
+    }
+}
+
+/// A human at the terminal, with a clock if there is a move timer and
+/// warned about blunders if asked to
+fn human_input(options: &Options) -> Box<dyn InputSource> {
+    let input: Box<dyn InputSource> = match options.timer {
+        Some(ref timer) => Box::new(timer.clone()),
+        None => Box::new(Stdin),
+    };
+
+    if options.blunder_alert {
+        Box::new(BlunderAlert { input })
     } else {
-        println!("Game ended with a draw");
+        input
+    }
+}
+
+/// Play a game between two humans at the terminal, adding the moves to
+/// `record`. Returns the outcome for X, `None` if a player quit.
+fn play_two_players(options: &Options, record: &mut GameRecord) -> Option<Outcome> {
+    let mut renderer = render::for_terminal(
+        options.clear_screen,
+        options.theme,
+        options.accessible,
+        Some(options.scoreboard.clone()),
+    );
+    let (mut x, mut o) = (human_input(options), human_input(options));
+    let (mut x, mut o) = (Human { input: x.as_mut() }, Human { input: o.as_mut() });
+
+    let mut controller =
+        GameController::new(
+            renderer.as_mut(),
+            options.commentary,
+            options.pie_rule,
+            options.events.clone(),
+        );
+    let ending = controller.play(record, &mut x, &mut o);
+    if ending == Ending::Quit {
+        return None;
+    }
+
+    Some(match ending.winner() {
+        Some(game::Player::X) => Outcome::Win,
+        Some(game::Player::O) => Outcome::Loss,
+        None => Outcome::Draw,
+    })
+}
+
+/// Store the move of an engine that decides on its turn instead of searching
+/// in the background
+fn decided(
+    search: &Mutex<Search>,
+    action: Option<Coord>,
+    evaluation: f64,
+    proven: Option<Exact>,
+) -> MutexGuard<'_, Search> {
+    let mut search = search.lock().unwrap();
+    *search = Search {
+        action,
+        stats: Vec::new(),
+        evaluation,
+        proven,
+        outlook: Some(Outlook::from_value(evaluation)),
+    };
+    search
+}
+
+/// Why the AI played `action`: the threats involved (unless commentary
+/// already covers them), its share of the search and the expected outcome
+fn explain_action(
+    board: &Board,
+    action: Coord,
+    search: &Search,
+    blunder: bool,
+    threats: bool,
+) -> String {
+    let mut lines = Vec::new();
+
+    if threats {
+        lines.push(commentary::describe(board, action));
+    }
+
+    let total: i32 = search.stats.iter().map(|s| s.visits).sum();
+    let share = |stats: &ActionStats| 100. * stats.visits as f64 / total.max(1) as f64;
+
+    if blunder {
+        lines.push("Played at random, the AI makes mistakes at this level".to_string());
+    } else if let Some(chosen) = search.stats.iter().find(|s| s.action == action) {
+        let mut line = format!(
+            "{} got {:.0}% of {} simulations",
+            action,
+            share(chosen),
+            total
+        );
+
+        if let Some(next) = search.stats.iter().find(|s| s.action != action) {
+            line.push_str(&format!(
+                ", next best {} {:.0}%",
+                next.action,
+                share(next)
+            ));
+        }
+        lines.push(line);
+    }
+
+    match search.proven {
+        Some(exact) => lines.push(format!("Solved: {}", exact_outcome(exact))),
+        None => lines.push(format!(
+            "Expected outcome: {} (evaluation {:+.2})",
+            outcome_name(search.evaluation.round() as i32),
+            search.evaluation
+        )),
+    }
+
+    lines.iter().map(|line| format!("  {}", line)).collect::<Vec<_>>().join("\n")
+}
+
+/// E.g. `a win for the AI in 3 moves`
+fn exact_outcome(exact: Exact) -> String {
+    let moves = if exact.moves == 1 { "move" } else { "moves" };
+    format!("{} in {} {}", outcome_name(exact.value), exact.moves, moves)
+}
+
+fn outcome_name(value: i32) -> &'static str {
+    match value {
+        1 => "a win for the AI",
+        -1 => "a loss for the AI",
+        _ => "a draw",
+    }
+}
+
+/// An outlook for `player` as one for Player X
+fn for_x(outlook: Outlook, player: Player) -> Outlook {
+    match player {
+        Player::X => outlook,
+        Player::O => outlook.flipped(),
+    }
+}
+
+/// Give the AI playing `player` its think time, showing the progress on a
+/// terminal, along with the evaluation bar of `bar`'s search
+fn think(
+    think_time: Duration,
+    iterations: &AtomicUsize,
+    bar: Option<&Mutex<Search>>,
+    player: Player,
+) {
+    let show = io::stdout().is_terminal();
+    let start = Instant::now();
+    let start_iterations = iterations.load(Ordering::Relaxed);
+
+    loop {
+        let elapsed = start.elapsed();
+
+        if show {
+            let progress = if think_time > elapsed {
+                elapsed.as_secs_f64() / think_time.as_secs_f64()
+            } else {
+                1.
+            };
+            let filled = (progress * PROGRESS_WIDTH as f64) as usize;
+
+            let evaluation = bar
+                .and_then(|search| search.lock().unwrap().outlook)
+                .map_or(String::new(), |outlook| {
+                    format!("  {}", render::evaluation_bar(&for_x(outlook, player)))
+                });
+
+            print!(
+                "\rThinking [{}{}] {:>3}% ({} iterations){}",
+                "#".repeat(filled),
+                " ".repeat(PROGRESS_WIDTH - filled),
+                (progress * 100.) as u32,
+                iterations.load(Ordering::Relaxed) - start_iterations,
+                evaluation
+            );
+            io::stdout().flush().unwrap();
+        }
+
+        if elapsed >= think_time {
+            break;
+        }
+
+        thread::sleep(cmp::min(PROGRESS_INTERVAL, think_time - elapsed));
+    }
+
+    if show {
+        println!();
     }
-}
\ No newline at end of file
+}
@@ -1,7 +1,246 @@
+use std::cmp;
 use std::f64;
+use std::fmt::{self, Display, Write};
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use rand::{self, Rng};
-use game::Board;
-use game::Player;
+use game::{Board, Coord, Player};
+use minimax::{self, Exact};
+
+/// How simulations pick their moves
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Rollout {
+    /// Uniformly random moves
+    Random,
+    /// Take immediate wins, block immediate losses, make forks, otherwise
+    /// random
+    Heuristic,
+    /// Heuristic moves, but a random one with the given probability
+    EpsilonGreedy(f64),
+    /// Random moves for at most the given number of plies, then the position
+    /// is scored by `evaluate`
+    Cutoff(u32),
+}
+
+impl Display for Rollout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Rollout::Random => write!(f, "Random"),
+            Rollout::Heuristic => write!(f, "Heuristic"),
+            Rollout::EpsilonGreedy(epsilon) => write!(f, "EpsilonGreedy({})", epsilon),
+            Rollout::Cutoff(plies) => write!(f, "Cutoff({})", plies),
+        }
+    }
+}
+
+/// How the search picks the child to descend into
+#[derive(Display, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Selection {
+    /// Average reward plus an exploration bonus shrinking with the visits
+    Ucb1,
+    /// UCB1 with the bonus scaled by the variance of the child's rewards
+    Ucb1Tuned,
+    /// AlphaZero's formula, spreading the exploration according to the
+    /// children's prior probabilities
+    Puct,
+}
+
+/// Which of several equally good children the search takes
+#[derive(Display, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TieBreak {
+    /// The one expanded first
+    First,
+    /// One at random. The order is drawn once per node, so that repeated
+    /// looks at the same tree agree on the move.
+    Random,
+}
+
+/// Tunable search parameters
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Config {
+    pub selection: Selection,
+    /// Weight of the exploration term of the selection formula
+    pub exploration: f64,
+    pub rollout: Rollout,
+    /// First play urgency: value assumed for unexplored actions. An explored
+    /// child is only selected over expanding a new one if its value is higher.
+    pub fpu: f64,
+    /// Weight of the heuristic bonus added to the selection value, which
+    /// fades as the child gets visited. 0 turns it off.
+    pub progressive_bias: f64,
+    /// Between children of equal value, both while searching and when
+    /// choosing the move to play
+    pub tie_break: TieBreak,
+    /// Rewards are multiplied by this for every move it took to reach them,
+    /// preferring quick wins and slow losses. 1 turns it off.
+    pub discount: f64,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            selection: Selection::Ucb1,
+            exploration: f64::consts::SQRT_2,
+            rollout: Rollout::Random,
+            fpu: f64::INFINITY,
+            progressive_bias: 0.,
+            tie_break: TieBreak::Random,
+            discount: 1.,
+        }
+    }
+}
+
+impl Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "selection = {}, exploration = {:.2}, rollout = {}, fpu = {}, bias = {}, ties = {}, \
+             discount = {}",
+            self.selection,
+            self.exploration,
+            self.rollout,
+            self.fpu,
+            self.progressive_bias,
+            self.tie_break,
+            self.discount
+        )
+    }
+}
+
+impl Config {
+    /// Parse a configuration like `selection=puct,rollout=epsilon:0.2,fpu=0.5`.
+    /// Parameters left out keep their default.
+    pub fn parse(spec: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+
+        for param in spec.split(',').filter(|p| !p.is_empty()) {
+            let (key, value) = param
+                .split_once('=')
+                .ok_or_else(|| format!("Expected <key>=<value>: {}", param))?;
+            let invalid = || format!("Invalid value for {}: {}", key, value);
+            let number = |value: &str| value.parse::<f64>().map_err(|_| invalid());
+
+            match key {
+                "selection" => {
+                    config.selection = match value {
+                        "ucb1" => Selection::Ucb1,
+                        "ucb1-tuned" => Selection::Ucb1Tuned,
+                        "puct" => Selection::Puct,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "exploration" => config.exploration = number(value)?,
+                "rollout" => {
+                    config.rollout = match value.split_once(':') {
+                        None if value == "random" => Rollout::Random,
+                        None if value == "heuristic" => Rollout::Heuristic,
+                        Some(("epsilon", epsilon)) => Rollout::EpsilonGreedy(number(epsilon)?),
+                        Some(("cutoff", plies)) => {
+                            Rollout::Cutoff(plies.parse().map_err(|_| invalid())?)
+                        }
+                        _ => return Err(invalid()),
+                    }
+                }
+                "fpu" => config.fpu = number(value)?,
+                "bias" => config.progressive_bias = number(value)?,
+                "discount" => {
+                    config.discount = number(value)?;
+                    if config.discount <= 0. || config.discount > 1. {
+                        return Err(invalid());
+                    }
+                }
+                "ties" => {
+                    config.tie_break = match value {
+                        "first" => TieBreak::First,
+                        "random" => TieBreak::Random,
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(format!("Unknown parameter: {}", key)),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Search statistics of an action at the root
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ActionStats {
+    pub action: Coord,
+    pub visits: i32,
+    /// Average reward, from -1 (loss) to 1 (win)
+    pub value: f64,
+}
+
+/// How likely a game is to end in a win, a draw or a loss, each from 0 to 1
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Outlook {
+    pub win: f64,
+    pub draw: f64,
+    pub loss: f64,
+}
+
+impl Outlook {
+    /// The outlook of an average reward with as many draws as it allows,
+    /// e.g. the certain result of an exact value
+    pub fn from_value(value: f64) -> Outlook {
+        Outlook {
+            win: value.max(0.),
+            draw: 1. - value.abs(),
+            loss: (-value).max(0.),
+        }
+    }
+
+    /// The same outlook for the opponent
+    pub fn flipped(self) -> Outlook {
+        Outlook {
+            win: self.loss,
+            draw: self.draw,
+            loss: self.win,
+        }
+    }
+}
+
+/// When a search ends, unless it is stopped earlier
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Limit {
+    Time(Duration),
+    Iterations(u64),
+}
+
+/// Everything a search found out about the root position
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    /// The action to play: a proven win, otherwise the one with the best
+    /// average reward that isn't proven to lose. `None` if the game is over.
+    pub action: Option<Coord>,
+    /// Statistics of all explored root actions, most explored first
+    pub stats: Vec<ActionStats>,
+    /// Average reward of all simulations so far, from -1 (loss) to 1 (win)
+    pub value: f64,
+    /// The expected line of play, starting with `action`
+    pub principal_variation: Vec<Coord>,
+    /// Iterations run by this search, each adding at most one node
+    pub nodes: u64,
+    /// The exact value for the searching player and the moves left, once
+    /// the whole game tree below the root has been searched
+    pub proven: Option<Exact>,
+    pub elapsed: Duration,
+}
+
+/// Size and shape of the search tree
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct TreeStats {
+    pub nodes: usize,
+    /// Approximate memory taken by the nodes, in bytes
+    pub memory: usize,
+    /// Levels below the root, 0 for a lone root
+    pub depth: usize,
+    pub root_visits: i32,
+}
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 enum NodeState {
@@ -16,107 +255,349 @@ struct Node {
     board: Board,
     children: Vec<Node>,
     runs: i32,
-    wins: i32,
-    action: Option<(i32, i32)>,
+    /// Sum of the rewards, which are fractional when discounted
+    wins: f64,
+    /// Sum of the squared rewards, for their variance
+    squares: f64,
+    /// Probability of this node's action being the best one before searching
+    prior: f64,
+    /// How promising the action looks to the heuristic, see `heuristic`
+    bias: f64,
+    action: Option<Coord>,
     state: NodeState,
+    /// Game-theoretic value for `us`, known once the subtree is complete
+    proven: Option<i32>,
+    /// Moves to the end of the game under best play, once `proven`
+    moves_left: u32,
+    /// Rank among its siblings when their values tie, lowest first
+    order: u32,
 }
 
 impl Node {
-    fn best_child(&mut self) -> Option<&mut Node> {
-        let mut best_value = f64::NEG_INFINITY;
-        let mut best_child = None;
-        let n_total = self.runs as f64;
-
-        for child in &mut self.children {
-            let w = child.wins as f64;
-            let n = child.runs as f64;
-            let value = w / n + (2. * n_total.ln() / n).sqrt();
-
-            if value > best_value {
-                best_value = value;
-                best_child = Some(child);
+    fn new(us: Player, board: Board, action: Option<Coord>) -> Node {
+        Node {
+            us,
+            board,
+            children: Vec::new(),
+            runs: 0,
+            wins: 0.,
+            squares: 0.,
+            prior: 1.,
+            bias: 0.,
+            action,
+            state: NodeState::Expandable,
+            proven: None,
+            moves_left: 0,
+            order: 0,
+        }
+    }
+
+    /// Value for the player to move at this node, whose perspective differs
+    /// from `us` at the opponent's nodes
+    fn for_mover(&self, value: i32) -> i32 {
+        if self.board.next_player() == self.us {
+            value
+        } else {
+            -value
+        }
+    }
+
+    /// MCTS-Solver: a node is proven as soon as the player to move has a
+    /// proven win among the children, or once all children are proven. A
+    /// win takes the quickest way there, anything else the longest.
+    fn update_proven(&mut self, current_reward: i32) {
+        match self.state {
+            NodeState::Leaf => self.proven = Some(current_reward),
+            _ => {
+                let win = self.for_mover(1);
+                let wins = self.children.iter().filter(|c| c.proven == Some(win));
+                if let Some(child) = wins.min_by_key(|c| c.moves_left) {
+                    self.proven = Some(win);
+                    self.moves_left = child.moves_left + 1;
+                    return;
+                }
+
+                if self.state == NodeState::Expandable {
+                    return;
+                }
+
+                let mut best: Option<(i32, u32)> = None;
+
+                for child in &self.children {
+                    let value = match child.proven {
+                        Some(value) => (self.for_mover(value), child.moves_left),
+                        None => return,
+                    };
+
+                    best = Some(cmp::max(best.unwrap_or(value), value));
+                }
+
+                if let Some((value, moves_left)) = best {
+                    self.proven = Some(self.for_mover(value));
+                    self.moves_left = moves_left + 1;
+                }
+            }
+        }
+    }
+
+    /// The child to play: the quickest proven win if there is one, otherwise
+    /// the child with the best average reward for the player to move, counting
+    /// proven draws as 0 and never choosing a proven loss unless nothing else
+    /// is left, then the longest one
+    fn best_move(&self) -> Option<&Node> {
+        // Proven results rank above or below any average, even a perfect one
+        let score = |c: &Node| match c.proven.map(|value| self.for_mover(value)) {
+            Some(1) => f64::INFINITY,
+            Some(-1) => f64::NEG_INFINITY,
+            Some(value) => value as f64,
+            None => f64::from(self.for_mover(1)) * c.wins / c.runs.max(1) as f64,
+        };
+        let moves_left = |a: &Node, b: &Node| match a.proven.map(|value| self.for_mover(value)) {
+            Some(1) => b.moves_left.cmp(&a.moves_left),
+            Some(-1) => a.moves_left.cmp(&b.moves_left),
+            _ => cmp::Ordering::Equal,
+        };
+
+        self.children.iter().max_by(|a, b| {
+            score(a)
+                .partial_cmp(&score(b))
+                .expect("Score is NaN")
+                .then_with(|| moves_left(a, b))
+                .then(a.runs.cmp(&b.runs))
+                .then(b.order.cmp(&a.order))
+        })
+    }
+
+    /// Selection value of one of this node's children
+    fn value(&self, child: &Node, config: &Config) -> f64 {
+        // Wins are counted for us, the opponent picks moves that are good for them
+        let w = if self.board.next_player() == self.us {
+            child.wins
+        } else {
+            -child.wins
+        };
+        let n = child.runs as f64;
+        let mean = w / n;
+        let parent = self.runs as f64;
+        let bias = config.progressive_bias * child.bias / (n + 1.);
+
+        bias + match config.selection {
+            Selection::Ucb1 => mean + config.exploration * (parent.ln() / n).sqrt(),
+            Selection::Ucb1Tuned => {
+                // Upper bound of the variance, capped at the largest possible
+                // one for rewards between -1 and 1
+                let variance =
+                    child.squares / n - mean * mean + (2. * parent.ln() / n).sqrt();
+                mean + config.exploration * (parent.ln() / n * variance.min(1.)).sqrt()
+            }
+            Selection::Puct => mean + config.exploration * child.prior * parent.sqrt() / (1. + n),
+        }
+    }
+
+    /// The child to search next. Proven children are skipped as searching
+    /// them again can't change their value.
+    fn best_child_index(&self, config: &Config) -> Option<usize> {
+        let mut best: Option<(f64, u32, usize)> = None;
+        let all_proven = self.children.iter().all(|c| c.proven.is_some());
+
+        for (i, child) in self.children.iter().enumerate() {
+            if child.proven.is_some() && !all_proven {
+                continue;
+            }
+
+            let value = self.value(child, config);
+            let better = best.is_none_or(|(best_value, best_order, _)| {
+                value > best_value || (value == best_value && child.order < best_order)
+            });
+
+            if better {
+                best = Some((value, child.order, i));
             }
         }
 
-        best_child
+        best.map(|(_, _, i)| i)
+    }
+
+    fn best_child(&mut self, config: &Config) -> Option<&mut Node> {
+        let idx = self.best_child_index(config)?;
+        Some(&mut self.children[idx])
     }
 
     /// Add child with previously unexplored action
-    fn expand(&mut self) -> Option<&mut Node> {
-        let mut actions = self.board.get_actions();
+    fn expand(&mut self, tie_break: TieBreak) -> Option<&mut Node> {
+        let actions = self.board.actions_iter();
 
-        if actions.is_empty() {
+        if actions.len() == 0 {
             self.state = NodeState::Leaf;
             return None;
         }
 
-        // Remove already explored actions
-        for child in &self.children {
-            actions.remove_item(&child.action.expect("Child has no action"));
-        }
+        // Nothing is known about the actions yet, they are all equally likely
+        let prior = 1. / actions.len() as f64;
 
-        if actions.len() == 1 {
+        // Skip already explored actions
+        let children = &self.children;
+        let mut unexplored =
+            actions.filter(|&action| children.iter().all(|child| child.action != Some(action)));
+        let remaining = unexplored.clone().count();
+
+        if remaining == 1 {
             // Only one action to explore, then this node will be fully expanded
             self.state = NodeState::FullyExpanded;
         }
 
         // Perform action
-        let action = *rand::thread_rng().choose(&actions).expect("actions is empty");
-        let mut board = self.board.clone();
+        let pick = rand::thread_rng().gen_range(0, remaining);
+        let action = unexplored.nth(pick).expect("actions is empty");
+        let mut board = self.board;
         board.perform_action(action);
 
-        self.children.push(Node {
-            us: self.us,
-            board: board,
-            children: Vec::new(),
-            runs: 0,
-            wins: 0,
-            action: Some(action),
-            state: NodeState::Expandable,
-        });
+        let mut child = Node::new(self.us, board, Some(action));
+        child.prior = prior;
+        child.bias = heuristic(&self.board, action);
+        child.order = match tie_break {
+            TieBreak::First => self.children.len() as u32,
+            TieBreak::Random => rand::random(),
+        };
+        self.children.push(child);
         self.children.last_mut()
     }
 
-    /// Simulate the current node's game until reaching an outcome
-    fn simulate(&mut self) -> i32 {
+    /// Simulate the current node's game until reaching an outcome, returning
+    /// the reward discounted by the moves it took
+    fn simulate(&mut self, rollout: Rollout, discount: f64) -> f64 {
         assert!(self.runs == 0);
-        assert!(self.wins == 0);
 
-        let mut board = self.board.clone();
+        let mut board = self.board;
+        let mut rng = rand::thread_rng();
+        let mut plies = 0;
 
         loop {
-            let actions = board.get_actions();
+            let mut actions = board.actions_iter();
 
-            if !actions.is_empty() {
-                let action = *rand::thread_rng().choose(&actions).expect("actions is empty");
+            if actions.len() > 0 {
+                let action = match rollout {
+                    Rollout::Random | Rollout::Cutoff(_) => None,
+                    Rollout::Heuristic => heuristic_action(&board),
+                    Rollout::EpsilonGreedy(epsilon) if rng.gen::<f64>() >= epsilon => {
+                        heuristic_action(&board)
+                    }
+                    Rollout::EpsilonGreedy(_) => None,
+                };
+                let action = action.unwrap_or_else(|| {
+                    let pick = rng.gen_range(0, actions.len());
+                    actions.nth(pick).expect("actions is empty")
+                });
                 board.perform_action(action);
+                plies += 1;
             }
 
-            if let Some(reward) = board.get_reward(self.us) {
+            let reward = match rollout {
+                Rollout::Cutoff(max_plies) if plies >= max_plies => {
+                    Some(board.get_reward(self.us).unwrap_or_else(|| evaluate(&board, self.us)))
+                }
+                _ => board.get_reward(self.us),
+            };
+
+            if let Some(reward) = reward {
+                let reward = f64::from(reward) * discount.powi(plies as i32);
                 self.runs = 1;
                 self.wins = reward;
+                self.squares = reward * reward;
 
                 return reward;
             }
         }
     }
 
-    /// Perform Monte Carlo Tree Search (selection, expansion, simulation, backpropagation)
-    fn perform_mcts(&mut self) -> i32 {
+    /// Number of nodes in the subtree
+    fn size(&self) -> usize {
+        1 + self.children.iter().map(Node::size).sum::<usize>()
+    }
+
+    /// Levels below this node in the deepest branch
+    fn depth(&self) -> usize {
+        self.children.iter().map(|c| c.depth() + 1).max().unwrap_or(0)
+    }
+
+    /// Write the subtree as JSON, including children up to `depth` levels deep
+    fn write_json(&self, out: &mut String, depth: usize) {
+        match self.action {
+            Some(action) => write!(out, "{{\"action\":\"{}\"", action),
+            None => write!(out, "{{\"action\":null"),
+        }
+        .unwrap();
+
+        write!(
+            out,
+            ",\"visits\":{},\"value\":{}",
+            self.runs,
+            self.wins / self.runs.max(1) as f64
+        )
+        .unwrap();
+
+        if depth > 0 {
+            out.push_str(",\"children\":[");
+
+            for (i, child) in self.children.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                child.write_json(out, depth - 1);
+            }
+
+            out.push(']');
+        }
+
+        out.push('}');
+    }
+
+    /// Perform one iteration of Monte Carlo Tree Search: selection, expansion,
+    /// simulation and backpropagation. A newly added node is reported through
+    /// `expanded` as its position and action.
+    fn perform_mcts(
+        &mut self,
+        config: &Config,
+        expanded: &mut Option<(Board, Coord)>,
+    ) -> f64 {
         let current_reward = self.board.get_reward(self.us).unwrap_or(0);
+
+        // The exact value is known, no need to search or simulate any further
+        if let Some(value) = self.proven {
+            let value = f64::from(value);
+            self.runs += 1;
+            self.wins += value;
+            self.squares += value * value;
+
+            return value;
+        }
+
         let reward = match self.state {
-            NodeState::Leaf => return current_reward,
+            NodeState::Leaf => f64::from(current_reward),
             NodeState::FullyExpanded => {
                 // Current state's actions are fully explored, explore the best child (selection)
-                let child = self.best_child().expect("Fully expanded node without children");
-                child.perform_mcts()
+                let child = self.best_child(config).expect("Fully expanded node without children");
+                child.perform_mcts(config, expanded) * config.discount
             }
             NodeState::Expandable => {
-                // Current state has unexplored actions -> expansion + simulation
-                match self.expand() {
-                    Some(child) => child.simulate(),
-                    // No child created -> this is a leaf node
-                    None => return current_reward,
+                // Explored children only win over the unexplored actions if they
+                // look better than the first play urgency value
+                let exploit = self
+                    .best_child_index(config)
+                    .filter(|&idx| self.value(&self.children[idx], config) > config.fpu);
+
+                if let Some(idx) = exploit {
+                    self.children[idx].perform_mcts(config, expanded) * config.discount
+                } else {
+                    // Current state has unexplored actions -> expansion + simulation
+                    match self.expand(config.tie_break) {
+                        Some(child) => {
+                            *expanded = Some((child.board, child.action.expect("Child has no action")));
+                            child.simulate(config.rollout, config.discount) * config.discount
+                        }
+                        // No child created -> this is a leaf node
+                        None => f64::from(current_reward),
+                    }
                 }
             }
         };
@@ -124,58 +605,384 @@ impl Node {
         // Backpropagation of simulation results
         self.runs += 1;
         self.wins += reward;
+        self.squares += reward * reward;
+
+        self.update_proven(current_reward);
 
         reward
     }
 }
 
-#[derive(Debug)]
+/// The heuristic rollout move: win if possible, otherwise prevent the
+/// opponent from winning, otherwise set up a fork
+pub fn heuristic_action(board: &Board) -> Option<Coord> {
+    let player = board.next_player();
+
+    let wins = board.immediate_wins(player);
+    let losses = board.immediate_wins(player.opponent());
+    wins.first()
+        .or_else(|| losses.first())
+        .cloned()
+        .or_else(|| board.forks(player).first().cloned())
+}
+
+/// Static value of an unfinished position for `player`: a win for the player
+/// to move if they can complete a line, a loss for them if the opponent
+/// threatens two lines they can't both block, otherwise a draw
+fn evaluate(board: &Board, player: Player) -> i32 {
+    let mover = board.next_player();
+    let value = if !board.immediate_wins(mover).is_empty() {
+        1
+    } else if board.immediate_wins(mover.opponent()).len() >= 2 {
+        -1
+    } else {
+        0
+    };
+
+    if mover == player {
+        value
+    } else {
+        -value
+    }
+}
+
+/// Static estimate of how good `action` is: the center beats the corners,
+/// which beat the edges, and winning, blocking and threatening earn a bonus
+fn heuristic(board: &Board, action: Coord) -> f64 {
+    let player = board.next_player();
+    let mut score = match (action.row(), action.col()) {
+        (1, 1) => 0.4,
+        (row, col) if row != 1 && col != 1 => 0.3,
+        _ => 0.2,
+    };
+
+    if board.immediate_wins(player).contains(&action) {
+        score += 0.6;
+    } else if board.immediate_wins(player.opponent()).contains(&action) {
+        score += 0.4;
+    } else {
+        let mut next = *board;
+        next.perform_action(action);
+        if !next.immediate_wins(player).is_empty() {
+            score += 0.2;
+        }
+    }
+
+    score
+}
+
+/// Gets notified about the progress of a search, e.g. to show it live or
+/// to instrument the search. All events are ignored by default.
+pub trait Observer: Send {
+    /// After every iteration, with the number of iterations since the search
+    /// was created
+    fn on_iteration(&mut self, _iterations: u64) {}
+
+    /// When a node is added to the tree, with its position and the action
+    /// leading to it
+    fn on_expansion(&mut self, _board: &Board, _action: Coord) {}
+
+    /// When the best action at the root changes
+    fn on_best_move_change(&mut self, _action: Coord) {}
+}
+
+/// Lets another thread interrupt a running search. Clones share the same
+/// flag.
+#[derive(Debug, Clone, Default)]
+pub struct StopToken {
+    stopped: Arc<AtomicBool>,
+}
+
+impl StopToken {
+    pub fn new() -> StopToken {
+        StopToken::default()
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// Allow searching again after a stop
+    pub fn reset(&self) {
+        self.stopped.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
 pub struct MCTS {
     root: Node,
-    us: Player,
+    config: Config,
+    observers: Vec<Box<dyn Observer>>,
+    iterations: u64,
+    /// The best action last reported to the observers
+    best_action: Option<Coord>,
+    /// Nodes dropped with the siblings of played actions
+    freed_nodes: usize,
 }
 
 impl MCTS {
-    pub fn new(player: Player, first_action: bool) -> MCTS {
+    /// Start searching from an arbitrary position
+    pub fn from_board(player: Player, board: Board, config: Config) -> MCTS {
         MCTS {
-            root: Node {
-                us: player,
-                board: Board::new(if first_action {
-                    player
-                } else {
-                    player.opponent()
-                }),
-                children: Vec::new(),
-                runs: 0,
-                wins: 0,
-                action: None,
-                state: NodeState::Expandable,
-            },
-            us: player,
+            root: Node::new(player, board, None),
+            config,
+            observers: Vec::new(),
+            iterations: 0,
+            best_action: None,
+            freed_nodes: 0,
         }
     }
 
-    pub fn get_action(&mut self) -> Option<(i32, i32)> {
-        self.root.best_child().map(|c| c.action.expect("Best child without action"))
+    pub fn add_observer(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    /// The action to play at the root
+    fn best_action(&self) -> Option<Coord> {
+        self.root
+            .best_move()
+            .map(|c| c.action.expect("Best child without action"))
     }
 
     pub fn run(&mut self) {
-        self.root.perform_mcts();
+        let mut expanded = None;
+        self.root.perform_mcts(&self.config, &mut expanded);
+        self.iterations += 1;
+
+        if self.observers.is_empty() {
+            return;
+        }
+
+        if let Some((board, action)) = expanded {
+            for observer in &mut self.observers {
+                observer.on_expansion(&board, action);
+            }
+        }
+
+        for observer in &mut self.observers {
+            observer.on_iteration(self.iterations);
+        }
+
+        let best_action = self.best_action();
+        if best_action != self.best_action {
+            self.best_action = best_action;
+
+            if let Some(action) = best_action {
+                for observer in &mut self.observers {
+                    observer.on_best_move_change(action);
+                }
+            }
+        }
+    }
+
+    /// Search until `limit` is reached or `stop` is triggered, but for at
+    /// least one iteration
+    pub fn search(&mut self, limit: Limit, stop: &StopToken) -> SearchResult {
+        let start = Instant::now();
+        let start_iterations = self.iterations;
+
+        loop {
+            self.run();
+
+            // Searching a solved position doesn't change the result
+            let done = self.solved().is_some() || match limit {
+                Limit::Time(time) => start.elapsed() >= time,
+                Limit::Iterations(iterations) => self.iterations - start_iterations >= iterations,
+            };
+
+            if done || stop.is_stopped() {
+                break;
+            }
+        }
+
+        SearchResult {
+            action: self.best_action(),
+            stats: self.action_stats(),
+            value: self.evaluation(),
+            principal_variation: self.principal_variation(),
+            nodes: self.iterations - start_iterations,
+            proven: self.exact(),
+            elapsed: start.elapsed(),
+        }
+    }
+
+    /// The exact value of the root position for the searching player, once
+    /// the search has seen the complete game tree
+    pub fn solved(&self) -> Option<i32> {
+        self.root.proven
+    }
+
+    /// Like `solved`, along with the moves left under best play
+    pub fn exact(&self) -> Option<Exact> {
+        let value = self.solved()?;
+        let moves = minimax::shared().exact(&self.root.board).moves;
+
+        Some(Exact { value, moves })
+    }
+
+    /// The position being searched
+    pub fn board(&self) -> Board {
+        self.root.board
+    }
+
+    /// Nodes freed so far by advancing the root with `perform_action`
+    pub fn freed_nodes(&self) -> usize {
+        self.freed_nodes
+    }
+
+    pub fn stats(&self) -> TreeStats {
+        let nodes = self.root.size();
+
+        TreeStats {
+            nodes,
+            memory: MCTS::node_memory(nodes),
+            depth: self.root.depth(),
+            root_visits: self.root.runs,
+        }
+    }
+
+    /// Approximate memory taken by `nodes` search tree nodes, in bytes
+    pub fn node_memory(nodes: usize) -> usize {
+        nodes * mem::size_of::<Node>()
+    }
+
+    /// Iterations run since the search was created
+    pub fn iterations(&self) -> u64 {
+        self.iterations
+    }
+
+    /// Average reward of all simulations so far, from -1 (loss) to 1 (win)
+    pub fn evaluation(&self) -> f64 {
+        self.root.wins / self.root.runs.max(1) as f64
+    }
+
+    /// Chances of a win, a draw and a loss for the searching player: certain
+    /// once the position is solved, otherwise those among the simulations of
+    /// the most explored action. Rewards of 1, 0 and -1 are told apart by the
+    /// average reward and the average of its square, discounted rewards
+    /// count partly as draws.
+    pub fn outlook(&self) -> Outlook {
+        if let Some(value) = self.solved() {
+            return Outlook::from_value(f64::from(value));
+        }
+
+        let node = self.root.children.iter().max_by_key(|c| c.runs).unwrap_or(&self.root);
+        let runs = f64::from(node.runs.max(1));
+        let mean = node.wins / runs;
+        let decided = (node.squares / runs).clamp(mean.abs(), 1.);
+
+        Outlook {
+            win: (decided + mean) / 2.,
+            draw: 1. - decided,
+            loss: (decided - mean) / 2.,
+        }
     }
 
-    pub fn perform_action(&mut self, action: (i32, i32)) {
+    /// Statistics of all explored root actions, most explored first
+    pub fn action_stats(&self) -> Vec<ActionStats> {
+        let mut stats: Vec<ActionStats> = self
+            .root
+            .children
+            .iter()
+            .map(|c| ActionStats {
+                action: c.action.expect("Child without action"),
+                visits: c.runs,
+                value: c.wins / c.runs as f64,
+            })
+            .collect();
+
+        stats.sort_by_key(|s| -s.visits);
+        stats
+    }
+
+    /// The search tree as JSON, down to `depth` levels below the root. Every
+    /// node has its `action` (`null` for the root), `visits`, average `value`
+    /// for the searching player and, within `depth`, its `children`.
+    pub fn tree_json(&self, depth: usize) -> String {
+        let mut out = String::new();
+        self.root.write_json(&mut out, depth);
+        out
+    }
+
+    /// The expected line of play: the best move at each level
+    fn principal_variation(&self) -> Vec<Coord> {
+        let mut variation = Vec::new();
+        let mut node = &self.root;
+
+        while let Some(child) = node.best_move() {
+            variation.push(child.action.expect("Child without action"));
+            node = child;
+        }
+
+        variation
+    }
+
+    pub fn perform_action(&mut self, action: Coord) {
         // Find index of child node with the desired action
         // That way, we don't have to start over but re-use all previous calculations
         let idx = self.root
             .children
             .iter()
-            .enumerate()
-            .find(|&(_, c)| c.action.expect("Child without action") == action)
-            .map(|(i, _)| i)
-            .expect("No child with action to be performed");
+            .position(|c| c.action.expect("Child without action") == action);
+
+        let root = match idx {
+            Some(idx) => self.root.children.swap_remove(idx),
+            // The search never looked at this action, start over from the new position
+            None => {
+                let mut board = self.root.board;
+                board.perform_action(action);
 
-        let node = self.root.children.remove(idx);
+                Node::new(self.root.us, board, Some(action))
+            }
+        };
+
+        // Free the old root along with the remaining siblings right away
+        let old = mem::replace(&mut self.root, root);
+        self.freed_nodes += old.size();
+        drop(old);
+
+        self.best_action = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::Board;
+    use super::{Config, Limit, StopToken, MCTS};
+
+    fn search(fen: &str, iterations: u64) -> MCTS {
+        let board = Board::from_fen(fen).unwrap();
+        let mut mcts = MCTS::from_board(board.next_player(), board, Config::default());
+        mcts.search(Limit::Iterations(iterations), &StopToken::new());
+        mcts
+    }
+
+    #[test]
+    fn plays_the_win_in_one() {
+        // Both players have two in a row, only the one to move can finish it
+        for _ in 0..20 {
+            assert_eq!(search("xx./oo./... o", 1000).best_action(), Some("2c".parse().unwrap()));
+            assert_eq!(search("xx./oo./... x", 1000).best_action(), Some("1c".parse().unwrap()));
+        }
+    }
+
+    #[test]
+    fn proves_a_draw() {
+        let mcts = search("xxo/.o./... x", 100_000);
+        assert_eq!(mcts.solved(), Some(0));
+    }
 
-        self.root = node;
+    #[test]
+    fn defends_as_long_as_possible() {
+        // Any move but 1c loses to X playing it. Blocking there threatens the
+        // diagonal, but X blocks it at 2b with threats on 3b and 3c.
+        let mcts = search("xx./o.x/o.. o", 100_000);
+        assert_eq!(mcts.solved(), Some(-1));
+        assert_eq!(mcts.root.moves_left, 4);
+        assert_eq!(mcts.best_action(), Some("1c".parse().unwrap()));
     }
 }
@@ -0,0 +1,42 @@
+use std::io::{self, Write};
+use input;
+
+/// Let the user pick one of `choices` by number, `default` (counting from
+/// 0) if they just press enter or stdin is closed
+pub fn choose(title: &str, choices: &[&str], default: usize) -> usize {
+    println!("{}", title);
+    for (i, choice) in choices.iter().enumerate() {
+        println!("  {}. {}", i + 1, choice);
+    }
+
+    loop {
+        print!("Choice [{}]: ", default + 1);
+        io::stdout().flush().unwrap();
+
+        let answer = match input::read_line() {
+            Some(answer) => answer,
+            None => {
+                println!();
+                return default;
+            }
+        };
+
+        match answer.trim() {
+            "" => return default,
+            answer => match answer.parse::<usize>() {
+                Ok(n) if n >= 1 && n <= choices.len() => return n - 1,
+                _ => println!("Enter a number from 1 to {}", choices.len()),
+            },
+        }
+    }
+}
+
+/// Ask a yes/no question, no being the default
+pub fn confirm(question: &str) -> bool {
+    print!("{} [y/N]: ", question);
+    io::stdout().flush().unwrap();
+
+    let answer = input::read_line().unwrap_or_default();
+
+    answer.trim().eq_ignore_ascii_case("y")
+}
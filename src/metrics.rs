@@ -0,0 +1,141 @@
+//! Metrics in the Prometheus text format, served over a minimal HTTP
+//! endpoint at `/metrics`.
+
+use std::fmt::Write as FmtWrite;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+/// Upper bounds of the buckets for durations, in seconds
+pub const DURATION_BUCKETS: [f64; 10] = [0.1, 0.25, 0.5, 1., 2.5, 5., 10., 30., 60., 120.];
+
+/// Counts of observed values by bucket
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// Observations up to each bound, not cumulative
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Histogram {
+        Histogram {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.,
+            count: 0,
+        }
+    }
+
+    pub fn observe_duration(&mut self, duration: Duration) {
+        let value = duration.as_secs_f64();
+        if let Some(i) = self.bounds.iter().position(|&bound| value <= bound) {
+            self.counts[i] += 1;
+        }
+
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// Where the metrics of a program are written to, one family at a time
+#[derive(Default)]
+pub struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    fn header(&mut self, name: &str, help: &str, kind: &str) {
+        writeln!(self.text, "# HELP {} {}", name, help).unwrap();
+        writeln!(self.text, "# TYPE {} {}", name, kind).unwrap();
+    }
+
+    pub fn counter(&mut self, name: &str, help: &str, value: u64) {
+        self.header(name, help, "counter");
+        writeln!(self.text, "{} {}", name, value).unwrap();
+    }
+
+    /// A counter split by the values of `label`
+    pub fn labeled_counter(&mut self, name: &str, help: &str, label: &str, values: &[(&str, u64)]) {
+        self.header(name, help, "counter");
+        for &(key, value) in values {
+            writeln!(self.text, "{}{{{}=\"{}\"}} {}", name, label, key, value).unwrap();
+        }
+    }
+
+    pub fn gauge(&mut self, name: &str, help: &str, value: usize) {
+        self.header(name, help, "gauge");
+        writeln!(self.text, "{} {}", name, value).unwrap();
+    }
+
+    pub fn histogram(&mut self, name: &str, help: &str, histogram: &Histogram) {
+        self.header(name, help, "histogram");
+
+        let mut cumulative = 0;
+        for (bound, count) in histogram.bounds.iter().zip(&histogram.counts) {
+            cumulative += count;
+            writeln!(self.text, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative).unwrap();
+        }
+        writeln!(self.text, "{}_bucket{{le=\"+Inf\"}} {}", name, histogram.count).unwrap();
+        writeln!(self.text, "{}_sum {}", name, histogram.sum).unwrap();
+        writeln!(self.text, "{}_count {}", name, histogram.count).unwrap();
+    }
+
+    pub fn finish(self) -> String {
+        self.text
+    }
+}
+
+fn respond(stream: TcpStream, render: &dyn Fn() -> String) {
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    if reader.read_line(&mut request).is_err() {
+        return;
+    }
+
+    // Skip the headers, nothing in them matters here
+    let mut header = String::new();
+    while reader.read_line(&mut header).is_ok_and(|n| n > 2) {
+        header.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", render()),
+        (Some("GET"), _) => ("404 Not Found", "Not found, try /metrics\n".to_string()),
+        _ => ("405 Method Not Allowed", "Only GET is supported\n".to_string()),
+    };
+
+    let mut stream = &stream;
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+}
+
+/// Serve the text returned by `render` at `/metrics` on `port`, in the
+/// background
+pub fn serve<F>(port: u16, render: F) -> Result<(), String>
+where
+    F: Fn() -> String + Send + 'static,
+{
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .map_err(|e| format!("Could not listen on port {}: {}", port, e))?;
+
+    thread::spawn(move || {
+        // Scrapes are rare and quick, one at a time is plenty
+        for stream in listener.incoming().flatten() {
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+            respond(stream, &render);
+        }
+    });
+
+    Ok(())
+}
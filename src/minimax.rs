@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use game::{Board, Coord};
+
+/// Evaluation cache shared by everything that needs exact values, so the game
+/// tree is searched only once per process
+static SHARED: OnceLock<Mutex<Minimax>> = OnceLock::new();
+
+/// Lock the shared evaluation cache
+pub fn shared() -> MutexGuard<'static, Minimax> {
+    SHARED.get_or_init(|| Mutex::new(Minimax::new())).lock().unwrap()
+}
+
+/// A game-theoretic value along with how long the game lasts under best
+/// play, the winner winning as soon as possible and the loser holding out
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Exact {
+    /// 1 (win), 0 (draw) or -1 (loss)
+    pub value: i32,
+    /// Moves left until the game ends, counting both players'
+    pub moves: u32,
+}
+
+/// E.g. `win in 3` or `forced draw in 5`
+impl Display for Exact {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.value {
+            1 => write!(f, "win in {}", self.moves),
+            -1 => write!(f, "loss in {}", self.moves),
+            _ => write!(f, "forced draw in {}", self.moves),
+        }
+    }
+}
+
+/// Perfect play by exhaustively searching the game tree
+#[derive(Debug, Default)]
+pub struct Minimax {
+    /// Values by canonical board, as symmetric boards share their value
+    values: HashMap<Board, i32>,
+    /// Moves left under best play, by canonical board
+    lengths: HashMap<Board, u32>,
+}
+
+impl Minimax {
+    pub fn new() -> Minimax {
+        Minimax::default()
+    }
+
+    /// Number of positions with a known value, up to symmetry
+    pub fn positions(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Game-theoretic value for the player to move: 1 (win), 0 (draw) or -1 (loss)
+    pub fn value(&mut self, board: &Board) -> i32 {
+        if let Some(reward) = board.get_reward(board.next_player()) {
+            return reward;
+        }
+
+        let (canonical, _) = board.canonical();
+        if let Some(&value) = self.values.get(&canonical) {
+            return value;
+        }
+
+        let value = board
+            .actions_iter()
+            .map(|action| {
+                let mut child = *board;
+                child.perform_action(action);
+
+                -self.value(&child)
+            })
+            .max()
+            .expect("Unfinished game without actions");
+
+        self.values.insert(canonical, value);
+        value
+    }
+
+    /// The value for the player to move along with the moves left
+    pub fn exact(&mut self, board: &Board) -> Exact {
+        Exact {
+            value: self.value(board),
+            moves: self.length(board),
+        }
+    }
+
+    fn length(&mut self, board: &Board) -> u32 {
+        if board.is_ended() {
+            return 0;
+        }
+
+        let (canonical, _) = board.canonical();
+        if let Some(&length) = self.lengths.get(&canonical) {
+            return length;
+        }
+
+        let value = self.value(&canonical);
+        let mut lengths = Vec::new();
+        for action in canonical.actions_iter() {
+            let mut child = canonical;
+            child.perform_action(action);
+
+            if -self.value(&child) == value {
+                lengths.push(1 + self.length(&child));
+            }
+        }
+
+        // The winner hurries, the other side drags the game out
+        let length = if value == 1 { lengths.iter().min() } else { lengths.iter().max() };
+        let length = *length.expect("Unfinished game without actions");
+
+        self.lengths.insert(canonical, length);
+        length
+    }
+
+    /// All actions that keep the game-theoretic value
+    pub fn best_actions(&mut self, board: &Board) -> Vec<Coord> {
+        // Search the canonical board and map its actions back
+        let (canonical, symmetry) = board.canonical();
+        let value = self.value(&canonical);
+
+        let mut actions: Vec<Coord> = canonical
+            .actions_iter()
+            .filter(|&action| {
+                let mut child = canonical;
+                child.perform_action(action);
+
+                -self.value(&child) == value
+            })
+            .map(|action| symmetry.inverse().apply(action))
+            .collect();
+
+        actions.sort();
+        actions
+    }
+
+    /// The best actions that also end the game soonest when winning, or
+    /// latest otherwise, e.g. the ways to win in 3 rather than in 5
+    pub fn fastest_actions(&mut self, board: &Board) -> Vec<Coord> {
+        let moves = self.exact(board).moves;
+
+        self.best_actions(board)
+            .into_iter()
+            .filter(|&action| {
+                let mut child = *board;
+                child.perform_action(action);
+
+                1 + self.length(&child) == moves
+            })
+            .collect()
+    }
+
+    /// The exact value for the player to move, naming the moves when they
+    /// win, e.g. `win in 3 with 2b` or `forced draw in 5`
+    pub fn describe(&mut self, board: &Board) -> String {
+        let exact = self.exact(board);
+        if exact.value != 1 {
+            return exact.to_string();
+        }
+
+        let actions: Vec<String> =
+            self.fastest_actions(board).iter().map(|action| action.to_string()).collect();
+        format!("{} with {}", exact, actions.join(" or "))
+    }
+}
@@ -0,0 +1,97 @@
+//! Names of common opening lines, listed in `openings.txt`. A line matches a
+//! game whose first moves are the same up to rotating and mirroring the
+//! board, and the longest matching line names the game's opening.
+
+use std::sync::OnceLock;
+use game::{Board, Coord, Symmetry};
+use record::GameRecord;
+use rules::Variant;
+
+static OPENINGS: OnceLock<Vec<Opening>> = OnceLock::new();
+
+struct Opening {
+    /// Names of the variants the line is played in, `None` for all of them
+    variants: Option<Vec<String>>,
+    moves: Vec<Coord>,
+    name: String,
+}
+
+impl Opening {
+    fn applies_to(&self, variant: Variant) -> bool {
+        self.variants.as_ref().is_none_or(|names| names.iter().any(|n| n == variant.name()))
+    }
+
+    /// Whether `moves` start with this line, in any orientation
+    fn matches(&self, moves: &[Coord]) -> bool {
+        moves.len() >= self.moves.len()
+            && Symmetry::ALL.iter().any(|symmetry| {
+                self.moves.iter().zip(moves).all(|(&own, &played)| symmetry.apply(own) == played)
+            })
+    }
+}
+
+fn parse(text: &str) -> Result<Vec<Opening>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|&(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            let invalid = |reason: &str| format!("openings.txt:{}: {}", i + 1, reason);
+            let mut fields = line.split_whitespace();
+
+            let variants = match fields.next() {
+                Some("*") => None,
+                Some(names) => Some(names.split(',').map(str::to_string).collect()),
+                None => return Err(invalid("missing variants")),
+            };
+
+            let mut moves = Vec::new();
+            let mut name = Vec::new();
+            for field in fields {
+                match field.parse::<Coord>() {
+                    Ok(action) if name.is_empty() => moves.push(action),
+                    _ => name.push(field),
+                }
+            }
+
+            if moves.is_empty() || name.is_empty() {
+                return Err(invalid("expected moves followed by a name"));
+            }
+
+            Ok(Opening {
+                variants,
+                moves,
+                name: name.join(" "),
+            })
+        })
+        .collect()
+}
+
+/// Read the openings, once at startup
+pub fn load() {
+    OPENINGS.get_or_init(|| {
+        parse(include_str!("openings.txt")).unwrap_or_else(|e| panic!("{}", e))
+    });
+}
+
+/// The name of the longest opening line that `moves`, played from `start`,
+/// begin with. Only games from the empty board have named openings.
+pub fn name(start: &Board, moves: &[Coord]) -> Option<&'static str> {
+    if start.fields.iter().flatten().any(Option::is_some) {
+        return None;
+    }
+
+    OPENINGS
+        .get()
+        .expect("Openings not loaded")
+        .iter()
+        .filter(|opening| opening.applies_to(start.variant) && opening.matches(moves))
+        // Lines for the variant win over those for all of them
+        .max_by_key(|opening| (opening.moves.len(), opening.variants.is_some()))
+        .map(|opening| opening.name.as_str())
+}
+
+/// The opening of a saved game
+pub fn of_record(record: &GameRecord) -> Option<&'static str> {
+    let actions: Vec<Coord> = record.moves.iter().map(|m| m.action).collect();
+    name(&record.first_board(), &actions)
+}
@@ -0,0 +1,47 @@
+use std::thread;
+
+/// One worker per CPU core
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Apply `work` to every item on up to `jobs` threads, returning the results
+/// in the order of the items.
+///
+/// Worker `i` takes items `i`, `i + jobs`, `i + 2 * jobs` and so on, so the
+/// assignment never depends on timing. Each worker starts from its own
+/// `init()` state, e.g. engines to play with, and draws from its own
+/// thread-local random number generator, seeded independently of the others.
+pub fn map<T, S, R, I, W>(items: &[T], jobs: usize, init: I, work: W) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    I: Fn() -> S + Sync,
+    W: Fn(&mut S, &T) -> R + Sync,
+{
+    let jobs = jobs.clamp(1, items.len().max(1));
+
+    let mut results: Vec<Vec<R>> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|worker| {
+                let (init, work) = (&init, &work);
+                scope.spawn(move || {
+                    let mut state = init();
+                    let mine = items.iter().skip(worker).step_by(jobs);
+                    mine.map(|item| work(&mut state, item)).collect()
+                })
+            })
+            .collect();
+
+        workers.into_iter().map(|worker| worker.join().expect("Worker panicked")).collect()
+    });
+
+    // Interleave the workers' results back into item order
+    let mut ordered = Vec::with_capacity(items.len());
+    let mut streams: Vec<_> = results.iter_mut().map(|r| r.drain(..)).collect();
+    for i in 0..items.len() {
+        ordered.push(streams[i % jobs].next().expect("Worker skipped an item"));
+    }
+
+    ordered
+}
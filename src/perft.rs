@@ -0,0 +1,66 @@
+use std::process;
+use cli;
+use game::{Board, Player};
+
+/// Leaf counts of the 3×3 game tree from the empty board, indexed by depth
+const REFERENCE: [u64; 10] = [1, 9, 72, 504, 3024, 15120, 54720, 148176, 200448, 127872];
+
+/// Count the positions reachable in exactly `depth` moves. Finished games
+/// don't have any moves, so they only count if reached at `depth`.
+pub fn perft(board: &Board, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    board
+        .actions_iter()
+        .map(|action| {
+            let mut child = *board;
+            child.perform_action(action);
+
+            perft(&child, depth - 1)
+        })
+        .sum()
+}
+
+pub fn run(args: &[String]) {
+    let mut depth = None;
+    let mut board = Board::new(Player::X);
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--position" => board = cli::position(args.next()),
+            _ if depth.is_none() => depth = Some(cli::value("depth", Some(arg))),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    let depth = depth.unwrap_or_else(|| cli::fail("Missing depth"));
+
+    // Reference values are only known for the start position
+    let reference = if board == Board::new(Player::X) {
+        &REFERENCE[..]
+    } else {
+        &[]
+    };
+    let mut mismatch = false;
+
+    println!("Position: {}", board.fen());
+
+    for d in 1..=depth {
+        let count = perft(&board, d);
+
+        match reference.get(d as usize) {
+            Some(&expected) if expected != count => {
+                println!("perft({}) = {} (expected {})", d, count, expected);
+                mismatch = true;
+            }
+            _ => println!("perft({}) = {}", d, count),
+        }
+    }
+
+    if mismatch {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,400 @@
+//! Charts of tuning experiments as SVG, drawn from the CSV results that
+//! `tournament --output` and `tune --output` write:
+//!
+//! - `win-rate`: the score of every entrant by iteration budget, from one
+//!   or more tournaments played at different `--iterations`
+//! - `elo`: the rating against the baseline by the value of one parameter,
+//!   from a tuning run
+
+use std::fmt::Write;
+use std::fs;
+use cli;
+use results::{self, Row};
+
+/// Okabe-Ito colors, told apart with color blindness too
+const PALETTE: [&str; 7] = [
+    "#0072b2", "#d55e00", "#009e73", "#cc79a7", "#e69f00", "#56b4e9", "#000000",
+];
+
+const WIDTH: f64 = 640.;
+const HEIGHT: f64 = 400.;
+/// Space around the plot area for the title, the axes and the legend
+const LEFT: f64 = 70.;
+const RIGHT: f64 = 170.;
+const TOP: f64 = 40.;
+const BOTTOM: f64 = 50.;
+
+/// The points of a series, values by x category
+struct Series {
+    name: String,
+    points: Vec<(usize, f64)>,
+}
+
+struct Chart {
+    title: String,
+    x_label: &'static str,
+    y_label: &'static str,
+    /// Evenly spaced along the x axis
+    categories: Vec<String>,
+    /// Values and labels from the bottom of the y axis to the top
+    y_ticks: Vec<(f64, String)>,
+    series: Vec<Series>,
+}
+
+/// Points scored in a number of games
+#[derive(Debug, Default, Copy, Clone)]
+struct Score {
+    points: f64,
+    games: u32,
+}
+
+impl Score {
+    fn add(&mut self, points: f64) {
+        self.points += points;
+        self.games += 1;
+    }
+
+    fn ratio(&self) -> f64 {
+        self.points / f64::from(self.games)
+    }
+
+    /// The rating difference the score implies, kept finite for a clean
+    /// sweep by counting it as half a game short of one
+    fn elo(&self) -> f64 {
+        let margin = 0.5 / f64::from(self.games);
+        let ratio = self.ratio().max(margin).min(1. - margin);
+        400. * (ratio / (1. - ratio)).log10()
+    }
+}
+
+fn field<'a>(row: &'a Row, column: &str, source: &str) -> Result<&'a str, String> {
+    row.get(column)
+        .map(String::as_str)
+        .ok_or_else(|| format!("Missing column {}, expected the results of {}", column, source))
+}
+
+/// Points for `player` (`x` or `o`) from the `winner` column
+fn points(winner: &str, player: &str) -> f64 {
+    match winner {
+        "draw" => 0.5,
+        _ if winner == player => 1.,
+        _ => 0.,
+    }
+}
+
+/// Add `key` to `keys` unless it's there yet, returning its index
+fn index_of(keys: &mut Vec<String>, key: &str) -> usize {
+    keys.iter().position(|k| k == key).unwrap_or_else(|| {
+        keys.push(key.to_string());
+        keys.len() - 1
+    })
+}
+
+/// Sort numeric categories by value, others keep their order of appearance.
+/// Returns where each category went.
+fn sort_categories(categories: &mut Vec<String>) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..categories.len()).collect();
+    let values: Option<Vec<f64>> = categories.iter().map(|c| c.parse().ok()).collect();
+    if let Some(values) = values {
+        order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+    }
+
+    *categories = order.iter().map(|&i| categories[i].clone()).collect();
+    let mut position = vec![0; order.len()];
+    for (new, &old) in order.iter().enumerate() {
+        position[old] = new;
+    }
+    position
+}
+
+/// Scores by series and category, as the points of a chart
+fn series(
+    names: Vec<String>,
+    categories: &mut Vec<String>,
+    scores: &[Vec<Score>],
+    value: fn(&Score) -> f64,
+) -> Vec<Series> {
+    let position = sort_categories(categories);
+
+    names
+        .into_iter()
+        .zip(scores)
+        .map(|(name, scores)| {
+            let mut points: Vec<(usize, f64)> = scores
+                .iter()
+                .enumerate()
+                .filter(|&(_, score)| score.games > 0)
+                .map(|(category, score)| (position[category], value(score)))
+                .collect();
+            points.sort_by_key(|&(category, _)| category);
+            Series { name, points }
+        })
+        .collect()
+}
+
+/// Grow `scores` to hold `names` by `categories`
+fn resize(scores: &mut Vec<Vec<Score>>, names: usize, categories: usize) {
+    scores.resize(names, Vec::new());
+    for row in scores.iter_mut() {
+        row.resize(categories, Score::default());
+    }
+}
+
+fn win_rate(files: &[String]) -> Result<Chart, String> {
+    const SOURCE: &str = "tournament --output";
+    let mut names = Vec::new();
+    let mut categories = Vec::new();
+    let mut scores: Vec<Vec<Score>> = Vec::new();
+
+    for file in files {
+        for row in results::read(file)? {
+            let iterations = index_of(&mut categories, field(&row, "iterations", SOURCE)?);
+            let winner = field(&row, "winner", SOURCE)?;
+
+            for &player in &["x", "o"] {
+                let name = index_of(&mut names, field(&row, player, SOURCE)?);
+                resize(&mut scores, names.len(), categories.len());
+                scores[name][iterations].add(points(winner, player));
+            }
+        }
+    }
+
+    Ok(Chart {
+        title: "Score by iteration budget".to_string(),
+        x_label: "Iterations per move",
+        y_label: "Score",
+        y_ticks: (0..=4).map(|i| (f64::from(i) * 25., format!("{}%", i * 25))).collect(),
+        series: series(names, &mut categories, &scores, |score| score.ratio() * 100.),
+        categories,
+    })
+}
+
+/// The value of `parameter` in a configuration as the subcommands print it,
+/// e.g. `selection = Ucb1, exploration = 1.41, ...`
+fn parameter_value<'a>(config: &'a str, parameter: &str) -> Option<&'a str> {
+    config
+        .split(", ")
+        .filter_map(|pair| pair.split_once(" = "))
+        .find(|&(key, _)| key == parameter)
+        .map(|(_, value)| value)
+}
+
+fn elo(files: &[String], parameter: &str) -> Result<Chart, String> {
+    const SOURCE: &str = "tune --output";
+    let mut categories = Vec::new();
+    let mut scores = vec![Vec::new()];
+
+    for file in files {
+        for row in results::read(file)? {
+            let config = field(&row, "config", SOURCE)?;
+            let value = parameter_value(config, parameter)
+                .ok_or_else(|| format!("Unknown parameter {} in [{}]", parameter, config))?;
+            let value = index_of(&mut categories, value);
+            resize(&mut scores, 1, categories.len());
+
+            let winner = field(&row, "winner", SOURCE)?;
+            scores[0][value].add(points(winner, field(&row, "config_player", SOURCE)?));
+        }
+    }
+
+    let series = series(vec![parameter.to_string()], &mut categories, &scores, Score::elo);
+
+    // Symmetric around zero, in steps of a quarter
+    let highest = series[0].points.iter().map(|&(_, elo)| elo.abs()).fold(0., f64::max);
+    let range = ((highest / 100.).ceil() * 100.).max(100.);
+    let y_ticks = (-4..=4)
+        .map(|i| {
+            let elo = range * f64::from(i) / 4.;
+            let label = if i == 0 { "0".to_string() } else { format!("{:+}", elo.round()) };
+            (elo, label)
+        })
+        .collect();
+
+    Ok(Chart {
+        title: format!("Elo against the baseline by {}", parameter),
+        x_label: "Parameter value",
+        y_label: "Elo",
+        categories,
+        y_ticks,
+        series,
+    })
+}
+
+/// Escape text for SVG
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn svg(chart: &Chart) -> String {
+    let mut out = String::new();
+    let (width, height) = (WIDTH - LEFT - RIGHT, HEIGHT - TOP - BOTTOM);
+    let (low, high) = (chart.y_ticks[0].0, chart.y_ticks[chart.y_ticks.len() - 1].0);
+
+    let columns = chart.categories.len() as f64;
+    let x = |category: usize| LEFT + width * (category as f64 + 0.5) / columns;
+    let y = |value: f64| TOP + height * (1. - (value - low) / (high - low));
+
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+         viewBox=\"0 0 {0} {1}\" font-family=\"sans-serif\" font-size=\"12\">",
+        WIDTH, HEIGHT
+    )
+    .unwrap();
+    writeln!(out, "  <rect width=\"{}\" height=\"{}\" fill=\"#ffffff\"/>", WIDTH, HEIGHT).unwrap();
+    writeln!(
+        out,
+        "  <text x=\"{}\" y=\"24\" text-anchor=\"middle\" font-size=\"16\">{}</text>",
+        LEFT + width / 2.,
+        escape(&chart.title)
+    )
+    .unwrap();
+
+    for &(value, ref label) in &chart.y_ticks {
+        writeln!(
+            out,
+            "  <line x1=\"{}\" y1=\"{2:.1}\" x2=\"{}\" y2=\"{2:.1}\" stroke=\"#dddddd\"/>",
+            LEFT,
+            LEFT + width,
+            y(value)
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "  <text x=\"{}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>",
+            LEFT - 8.,
+            y(value) + 4.,
+            label
+        )
+        .unwrap();
+    }
+
+    for (i, category) in chart.categories.iter().enumerate() {
+        writeln!(
+            out,
+            "  <text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            x(i),
+            TOP + height + 18.,
+            escape(category)
+        )
+        .unwrap();
+    }
+
+    writeln!(
+        out,
+        "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#333333\"/>",
+        LEFT, TOP, width, height
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "  <text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+        LEFT + width / 2.,
+        HEIGHT - 10.,
+        chart.x_label
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "  <text x=\"18\" y=\"{0}\" text-anchor=\"middle\" \
+         transform=\"rotate(-90 18 {0})\">{1}</text>",
+        TOP + height / 2.,
+        chart.y_label
+    )
+    .unwrap();
+
+    for (i, series) in chart.series.iter().enumerate() {
+        let color = PALETTE[i % PALETTE.len()];
+        let points: Vec<String> = series
+            .points
+            .iter()
+            .map(|&(category, value)| format!("{:.1},{:.1}", x(category), y(value)))
+            .collect();
+
+        writeln!(
+            out,
+            "  <polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>",
+            points.join(" "),
+            color
+        )
+        .unwrap();
+        for &(category, value) in &series.points {
+            writeln!(
+                out,
+                "  <circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3.5\" fill=\"{}\"/>",
+                x(category),
+                y(value),
+                color
+            )
+            .unwrap();
+        }
+
+        let legend = TOP + 10. + i as f64 * 20.;
+        writeln!(
+            out,
+            "  <rect x=\"{}\" y=\"{}\" width=\"12\" height=\"12\" fill=\"{}\"/>",
+            LEFT + width + 16.,
+            legend,
+            color
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "  <text x=\"{}\" y=\"{}\">{}</text>",
+            LEFT + width + 34.,
+            legend + 10.,
+            escape(&series.name)
+        )
+        .unwrap();
+    }
+
+    out.push_str("</svg>\n");
+    out
+}
+
+/// Plot the results of tournaments or a tuning run
+pub fn run(args: &[String]) {
+    let mut kind: Option<&String> = None;
+    let mut files = Vec::new();
+    let mut parameter: Option<String> = None;
+    let mut out: Option<String> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--parameter" => parameter = Some(cli::value(arg, args.next())),
+            "--out" => out = Some(cli::value(arg, args.next())),
+            _ if arg.starts_with("--") => cli::fail(&format!("Unknown option: {}", arg)),
+            _ if kind.is_none() => kind = Some(arg),
+            _ => files.push(arg.clone()),
+        }
+    }
+
+    if files.is_empty() {
+        cli::fail("Missing results file");
+    }
+    if out.as_ref().is_some_and(|out| !out.ends_with(".svg")) {
+        cli::fail("Charts can only be written as .svg");
+    }
+
+    let chart = match (kind.map(String::as_str), parameter) {
+        (Some("win-rate"), None) => win_rate(&files),
+        (Some("elo"), Some(parameter)) => elo(&files, &parameter),
+        (Some("elo"), None) => cli::fail("The elo chart needs --parameter"),
+        (Some("win-rate"), Some(_)) => cli::fail("--parameter is only for the elo chart"),
+        _ => cli::fail("Expected the chart to draw, win-rate or elo"),
+    };
+    let chart = chart.unwrap_or_else(|e| cli::fail(&e));
+    if chart.categories.is_empty() {
+        cli::fail("No games to plot");
+    }
+
+    match out {
+        Some(path) => {
+            fs::write(&path, svg(&chart))
+                .unwrap_or_else(|e| cli::fail(&format!("Could not write {}: {}", path, e)));
+            println!("Wrote {}", path);
+        }
+        None => print!("{}", svg(&chart)),
+    }
+}
@@ -0,0 +1,242 @@
+use std::time::{Duration, Instant};
+use game::{Board, Coord, Player};
+use minimax;
+
+/// Proof and disproof number of a node that can no longer be (dis)proven
+const INFINITY: u64 = u64::MAX;
+
+/// Outcome of a proof-number search
+#[derive(Debug, Copy, Clone)]
+pub struct Proof {
+    /// Game-theoretic value for the player to move: 1 (win), 0 (draw) or -1 (loss)
+    pub value: i32,
+    /// A move achieving that value, the longest defence when lost. `None` if
+    /// the game is over.
+    pub action: Option<Coord>,
+    /// Nodes expanded over all searches
+    pub nodes: usize,
+    pub elapsed: Duration,
+}
+
+/// What the attacker tries to prove
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Goal {
+    Win,
+    /// A win or a draw
+    NoLoss,
+}
+
+#[derive(Debug)]
+struct Node {
+    board: Board,
+    action: Option<Coord>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    proof: u64,
+    disproof: u64,
+}
+
+/// A single proof-number search tree, proving one goal for the attacker
+struct Search {
+    nodes: Vec<Node>,
+    attacker: Player,
+    goal: Goal,
+    expanded: usize,
+}
+
+impl Search {
+    fn new(board: Board, goal: Goal) -> Search {
+        let mut search = Search {
+            nodes: Vec::new(),
+            attacker: board.next_player(),
+            goal,
+            expanded: 0,
+        };
+        let (proof, disproof) = search.initial_numbers(&board);
+        search.nodes.push(Node {
+            board,
+            action: None,
+            parent: None,
+            children: Vec::new(),
+            proof,
+            disproof,
+        });
+
+        search
+    }
+
+    /// Proof and disproof number of a freshly generated node
+    fn initial_numbers(&self, board: &Board) -> (u64, u64) {
+        match board.get_reward(self.attacker) {
+            Some(reward) if reward == 1 || (reward == 0 && self.goal == Goal::NoLoss) => {
+                (0, INFINITY)
+            }
+            Some(_) => (INFINITY, 0),
+            None => (1, 1),
+        }
+    }
+
+    /// Search until the goal is either proven or disproven
+    fn prove(&mut self) -> bool {
+        while self.nodes[0].proof != 0 && self.nodes[0].disproof != 0 {
+            let node = self.most_proving_node();
+            self.expand(node);
+            self.update_ancestors(node);
+        }
+
+        self.nodes[0].proof == 0
+    }
+
+    /// The root's first move that satisfies the goal
+    fn proving_action(&self) -> Option<Coord> {
+        self.nodes[0]
+            .children
+            .iter()
+            .map(|&child| &self.nodes[child])
+            .find(|child| child.proof == 0)
+            .and_then(|child| child.action)
+    }
+
+    /// Where the attacker moves, one good child is enough (OR node), otherwise
+    /// all children have to work out (AND node)
+    fn is_or_node(&self, node: usize) -> bool {
+        self.nodes[node].board.next_player() == self.attacker
+    }
+
+    fn most_proving_node(&self) -> usize {
+        let mut node = 0;
+
+        while !self.nodes[node].children.is_empty() {
+            let children = self.nodes[node].children.iter().cloned();
+            node = if self.is_or_node(node) {
+                children.min_by_key(|&child| self.nodes[child].proof)
+            } else {
+                children.min_by_key(|&child| self.nodes[child].disproof)
+            }
+            .expect("Expanded node without children");
+        }
+
+        node
+    }
+
+    fn expand(&mut self, node: usize) {
+        let board = self.nodes[node].board;
+
+        for action in board.actions_iter() {
+            let mut child = board;
+            child.perform_action(action);
+
+            let (proof, disproof) = self.initial_numbers(&child);
+            let index = self.nodes.len();
+            self.nodes.push(Node {
+                board: child,
+                action: Some(action),
+                parent: Some(node),
+                children: Vec::new(),
+                proof,
+                disproof,
+            });
+            self.nodes[node].children.push(index);
+        }
+
+        self.expanded += 1;
+        self.set_numbers(node);
+    }
+
+    fn set_numbers(&mut self, node: usize) {
+        let children = &self.nodes[node].children;
+        let proofs = children.iter().map(|&child| self.nodes[child].proof);
+        let disproofs = children.iter().map(|&child| self.nodes[child].disproof);
+
+        let (proof, disproof) = if self.is_or_node(node) {
+            (proofs.min(), Some(disproofs.fold(0, u64::saturating_add)))
+        } else {
+            (Some(proofs.fold(0, u64::saturating_add)), disproofs.min())
+        };
+
+        let node = &mut self.nodes[node];
+        node.proof = proof.unwrap_or(INFINITY);
+        node.disproof = disproof.unwrap_or(INFINITY);
+    }
+
+    fn update_ancestors(&mut self, node: usize) {
+        let mut parent = self.nodes[node].parent;
+
+        while let Some(node) = parent {
+            self.set_numbers(node);
+            parent = self.nodes[node].parent;
+        }
+    }
+}
+
+/// In a lost position, the move holding out the longest
+fn longest_defence(board: &Board) -> Option<Coord> {
+    let mut minimax = minimax::shared();
+
+    // Reversed so that of equally long defences the first is taken
+    board.actions_iter().rev().max_by_key(|&action| {
+        let mut child = *board;
+        child.perform_action(action);
+        minimax.exact(&child).moves
+    })
+}
+
+/// Prove the game-theoretic value of a position: first whether the player to
+/// move wins, and if not, whether they can at least hold the draw
+pub fn solve(board: &Board) -> Proof {
+    let start = Instant::now();
+
+    if let Some(value) = board.get_reward(board.next_player()) {
+        return Proof {
+            value,
+            action: None,
+            nodes: 0,
+            elapsed: start.elapsed(),
+        };
+    }
+
+    let mut win = Search::new(*board, Goal::Win);
+    let (value, action, nodes) = if win.prove() {
+        (1, win.proving_action(), win.expanded)
+    } else {
+        let mut draw = Search::new(*board, Goal::NoLoss);
+        if draw.prove() {
+            (0, draw.proving_action(), win.expanded + draw.expanded)
+        } else {
+            (-1, longest_defence(board), win.expanded + draw.expanded)
+        }
+    };
+
+    Proof {
+        value,
+        action,
+        nodes,
+        elapsed: start.elapsed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::{Board, Player};
+    use super::solve;
+
+    #[test]
+    fn proves_a_win() {
+        let proof = solve(&Board::from_fen("xx./oo./... o").unwrap());
+        assert_eq!(proof.value, 1);
+        assert_eq!(proof.action, Some("2c".parse().unwrap()));
+    }
+
+    #[test]
+    fn proves_a_draw() {
+        assert_eq!(solve(&Board::new(Player::X)).value, 0);
+    }
+
+    #[test]
+    fn defends_a_lost_position_as_long_as_possible() {
+        // Only blocking 1c keeps X from winning on the next move
+        let proof = solve(&Board::from_fen("xx./o.x/o.. o").unwrap());
+        assert_eq!(proof.value, -1);
+        assert_eq!(proof.action, Some("1c".parse().unwrap()));
+    }
+}
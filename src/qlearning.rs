@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use rand::{self, Rng};
+use cli;
+use game::{Board, Coord, Player};
+
+/// How far a single update moves a value towards its target
+const LEARNING_RATE: f64 = 0.3;
+/// Share of moves played at random while learning, to keep exploring
+const EXPLORATION_RATE: f64 = 0.2;
+
+pub const DEFAULT_EPISODES: u32 = 50000;
+pub const DEFAULT_TABLE: &str = "q-table.txt";
+
+/// Learned values of (position, action) pairs for the player to move,
+/// 1 meaning a sure win and -1 a sure loss. Symmetric positions share their
+/// entries.
+#[derive(Debug, Default)]
+pub struct QTable {
+    values: HashMap<(Board, Coord), f64>,
+}
+
+impl QTable {
+    pub fn new() -> QTable {
+        QTable::default()
+    }
+
+    /// The table entry for playing `action` on `board`
+    fn key(board: &Board, action: Coord) -> (Board, Coord) {
+        let (canonical, symmetry) = board.canonical();
+        (canonical, symmetry.apply(action))
+    }
+
+    /// What playing `action` is worth according to the table, unknown actions
+    /// being neutral
+    pub fn value(&self, board: &Board, action: Coord) -> f64 {
+        self.values.get(&QTable::key(board, action)).cloned().unwrap_or(0.)
+    }
+
+    /// The highest valued action along with its value
+    pub fn best_action(&self, board: &Board) -> Option<(Coord, f64)> {
+        board
+            .actions_iter()
+            .map(|action| (action, self.value(board, action)))
+            .fold(None, |best, (action, value)| match best {
+                Some((_, best_value)) if best_value >= value => best,
+                _ => Some((action, value)),
+            })
+    }
+
+    /// Learn from games the agent plays against itself. Both sides share the
+    /// table, a move being worth what the opponent's best reply is not.
+    pub fn learn(&mut self, episodes: u32) {
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..episodes {
+            let mut board = Board::new(Player::X);
+
+            while !board.is_ended() {
+                let action = match self.best_action(&board) {
+                    Some((action, _)) if rng.gen::<f64>() >= EXPLORATION_RATE => action,
+                    _ => *rng.choose(&board.get_actions()).expect("No legal action"),
+                };
+
+                let mut next = board;
+                next.perform_action(action);
+
+                let target = match next.get_reward(board.next_player()) {
+                    Some(reward) => f64::from(reward),
+                    None => -self.best_action(&next).map_or(0., |(_, value)| value),
+                };
+
+                let value = self.values.entry(QTable::key(&board, action)).or_insert(0.);
+                *value += LEARNING_RATE * (target - *value);
+
+                board = next;
+            }
+        }
+    }
+
+    /// Number of (position, action) pairs with a value
+    pub fn entries(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Read a table written by `save`, one `<position> <action> <value>` per line
+    pub fn load(path: &str) -> Result<QTable, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        let mut table = QTable::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let entry = match parts[..] {
+                [rows, player, action, value] => {
+                    let board = Board::from_fen(&format!("{} {}", rows, player)).ok();
+                    board.and_then(|board| {
+                        Some((board, action.parse().ok()?, value.parse().ok()?))
+                    })
+                }
+                _ => None,
+            };
+
+            match entry {
+                Some((board, action, value)) => {
+                    table.values.insert((board, action), value);
+                }
+                None => return Err(format!("{}: Line {}: Invalid entry", path, number + 1)),
+            }
+        }
+
+        Ok(table)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut entries: Vec<String> = self
+            .values
+            .iter()
+            .map(|(&(board, action), value)| {
+                format!("{} {} {:.4}", board.fen(), action, value)
+            })
+            .collect();
+        entries.sort();
+
+        let mut text = entries.join("\n");
+        text.push('\n');
+        fs::write(path, text).map_err(|e| format!("Could not write {}: {}", path, e))
+    }
+
+    /// The table in `path`, or a freshly learned one if there is none yet
+    pub fn load_or_learn(path: &str) -> Result<QTable, String> {
+        match fs::metadata(path) {
+            Ok(_) => QTable::load(path),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                println!("No Q-table in {}, learning from {} games", path, DEFAULT_EPISODES);
+
+                let mut table = QTable::new();
+                table.learn(DEFAULT_EPISODES);
+                table.save(path)?;
+                Ok(table)
+            }
+            Err(e) => Err(format!("Could not read {}: {}", path, e)),
+        }
+    }
+}
+
+/// Train the Q-learning agent by self-play, continuing from an existing table
+pub fn run(args: &[String]) {
+    let mut episodes = DEFAULT_EPISODES;
+    let mut path = DEFAULT_TABLE.to_string();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--episodes" => episodes = cli::value(arg, args.next()),
+            "--table" => path = cli::value(arg, args.next()),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    let mut table = if fs::metadata(&path).is_ok() {
+        QTable::load(&path).unwrap_or_else(|e| cli::fail(&e))
+    } else {
+        QTable::new()
+    };
+
+    table.learn(episodes);
+    table.save(&path).unwrap_or_else(|e| cli::fail(&e));
+
+    println!("Learned from {} games, {} entries in {}", episodes, table.entries(), path);
+}
@@ -0,0 +1,584 @@
+use std::fmt::{self, Display};
+use std::fs;
+use std::time::Duration;
+use game::{Board, Coord, GameStatus, Player};
+use rules::Variant;
+
+/// A move of a recorded game, optionally annotated
+#[derive(Debug, Clone)]
+pub struct Move {
+    pub action: Coord,
+    pub comment: Option<String>,
+    /// How long the player thought about the move, if it was timed
+    pub time: Option<Duration>,
+}
+
+/// A game as a start position and the moves played from it. Saved as text,
+/// one move per line:
+///
+/// ```text
+/// # Lines starting with # are ignored
+/// variant misere
+/// position x.o/.../... x
+/// 2b 1.52s ; Takes the center
+/// 1a 0.30s
+/// result draw
+/// ```
+///
+/// The `variant` line is omitted for standard games, the `position` line for
+/// games started from the empty board, the `result` line for unfinished
+/// games. A handicap's stones follow the
+/// position, e.g. `stones x 2b 1a` for X holding 2b and 1a from the start.
+/// Under the pie rule, a `swap` line follows the first move if the second
+/// player took it over. Moves may be followed by the time the player took
+/// for them.
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    pub start: Board,
+    pub moves: Vec<Move>,
+    /// The sides were swapped after the first move
+    pub swapped: bool,
+    /// Handicap stones of a player, put on `start` before the first move
+    pub stones: Option<(Player, Vec<Coord>)>,
+}
+
+impl GameRecord {
+    pub fn new(start: Board) -> GameRecord {
+        GameRecord {
+            start,
+            moves: Vec::new(),
+            swapped: false,
+            stones: None,
+        }
+    }
+
+    pub fn push(&mut self, action: Coord) {
+        self.moves.push(Move {
+            action,
+            comment: None,
+            time: None,
+        });
+    }
+
+    /// Add a move that the player took `time` for
+    pub fn push_timed(&mut self, action: Coord, time: Duration) {
+        self.push(action);
+        self.moves.last_mut().expect("Move just added").time = Some(time);
+    }
+
+    /// Attach a comment to the last move, replacing any previous one
+    pub fn annotate(&mut self, comment: &str) -> Result<(), String> {
+        let last = self.moves.last_mut().ok_or("No move to annotate")?;
+        last.comment = Some(comment.trim().to_string());
+        Ok(())
+    }
+
+    /// Whether the game started from the empty board with X to move, whatever
+    /// the variant
+    fn starts_empty(&self) -> bool {
+        let empty = Board::new(Player::X);
+        self.start.fields == empty.fields && self.start.next_player == empty.next_player
+    }
+
+    /// The position of the first move: the start position with any handicap
+    /// stones
+    pub fn first_board(&self) -> Board {
+        let mut board = self.start;
+        if let Some((player, ref stones)) = self.stones {
+            for &stone in stones {
+                board.place(stone, player);
+            }
+        }
+
+        board
+    }
+
+    /// The positions of the game, starting with the one of the first move
+    pub fn boards(&self) -> Vec<Board> {
+        let mut board = self.first_board();
+        let mut boards = vec![board];
+
+        for (i, m) in self.moves.iter().enumerate() {
+            board.perform_action(m.action);
+            if i == 0 && self.swapped {
+                board.swap();
+            }
+            boards.push(board);
+        }
+
+        boards
+    }
+
+    pub fn board(&self) -> Board {
+        *self.boards().last().expect("No positions")
+    }
+
+    /// How long `player` thought over the game, e.g. `12.3s, 2.5s a move,
+    /// longest 6.1s on 3. 1a`. `None` if none of their moves were timed.
+    pub fn thinking_time(&self, player: Player) -> Option<String> {
+        let boards = self.boards();
+        let timed: Vec<(usize, &Move, Duration)> = self
+            .moves
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| boards[i].next_player() == player)
+            .filter_map(|(i, m)| m.time.map(|time| (i, m, time)))
+            .collect();
+
+        let total: Duration = timed.iter().map(|&(_, _, time)| time).sum();
+        let &(i, longest, time) = timed.iter().max_by_key(|&&(_, _, time)| time)?;
+
+        Some(format!(
+            "{:.1}s, {:.1}s a move, longest {:.1}s on {}. {}",
+            total.as_secs_f64(),
+            total.as_secs_f64() / timed.len() as f64,
+            time.as_secs_f64(),
+            i + 1,
+            longest.action
+        ))
+    }
+
+    pub fn parse(text: &str) -> Result<GameRecord, String> {
+        let mut record = GameRecord::new(Board::new(Player::X));
+        let mut board = record.start;
+        let mut result = None;
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let error = |message: &str| Err(format!("Line {}: {}", i + 1, message));
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if result.is_some() {
+                return error("Moves after the result");
+            }
+
+            if let Some(name) = line.strip_prefix("variant ") {
+                if !record.moves.is_empty() || record.stones.is_some() {
+                    return error("Variant after the first move");
+                }
+
+                let variant = match Variant::by_name(name.trim()) {
+                    Some(variant) => variant,
+                    None => return error(&format!("Unknown variant {}", name.trim())),
+                };
+                record.start.variant = variant;
+                board.variant = variant;
+                continue;
+            }
+
+            if let Some(fen) = line.strip_prefix("position ") {
+                if !record.moves.is_empty() {
+                    return error("Position after the first move");
+                }
+
+                let variant = record.start.variant;
+                record.start = Board::from_fen(fen)?;
+                record.start.variant = variant;
+                board = record.start;
+                continue;
+            }
+
+            if let Some(stones) = line.strip_prefix("stones ") {
+                if !record.moves.is_empty() || record.stones.is_some() {
+                    return error("Stones other than once before the first move");
+                }
+
+                let mut stones = stones.split_whitespace();
+                let player = match stones.next() {
+                    Some("x") => Player::X,
+                    Some("o") => Player::O,
+                    _ => return error("Expected the player the stones are for"),
+                };
+                let stones: Vec<Coord> =
+                    stones.map(|cell| cell.parse()).collect::<Result<_, _>>()?;
+                for &stone in &stones {
+                    if !board.is_legal_action(stone) {
+                        return error(&format!("Stone {} on an occupied cell", stone));
+                    }
+                    board.place(stone, player);
+                }
+
+                record.stones = Some((player, stones));
+                continue;
+            }
+
+            if line == "swap" {
+                if record.moves.len() != 1 || record.swapped {
+                    return error("Swap other than right after the first move");
+                }
+
+                record.swapped = true;
+                board.swap();
+                continue;
+            }
+
+            if let Some(value) = line.strip_prefix("result ") {
+                result = Some(match value.trim() {
+                    "x" => Some(Player::X),
+                    "o" => Some(Player::O),
+                    "draw" => None,
+                    _ => return error("Invalid result"),
+                });
+                continue;
+            }
+
+            let (action, comment) = match line.split_once(';') {
+                Some((action, comment)) => (action.trim(), Some(comment.trim().to_string())),
+                None => (line, None),
+            };
+            let (action, time) = match action.split_once(' ') {
+                Some((action, time)) => {
+                    let seconds = time.trim().strip_suffix('s').and_then(|s| s.parse().ok());
+                    match seconds.and_then(|s| Duration::try_from_secs_f64(s).ok()) {
+                        Some(time) => (action, Some(time)),
+                        None => return error(&format!("Invalid time {}", time.trim())),
+                    }
+                }
+                None => (action, None),
+            };
+
+            let action = match action.parse::<Coord>().ok() {
+                Some(action) if board.is_legal_action(action) && !board.is_ended() => action,
+                Some(_) => {
+                    let number = record.moves.len() + 1;
+                    return error(&format!("Illegal move {} (move {})", action, number));
+                }
+                None => return error(&format!("Invalid move {}", action)),
+            };
+
+            board.perform_action(action);
+            record.moves.push(Move {
+                action,
+                comment,
+                time,
+            });
+        }
+
+        if let Some(winner) = result {
+            let status = board.status();
+            if status == GameStatus::InProgress || status.winner() != winner {
+                let played = match status {
+                    GameStatus::InProgress => "the game is unfinished".to_string(),
+                    GameStatus::Won(player, _) => format!("Player {} wins", player),
+                    GameStatus::Draw => "it's a draw".to_string(),
+                };
+                let recorded = match winner {
+                    Some(player) => format!("Player {} wins", player),
+                    None => "a draw".to_string(),
+                };
+                return Err(format!(
+                    "Result does not match the moves: recorded {}, but {}",
+                    recorded, played
+                ));
+            }
+        }
+
+        Ok(record)
+    }
+
+    /// The game as a short code that can be put into a URL unescaped, e.g.
+    /// `5s19` for 2b, a swap, 1a and 3c. Moves are cells numbered 1 to 9 row
+    /// by row, `s` marking a swap after the first move. A variant other than
+    /// the standard game comes first, followed by `~`: `misere~5s19`. Then a
+    /// start position other than the empty board, as its cells (`x`, `o` or
+    /// `-`) and the side to move, followed by `.`: `x---o----x.37`. Handicap
+    /// stones come after that, as the player holding them and their cells,
+    /// followed by `+`: `x41+53`.
+    pub fn share_code(&self) -> String {
+        let mut code = String::new();
+
+        if self.start.variant != Variant::STANDARD {
+            code.push_str(self.start.variant.name());
+            code.push('~');
+        }
+
+        if !self.starts_empty() {
+            let fen = self.start.fen();
+            code.extend(fen.chars().filter(|&c| c != '/' && c != ' ').map(|c| match c {
+                '.' => '-',
+                c => c,
+            }));
+            code.push('.');
+        }
+
+        if let Some((player, ref stones)) = self.stones {
+            code.push_str(&player.to_string().to_lowercase());
+            code.extend(stones.iter().map(|stone| char::from(b'1' + stone.index() as u8)));
+            code.push('+');
+        }
+
+        for (i, m) in self.moves.iter().enumerate() {
+            code.push(char::from(b'1' + m.action.index() as u8));
+            if i == 0 && self.swapped {
+                code.push('s');
+            }
+        }
+
+        code
+    }
+
+    /// Read a game from its share code, checking that the moves are legal
+    pub fn from_share_code(code: &str) -> Result<GameRecord, String> {
+        let (variant, code) = match code.trim().split_once('~') {
+            Some((name, code)) => match Variant::by_name(name) {
+                Some(variant) => (variant, code),
+                None => return Err(format!("Unknown variant in the code: {}", name)),
+            },
+            None => (Variant::STANDARD, code.trim()),
+        };
+        let (position, moves) = match code.split_once('.') {
+            Some((position, moves)) => (Some(position), moves),
+            None => (None, code),
+        };
+
+        let mut record = match position {
+            Some(position) if position.len() == 10 && position.is_ascii() => {
+                let cells = position.replace('-', ".");
+                let fen = format!(
+                    "{}/{}/{} {}",
+                    &cells[0..3],
+                    &cells[3..6],
+                    &cells[6..9],
+                    &cells[9..]
+                );
+                GameRecord::new(Board::from_fen(&fen)?)
+            }
+            Some(_) => return Err("Invalid position in the code".to_string()),
+            None => GameRecord::new(Board::new(Player::X)),
+        };
+        record.start.variant = variant;
+        let mut board = record.start;
+
+        let moves = match moves.split_once('+') {
+            Some((stones, moves)) => {
+                let mut cells = stones.chars();
+                let player = match cells.next() {
+                    Some('x') => Player::X,
+                    Some('o') => Player::O,
+                    _ => return Err("Invalid handicap stones in the code".to_string()),
+                };
+
+                let mut stones = Vec::new();
+                for c in cells {
+                    let stone = match c {
+                        '1'..='9' => Coord::from_index((c as u8 - b'1') as usize).unwrap(),
+                        _ => return Err(format!("Invalid character in the code: {}", c)),
+                    };
+                    if !board.is_legal_action(stone) {
+                        return Err(format!("Stone {} on an occupied cell", stone));
+                    }
+                    board.place(stone, player);
+                    stones.push(stone);
+                }
+
+                record.stones = Some((player, stones));
+                moves
+            }
+            None => moves,
+        };
+
+        for c in moves.chars() {
+            match c {
+                's' if record.moves.len() == 1 && !record.swapped => {
+                    record.swapped = true;
+                    board.swap();
+                }
+                '1'..='9' => {
+                    let action = Coord::from_index((c as u8 - b'1') as usize).unwrap();
+                    if board.is_ended() || !board.is_legal_action(action) {
+                        return Err(format!("Illegal move {}", action));
+                    }
+
+                    board.perform_action(action);
+                    record.push(action);
+                }
+                _ => return Err(format!("Invalid character in the code: {}", c)),
+            }
+        }
+
+        Ok(record)
+    }
+
+    pub fn load(path: &str) -> Result<GameRecord, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        GameRecord::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_string()).map_err(|e| format!("Could not write {}: {}", path, e))
+    }
+}
+
+impl Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        if self.start.variant != Variant::STANDARD {
+            writeln!(f, "variant {}", self.start.variant.name())?;
+        }
+        if !self.starts_empty() {
+            writeln!(f, "position {}", self.start.fen())?;
+        }
+        if let Some((player, ref stones)) = self.stones {
+            let stones: Vec<String> = stones.iter().map(|s| s.to_string()).collect();
+            writeln!(f, "stones {} {}", player.to_string().to_lowercase(), stones.join(" "))?;
+        }
+
+        for (i, m) in self.moves.iter().enumerate() {
+            write!(f, "{}", m.action)?;
+            if let Some(time) = m.time {
+                write!(f, " {:.2}s", time.as_secs_f64())?;
+            }
+            match m.comment {
+                Some(ref comment) => writeln!(f, " ; {}", comment)?,
+                None => writeln!(f)?,
+            }
+
+            if i == 0 && self.swapped {
+                writeln!(f, "swap")?;
+            }
+        }
+
+        let board = self.board();
+        match board.status() {
+            GameStatus::Won(Player::X, _) => writeln!(f, "result x")?,
+            GameStatus::Won(Player::O, _) => writeln!(f, "result o")?,
+            GameStatus::Draw => writeln!(f, "result draw")?,
+            GameStatus::InProgress => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use game::{Board, Coord, GameStatus, Player};
+    use rules::{self, Variant};
+    use super::GameRecord;
+
+    #[test]
+    fn misere_game_survives_save_and_load() {
+        rules::register_builtins();
+        let misere = Variant::by_name("misere").unwrap();
+
+        // X completes the top row and loses
+        let mut record = GameRecord::new(Board::with_variant(Player::X, misere));
+        for cell in &["1a", "2a", "1b", "2b", "1c"] {
+            record.push(cell.parse::<Coord>().unwrap());
+        }
+
+        let text = record.to_string();
+        assert!(text.starts_with("variant misere\n"));
+        assert!(!text.contains("position"));
+
+        let path = env::temp_dir().join(format!("mcts-misere-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        record.save(path).unwrap();
+        let loaded = GameRecord::load(path);
+        fs::remove_file(path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.start.variant, misere);
+        for board in loaded.boards() {
+            board.validate().unwrap();
+        }
+        assert_eq!(loaded.board().status().winner(), Some(Player::O));
+        assert!(loaded.board().status() != GameStatus::InProgress);
+
+        let shared = GameRecord::from_share_code(&record.share_code()).unwrap();
+        assert_eq!(record.share_code(), "misere~14253");
+        assert_eq!(shared.start.variant, misere);
+        assert_eq!(shared.board(), loaded.board());
+    }
+
+    /// The code of a game read from `code`, after saving and loading it as text
+    fn round_trip(code: &str) -> String {
+        let record = GameRecord::from_share_code(code).unwrap();
+        assert_eq!(record.share_code(), code);
+        GameRecord::parse(&record.to_string()).unwrap().share_code()
+    }
+
+    #[test]
+    fn share_codes_round_trip() {
+        assert_eq!(round_trip(""), "");
+        assert_eq!(round_trip("5s19"), "5s19");
+        assert_eq!(round_trip("x---o----x.37"), "x---o----x.37");
+        assert_eq!(round_trip("x41+53"), "x41+53");
+
+        let record = GameRecord::from_share_code("5s19").unwrap();
+        let moves: Vec<String> = record.moves.iter().map(|m| m.action.to_string()).collect();
+        assert_eq!(moves, ["2b", "1a", "3c"]);
+        assert!(record.swapped);
+    }
+
+    #[test]
+    fn rejects_invalid_share_codes() {
+        rules::register_builtins();
+        let error = |code: &str| GameRecord::from_share_code(code).unwrap_err();
+
+        assert_eq!(error("nope~5"), "Unknown variant in the code: nope");
+        assert_eq!(error("x--.5"), "Invalid position in the code");
+        assert_eq!(error("y1+5"), "Invalid handicap stones in the code");
+        assert_eq!(error("x11+5"), "Stone 1a on an occupied cell");
+        assert_eq!(error("s5"), "Invalid character in the code: s");
+        assert_eq!(error("5ss"), "Invalid character in the code: s");
+        assert_eq!(error("55"), "Illegal move 2b");
+        // X has completed the top row
+        assert_eq!(error("142537"), "Illegal move 3a");
+        assert_eq!(error("5a"), "Invalid character in the code: a");
+    }
+
+    #[test]
+    fn parses_comments_and_times() {
+        let record = GameRecord::parse("# A game\n2b 1.50s ; Takes the center\n\n1a\n").unwrap();
+
+        assert_eq!(record.moves.len(), 2);
+        assert_eq!(record.moves[0].comment.as_ref().unwrap(), "Takes the center");
+        assert_eq!(record.moves[0].time.unwrap().as_millis(), 1500);
+        assert!(record.moves[1].comment.is_none() && record.moves[1].time.is_none());
+        assert_eq!(GameRecord::parse(&record.to_string()).unwrap().to_string(), record.to_string());
+    }
+
+    #[test]
+    fn rejects_invalid_records() {
+        rules::register_builtins();
+        let error = |text: &str| GameRecord::parse(text).unwrap_err();
+
+        assert_eq!(error("result x\n1a"), "Line 2: Moves after the result");
+        assert_eq!(error("result maybe"), "Line 1: Invalid result");
+        assert_eq!(error("zz"), "Line 1: Invalid move zz");
+        assert_eq!(error("1a\n1a"), "Line 2: Illegal move 1a (move 2)");
+        // X has completed the top row
+        assert_eq!(
+            error("1a\n2a\n1b\n2b\n1c\n3c"),
+            "Line 6: Illegal move 3c (move 6)"
+        );
+        assert_eq!(error("1a 3"), "Line 1: Invalid time 3");
+        assert_eq!(error("1a -1s"), "Line 1: Invalid time -1s");
+        assert_eq!(
+            error("1a\nresult o"),
+            "Result does not match the moves: recorded Player O wins, but the game is unfinished"
+        );
+        assert_eq!(
+            error("1a\n2a\n1b\n2b\n1c\nresult draw"),
+            "Result does not match the moves: recorded a draw, but Player X wins"
+        );
+        assert_eq!(
+            error("1a\nposition .../.../... x"),
+            "Line 2: Position after the first move"
+        );
+        assert_eq!(error("swap"), "Line 1: Swap other than right after the first move");
+        assert_eq!(
+            error("1a\n2b\nswap"),
+            "Line 3: Swap other than right after the first move"
+        );
+        assert_eq!(error("variant nope"), "Line 1: Unknown variant nope");
+        assert_eq!(error("1a\nvariant misere"), "Line 2: Variant after the first move");
+        assert_eq!(error("stones z 1a"), "Line 1: Expected the player the stones are for");
+        assert_eq!(error("stones x 1a 1a"), "Line 1: Stone 1a on an occupied cell");
+    }
+}
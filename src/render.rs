@@ -0,0 +1,259 @@
+use std::sync::{Arc, Mutex};
+use game::{Board, GameStatus};
+use heatmap::Heatmap;
+use mcts::Outlook;
+use scoreboard::Scoreboard;
+use theme::{self, Theme};
+use CLEAR_SCREEN;
+
+/// Width of the evaluation bar, without its brackets
+const EVALUATION_WIDTH: usize = 20;
+
+/// How a frontend presents a game to its players
+pub trait Renderer {
+    fn draw_board(&mut self, board: &Board);
+
+    /// Ask the player to move for their move
+    fn show_prompt(&mut self, board: &Board);
+
+    /// Tell who won the finished game on `board`
+    fn announce_result(&mut self, board: &Board);
+
+    /// Anything else worth knowing, e.g. about the AI's search
+    fn show_stats(&mut self, stats: &str);
+
+    /// Like `draw_board`, with how good every move is on the empty cells
+    fn draw_heatmap(&mut self, board: &Board, heatmap: &Heatmap) {
+        self.draw_board(board);
+        self.show_stats(&format!("Moves: {}", heatmap.describe()));
+    }
+
+    /// The engine's outlook for the position, for Player X
+    fn show_evaluation(&mut self, outlook: &Outlook) {
+        self.show_stats(&format!("Evaluation: {}", evaluation_bar(outlook)));
+    }
+}
+
+/// An outlook for Player X as a bar filled with X's chances from the left
+/// and O's from the right, the draws in between, e.g.
+/// `X 30% [xxxxxx----------oooo] 20% O`
+pub fn evaluation_bar(outlook: &Outlook) -> String {
+    let x = (outlook.win * EVALUATION_WIDTH as f64).round() as usize;
+    let o = ((outlook.loss * EVALUATION_WIDTH as f64).round() as usize).min(EVALUATION_WIDTH - x);
+
+    format!(
+        "X {:.0}% [{}{}{}] {:.0}% O",
+        percent(outlook.win),
+        "x".repeat(x),
+        "-".repeat(EVALUATION_WIDTH - x - o),
+        "o".repeat(o),
+        percent(outlook.loss)
+    )
+}
+
+/// A chance as a percentage, without the sign of a -0
+fn percent(chance: f64) -> f64 {
+    (chance * 100.).abs()
+}
+
+/// The board as text: a header naming the columns, then one line per row
+/// starting with its number
+pub fn board_lines(board: &Board, theme: &Theme) -> Vec<String> {
+    let mut lines = vec!["  a b c".to_string()];
+
+    for (i, row) in board.fields.iter().enumerate() {
+        let mut line = format!("{} ", i + 1);
+
+        for &cell in row {
+            line.push_str(theme.cell(cell));
+            line.push(' ');
+        }
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// The renderer for a terminal: plain scrolling output, redrawing the whole
+/// screen instead if `clear_screen` is set, or a layout for screen readers.
+/// The full screen shows `scoreboard` beside the board.
+pub fn for_terminal(
+    clear_screen: bool,
+    theme: Theme,
+    accessible: bool,
+    scoreboard: Option<Arc<Mutex<Scoreboard>>>,
+) -> Box<dyn Renderer> {
+    if accessible {
+        Box::new(Accessible)
+    } else if clear_screen {
+        Box::new(Tui {
+            cli: Cli { theme },
+            messages: Vec::new(),
+            scoreboard,
+            evaluation: None,
+        })
+    } else {
+        Box::new(Cli { theme })
+    }
+}
+
+/// Scrolling terminal output
+pub struct Cli {
+    pub theme: Theme,
+}
+
+impl Cli {
+    pub fn plain() -> Cli {
+        Cli { theme: theme::PLAIN }
+    }
+}
+
+impl Renderer for Cli {
+    fn draw_board(&mut self, board: &Board) {
+        for line in board_lines(board, &self.theme) {
+            println!("{}", line);
+        }
+    }
+
+    fn draw_heatmap(&mut self, board: &Board, heatmap: &Heatmap) {
+        // Colored themes have already checked for a terminal that can show it
+        for line in heatmap.lines(board, &self.theme, self.theme != theme::PLAIN) {
+            println!("{}", line);
+        }
+        println!("{}", heatmap.legend(board));
+    }
+
+    fn show_prompt(&mut self, board: &Board) {
+        println!("Turn: {}", board.next_player());
+    }
+
+    fn announce_result(&mut self, board: &Board) {
+        match board.status().winner() {
+            Some(player) => println!("Winner is Player {}", player),
+            None => println!("Game ended with a draw"),
+        }
+    }
+
+    fn show_stats(&mut self, stats: &str) {
+        println!("{}", stats);
+    }
+}
+
+/// Full-screen terminal output, clearing the screen for every board
+pub struct Tui {
+    cli: Cli,
+    /// Everything shown since the last board, repeated below the next one
+    messages: Vec<String>,
+    scoreboard: Option<Arc<Mutex<Scoreboard>>>,
+    /// The latest evaluation bar, shown under every board until the game ends
+    evaluation: Option<String>,
+}
+
+impl Tui {
+    /// Clear the screen and draw the `lines` of a board with everything
+    /// around it
+    fn draw(&mut self, mut lines: Vec<String>) {
+        print!("{}", CLEAR_SCREEN);
+
+        if let Some(ref scoreboard) = self.scoreboard {
+            // The rows are 8 columns wide, not counting color escapes
+            lines[0] = format!("{:<8}", lines[0]);
+            let panel = scoreboard.lock().unwrap().lines();
+
+            for (line, panel) in lines.iter_mut().zip(panel) {
+                line.push_str("    ");
+                line.push_str(&panel);
+            }
+        }
+        for line in lines {
+            println!("{}", line);
+        }
+        if let Some(ref evaluation) = self.evaluation {
+            println!("{}", evaluation);
+        }
+
+        for message in self.messages.drain(..) {
+            self.cli.show_stats(&message);
+        }
+    }
+}
+
+impl Renderer for Tui {
+    fn draw_board(&mut self, board: &Board) {
+        let lines = board_lines(board, &self.cli.theme);
+        self.draw(lines);
+    }
+
+    fn draw_heatmap(&mut self, board: &Board, heatmap: &Heatmap) {
+        let mut lines = heatmap.lines(board, &self.cli.theme, true);
+        lines.push(heatmap.legend(board));
+        self.draw(lines);
+    }
+
+    fn show_prompt(&mut self, board: &Board) {
+        self.cli.show_prompt(board);
+    }
+
+    fn announce_result(&mut self, board: &Board) {
+        self.cli.announce_result(board);
+        self.evaluation = None;
+    }
+
+    fn show_stats(&mut self, stats: &str) {
+        self.cli.show_stats(stats);
+        self.messages.push(stats.to_string());
+    }
+
+    fn show_evaluation(&mut self, outlook: &Outlook) {
+        self.evaluation = Some(evaluation_bar(outlook));
+    }
+}
+
+/// Output for screen readers: every cell is spelled out and nothing relies
+/// on the layout of a grid
+pub struct Accessible;
+
+impl Renderer for Accessible {
+    fn draw_board(&mut self, board: &Board) {
+        for (i, row) in board.fields.iter().enumerate() {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&['a', 'b', 'c'])
+                .map(|(cell, col)| match *cell {
+                    Some(player) => format!("{}{} {}", i + 1, col, player),
+                    None => format!("{}{} empty", i + 1, col),
+                })
+                .collect();
+
+            println!("Row {}: {}.", i + 1, cells.join(", "));
+        }
+    }
+
+    fn show_prompt(&mut self, board: &Board) {
+        println!("Player {} to move.", board.next_player());
+    }
+
+    fn announce_result(&mut self, board: &Board) {
+        match board.status() {
+            GameStatus::Won(player, line) => println!(
+                "Game over. Player {} wins with {}, {} and {}.",
+                player, line[0], line[1], line[2]
+            ),
+            _ => println!("Game over. It's a draw."),
+        }
+    }
+
+    fn show_stats(&mut self, stats: &str) {
+        println!("{}", stats);
+    }
+
+    fn show_evaluation(&mut self, outlook: &Outlook) {
+        println!(
+            "Evaluation: X wins {:.0}%, draw {:.0}%, O wins {:.0}%.",
+            percent(outlook.win),
+            percent(outlook.draw),
+            percent(outlook.loss)
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::io::{self, Write};
+use std::process;
+use cli;
+use commentary;
+use game::{Coord, GameStatus, Player};
+use openings;
+use record::GameRecord;
+use render::{Cli, Renderer};
+
+/// Show a saved game move by move along with its annotations
+pub fn run(args: &[String]) {
+    let mut file: Option<&String> = None;
+    let mut step = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "--step" => step = true,
+            _ if file.is_none() && !arg.starts_with("--") => file = Some(arg),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    let file = file.unwrap_or_else(|| cli::fail("Missing game file"));
+    let record = GameRecord::load(file).unwrap_or_else(|e| cli::fail(&e));
+    let boards = record.boards();
+
+    boards[0].print();
+
+    let actions: Vec<Coord> = record.moves.iter().map(|m| m.action).collect();
+    for (i, (m, board)) in record.moves.iter().zip(&boards[1..]).enumerate() {
+        if step {
+            print!("[Enter for the next move] ");
+            io::stdout().flush().unwrap();
+
+            if io::stdin().read_line(&mut String::new()).unwrap() == 0 {
+                step = false;
+            }
+        }
+
+        println!();
+        let time = m.time.map_or(String::new(), |time| format!(" ({:.1}s)", time.as_secs_f64()));
+        println!(
+            "{}. {} plays {}{}",
+            i + 1,
+            boards[i].next_player(),
+            m.action,
+            time
+        );
+        println!("   {}", commentary::describe(&boards[i], m.action));
+
+        // Name the opening once the move leads into a longer line
+        let opening = openings::name(&boards[0], &actions[..=i]);
+        if opening.is_some() && opening != openings::name(&boards[0], &actions[..i]) {
+            println!("   Opening: {}", opening.unwrap_or_default());
+        }
+        if let Some(ref comment) = m.comment {
+            println!("   {}", comment);
+        }
+        if i == 0 && record.swapped {
+            println!("   {} swaps, taking over the move", boards[0].next_player().opponent());
+        }
+        board.print();
+    }
+
+    let board = record.board();
+    println!();
+    if board.is_ended() {
+        Cli::plain().announce_result(&board);
+    } else {
+        println!("Game is not finished, Player {} to move", board.next_player());
+    }
+    print_thinking_times(&record);
+}
+
+/// How long both players thought over the game, if the moves were timed
+pub fn print_thinking_times(record: &GameRecord) {
+    for &player in &[Player::X, Player::O] {
+        if let Some(time) = record.thinking_time(player) {
+            println!("Player {} thought {}", player, time);
+        }
+    }
+}
+
+/// Re-play a saved game against the rules, reporting the first illegal move
+/// or a result not matching the moves
+pub fn verify(args: &[String]) {
+    let file = match args {
+        [file] if !file.starts_with("--") => file,
+        _ => cli::fail("Expected a single game file"),
+    };
+
+    let record = match GameRecord::load(file) {
+        Ok(record) => record,
+        Err(e) => {
+            println!("Invalid: {}", e);
+            process::exit(1);
+        }
+    };
+
+    // Handicap stones put one player ahead of the usual turns
+    let boards = if record.stones.is_none() { record.boards() } else { Vec::new() };
+    for (i, board) in boards.iter().enumerate() {
+        if let Err(e) = board.validate() {
+            println!("Invalid: position after move {}: {}", i, e);
+            process::exit(1);
+        }
+    }
+
+    let board = record.board();
+    let result = match board.status() {
+        GameStatus::InProgress => format!("unfinished, Player {} to move", board.next_player()),
+        GameStatus::Won(player, _) => format!("Player {} wins", player),
+        GameStatus::Draw => "draw".to_string(),
+    };
+    println!("Valid: {} moves, {}", record.moves.len(), result);
+}
@@ -0,0 +1,303 @@
+use std::fmt::Write;
+use std::fs;
+use std::time::Duration;
+use analyze::value_name;
+use cli;
+use commentary;
+use game::{Board, Coord, GameStatus, Player};
+use minimax;
+use openings;
+use record::GameRecord;
+use render;
+use svg;
+use theme;
+
+/// A move of the game along with the solver's verdict on it
+struct Evaluated {
+    player: Player,
+    action: Coord,
+    comment: Option<String>,
+    time: Option<Duration>,
+    /// Value for the player before and after moving
+    before: i32,
+    after: i32,
+    /// The moves that would have kept the value
+    best: Vec<Coord>,
+    /// The positions before and after the move
+    from: Board,
+    board: Board,
+}
+
+impl Evaluated {
+    /// Whether the move gave away a win or a draw
+    fn is_blunder(&self) -> bool {
+        self.after < self.before
+    }
+}
+
+fn evaluate(record: &GameRecord) -> Vec<Evaluated> {
+    let mut solver = minimax::shared();
+    let boards = record.boards();
+
+    record
+        .moves
+        .iter()
+        .zip(boards.windows(2))
+        .map(|(m, boards)| Evaluated {
+            player: boards[0].next_player(),
+            action: m.action,
+            comment: m.comment.clone(),
+            time: m.time,
+            before: solver.value(&boards[0]),
+            after: -solver.value(&boards[1]),
+            best: solver.best_actions(&boards[0]),
+            from: boards[0],
+            board: boards[1],
+        })
+        .collect()
+}
+
+fn result(board: &Board) -> String {
+    match board.status() {
+        GameStatus::Won(player, _) => format!("Player {} wins", player),
+        GameStatus::Draw => "Draw".to_string(),
+        GameStatus::InProgress => format!("Unfinished, Player {} to move", board.next_player()),
+    }
+}
+
+fn format_time(time: Option<Duration>) -> String {
+    time.map_or(String::new(), |time| format!("{:.1}s", time.as_secs_f64()))
+}
+
+/// How long each side thought, for the players whose moves were timed
+fn thinking_times(record: &GameRecord) -> Vec<String> {
+    [Player::X, Player::O]
+        .iter()
+        .filter_map(|&player| {
+            record.thinking_time(player).map(|time| format!("{}: {}", player, time))
+        })
+        .collect()
+}
+
+/// The handicap stones the game started with, e.g. `X on 2b 1a`
+fn stones(record: &GameRecord) -> Option<String> {
+    record
+        .stones
+        .as_ref()
+        .map(|&(player, ref stones)| format!("{} on {}", player, format_actions(stones)))
+}
+
+fn format_actions(actions: &[Coord]) -> String {
+    actions.iter().cloned().map(|action| action.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+/// One line per player, e.g. `X: 1 blunder (3. 1a)`
+fn blunder_summary(moves: &[Evaluated]) -> Vec<String> {
+    [Player::X, Player::O]
+        .iter()
+        .map(|&player| {
+            let blunders: Vec<String> = moves
+                .iter()
+                .enumerate()
+                .filter(|&(_, m)| m.player == player && m.is_blunder())
+                .map(|(i, m)| format!("{}. {}", i + 1, m.action))
+                .collect();
+
+            match blunders.len() {
+                0 => format!("{}: no blunders", player),
+                1 => format!("{}: 1 blunder ({})", player, blunders[0]),
+                n => format!("{}: {} blunders ({})", player, n, blunders.join(", ")),
+            }
+        })
+        .collect()
+}
+
+/// The moves worth a diagram: every blunder and the final move
+fn key_moments(moves: &[Evaluated]) -> Vec<usize> {
+    (0..moves.len()).filter(|&i| moves[i].is_blunder() || i + 1 == moves.len()).collect()
+}
+
+fn markdown(record: &GameRecord, moves: &[Evaluated]) -> String {
+    let mut out = String::new();
+    let board = record.board();
+
+    writeln!(out, "# Game report\n").unwrap();
+    writeln!(out, "- Start: `{}`", record.start.fen()).unwrap();
+    if let Some(stones) = stones(record) {
+        writeln!(out, "- Handicap: {}", stones).unwrap();
+    }
+    writeln!(out, "- Result: {}", result(&board)).unwrap();
+    if let Some(opening) = openings::of_record(record) {
+        writeln!(out, "- Opening: {}", opening).unwrap();
+    }
+    for time in thinking_times(record) {
+        writeln!(out, "- Thinking time of {}", time).unwrap();
+    }
+    writeln!(out, "\n## Moves\n").unwrap();
+    writeln!(out, "| # | Player | Move | Time | Before | After | Best moves | Comment |").unwrap();
+    writeln!(out, "|---|--------|------|------|--------|-------|------------|---------|").unwrap();
+
+    for (i, m) in moves.iter().enumerate() {
+        writeln!(
+            out,
+            "| {} | {} | {}{} | {} | {} | {} | {} | {} |",
+            i + 1,
+            m.player,
+            m.action,
+            if m.is_blunder() { " ??" } else { "" },
+            format_time(m.time),
+            value_name(m.before),
+            value_name(m.after),
+            format_actions(&m.best),
+            m.comment.as_deref().unwrap_or("").replace('|', "\\|")
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "\n## Key moments").unwrap();
+    for i in key_moments(moves) {
+        let m = &moves[i];
+        writeln!(
+            out,
+            "\n### {}. {} plays {}\n",
+            i + 1,
+            m.player,
+            m.action
+        )
+        .unwrap();
+        writeln!(out, "```").unwrap();
+        for line in render::board_lines(&m.board, &theme::PLAIN) {
+            writeln!(out, "{}", line.trim_end()).unwrap();
+        }
+        writeln!(out, "```\n").unwrap();
+        writeln!(out, "{}", commentary::describe(&m.from, m.action)).unwrap();
+    }
+
+    writeln!(out, "\n## Blunders\n").unwrap();
+    for line in blunder_summary(moves) {
+        writeln!(out, "- {}", line).unwrap();
+    }
+
+    out
+}
+
+/// Escape text for HTML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn html(record: &GameRecord, moves: &[Evaluated]) -> String {
+    let mut out = String::new();
+    let board = record.board();
+
+    out.push_str(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Game report</title>\n\
+         <style>\n\
+         body { font-family: sans-serif; max-width: 50em; margin: 2em auto; }\n\
+         table { border-collapse: collapse; }\n\
+         th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; }\n\
+         .blunder { background: #fdd; }\n\
+         </style>\n</head>\n<body>\n",
+    );
+    writeln!(out, "<h1>Game report</h1>").unwrap();
+    writeln!(out, "<p>Start: <code>{}</code><br>", escape(&record.start.fen())).unwrap();
+    if let Some(stones) = stones(record) {
+        writeln!(out, "Handicap: {}<br>", stones).unwrap();
+    }
+    write!(out, "Result: {}", result(&board)).unwrap();
+    if let Some(opening) = openings::of_record(record) {
+        write!(out, "<br>\nOpening: {}", escape(opening)).unwrap();
+    }
+    for time in thinking_times(record) {
+        write!(out, "<br>\nThinking time of {}", time).unwrap();
+    }
+    writeln!(out, "</p>").unwrap();
+
+    writeln!(out, "<h2>Moves</h2>\n<table>").unwrap();
+    writeln!(
+        out,
+        "<tr><th>#</th><th>Player</th><th>Move</th><th>Time</th><th>Before</th>\
+         <th>After</th><th>Best moves</th><th>Comment</th></tr>"
+    )
+    .unwrap();
+    for (i, m) in moves.iter().enumerate() {
+        writeln!(
+            out,
+            "<tr{}><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+             <td>{}</td><td>{}</td></tr>",
+            if m.is_blunder() { " class=\"blunder\"" } else { "" },
+            i + 1,
+            m.player,
+            m.action,
+            format_time(m.time),
+            value_name(m.before),
+            value_name(m.after),
+            format_actions(&m.best),
+            escape(m.comment.as_deref().unwrap_or(""))
+        )
+        .unwrap();
+    }
+    writeln!(out, "</table>").unwrap();
+
+    writeln!(out, "<h2>Key moments</h2>").unwrap();
+    for i in key_moments(moves) {
+        let m = &moves[i];
+        writeln!(
+            out,
+            "<h3>{}. {} plays {}</h3>",
+            i + 1,
+            m.player,
+            m.action
+        )
+        .unwrap();
+        out.push_str(&svg::board(&m.board, &svg::LIGHT, 60, Some(m.action)));
+        writeln!(out, "<p>{}</p>", escape(&commentary::describe(&m.from, m.action))).unwrap();
+    }
+
+    writeln!(out, "<h2>Blunders</h2>\n<ul>").unwrap();
+    for line in blunder_summary(moves) {
+        writeln!(out, "<li>{}</li>", line).unwrap();
+    }
+    out.push_str("</ul>\n</body>\n</html>\n");
+
+    out
+}
+
+/// Write a report on a saved game, evaluating every move with the solver
+pub fn run(args: &[String]) {
+    let mut file: Option<&String> = None;
+    let mut format: Option<String> = None;
+    let mut out: Option<String> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => format = Some(cli::value(arg, args.next())),
+            "--out" => out = Some(cli::value(arg, args.next())),
+            _ if file.is_none() && !arg.starts_with("--") => file = Some(arg),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    let file = file.unwrap_or_else(|| cli::fail("Missing game file"));
+    let record = GameRecord::load(file).unwrap_or_else(|e| cli::fail(&e));
+    let moves = evaluate(&record);
+
+    // Without --format, go by the extension of the output file
+    let html_output = match format.as_deref() {
+        Some("html") => true,
+        Some("markdown") | Some("md") => false,
+        Some(other) => cli::fail(&format!("Unknown format {}, expected html or markdown", other)),
+        None => out.as_deref().is_some_and(|o| o.ends_with(".html") || o.ends_with(".htm")),
+    };
+    let report = if html_output { html(&record, &moves) } else { markdown(&record, &moves) };
+
+    match out {
+        Some(path) => {
+            fs::write(&path, report)
+                .unwrap_or_else(|e| cli::fail(&format!("Could not write {}: {}", path, e)));
+            println!("Wrote {}", path);
+        }
+        None => print!("{}", report),
+    }
+}
@@ -0,0 +1,221 @@
+//! Per-game results of tournaments, tuning runs and training, written with
+//! `--output <file>` as CSV (`.csv`) or JSON (`.json`, an array of objects
+//! with the columns as keys) for analysis elsewhere.
+//!
+//! Every file has one row per game. `winner` is `x`, `o` or `draw`,
+//! `moves` counts the moves played, `seconds` the time the game took and
+//! `iterations` the search budget per move.
+//!
+//! ```text
+//! tournament  round, game, iterations, x, o, x_engine, o_engine, winner, moves,
+//!             seconds
+//! tune        config, baseline, game, iterations, config_player, winner, moves,
+//!             seconds
+//! train       game, config, winner, moves, seconds
+//! ```
+//!
+//! Engines are MCTS configurations as the subcommands print them, or the
+//! command of an external engine.
+//!
+//! CSV files can be read back with `read`, e.g. to plot them.
+
+use std::collections::HashMap;
+use std::fs;
+use std::mem;
+use std::time::Duration;
+use game::Player;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Text(String),
+    Number(f64),
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Value {
+        Value::Text(text.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Value {
+        Value::Text(text)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(number: usize) -> Value {
+        Value::Number(number as f64)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(number: u32) -> Value {
+        Value::Number(f64::from(number))
+    }
+}
+
+impl From<Duration> for Value {
+    fn from(duration: Duration) -> Value {
+        Value::Number((duration.as_secs_f64() * 1000.).round() / 1000.)
+    }
+}
+
+/// A player as `x` or `o`
+pub fn player(player: Player) -> Value {
+    Value::Text(player.to_string().to_lowercase())
+}
+
+/// The `winner` column: `x`, `o` or `draw`
+pub fn winner(winner: Option<Player>) -> Value {
+    winner.map_or(Value::from("draw"), player)
+}
+
+/// Rows of results under named columns
+#[derive(Debug)]
+pub struct Results {
+    columns: &'static [&'static str],
+    rows: Vec<Vec<Value>>,
+}
+
+impl Results {
+    pub fn new(columns: &'static [&'static str]) -> Results {
+        Results {
+            columns,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<Value>) {
+        assert_eq!(row.len(), self.columns.len(), "Row doesn't match the columns");
+        self.rows.push(row);
+    }
+
+    fn csv(&self) -> String {
+        let field = |value: &Value| match *value {
+            Value::Text(ref text) if text.contains([',', '"', '\n']) => {
+                format!("\"{}\"", text.replace('"', "\"\""))
+            }
+            Value::Text(ref text) => text.clone(),
+            Value::Number(number) => number.to_string(),
+        };
+
+        let mut out = self.columns.join(",") + "\n";
+        for row in &self.rows {
+            out.push_str(&row.iter().map(field).collect::<Vec<_>>().join(","));
+            out.push('\n');
+        }
+        out
+    }
+
+    fn json(&self) -> String {
+        let field = |value: &Value| match *value {
+            Value::Text(ref text) => format!("\"{}\"", escape_json(text)),
+            Value::Number(number) => number.to_string(),
+        };
+
+        let rows: Vec<String> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let fields: Vec<String> = self
+                    .columns
+                    .iter()
+                    .zip(row)
+                    .map(|(column, value)| format!("\"{}\":{}", column, field(value)))
+                    .collect();
+                format!("  {{{}}}", fields.join(","))
+            })
+            .collect();
+
+        format!("[\n{}\n]\n", rows.join(",\n"))
+    }
+
+    /// Write the results to `path`, in the format of its extension
+    pub fn write(&self, path: &str) -> Result<(), String> {
+        check_path(path)?;
+        let text = if path.ends_with(".csv") { self.csv() } else { self.json() };
+
+        fs::write(path, text).map_err(|e| format!("Could not write {}: {}", path, e))
+    }
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Check that `--output` names a format before playing for hours
+pub fn check_path(path: &str) -> Result<(), String> {
+    if path.ends_with(".csv") || path.ends_with(".json") {
+        Ok(())
+    } else {
+        Err(format!("Unknown output format for {}, expected .csv or .json", path))
+    }
+}
+
+/// A row read back from a results file, by column
+pub type Row = HashMap<String, String>;
+
+/// Read the rows of a CSV results file
+pub fn read(path: &str) -> Result<Vec<Row>, String> {
+    if !path.ends_with(".csv") {
+        return Err(format!("Can only read results from .csv files, not {}", path));
+    }
+
+    let text = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let mut records = parse_csv(&text).into_iter();
+    let columns = records.next().ok_or_else(|| format!("{} is empty", path))?;
+
+    records
+        .map(|fields| {
+            if fields.len() == columns.len() {
+                Ok(columns.iter().cloned().zip(fields).collect())
+            } else {
+                Err(format!("{}: a row doesn't match the columns", path))
+            }
+        })
+        .collect()
+}
+
+/// The records of CSV text, with quoted fields as written by `Results`
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => record.push(mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                record.push(mem::take(&mut field));
+                records.push(mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+
+    // The last line may lack its newline
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
@@ -0,0 +1,149 @@
+use std::sync::{OnceLock, RwLock};
+use game::{self, Coord, Line, Player};
+
+/// A change to the standard rules. Variants are built by combining rules,
+/// each one adjusting the outcome of the ones before it.
+pub trait Rule: Send + Sync {
+    /// The lines completing the game, given the ones of the previous rules
+    fn lines(&self, lines: Vec<Line>) -> Vec<Line> {
+        lines
+    }
+
+    /// Who wins a game that `winner` wins by the previous rules
+    fn winner(&self, winner: Player) -> Player {
+        winner
+    }
+}
+
+/// Completing a line loses the game
+pub struct Misere;
+
+impl Rule for Misere {
+    fn winner(&self, winner: Player) -> Player {
+        winner.opponent()
+    }
+}
+
+/// The board wraps around at the edges like a torus, adding the diagonals
+/// broken by an edge as lines
+pub struct WrapAround;
+
+impl Rule for WrapAround {
+    fn lines(&self, mut lines: Vec<Line>) -> Vec<Line> {
+        let cell = |row, col| Coord::new(row, col).unwrap();
+
+        for shift in 1..3 {
+            let anti = (2 + shift) % 3;
+            lines.push([cell(0, shift), cell(1, (shift + 1) % 3), cell(2, (shift + 2) % 3)]);
+            lines.push([cell(0, anti), cell(1, (anti + 2) % 3), cell(2, (anti + 1) % 3)]);
+        }
+
+        lines
+    }
+}
+
+/// A registered combination of rules
+struct Definition {
+    name: &'static str,
+    description: &'static str,
+    rules: Vec<Box<dyn Rule>>,
+    /// The lines after applying all rules
+    lines: Vec<Line>,
+}
+
+/// The variants that can be played, `Variant` indexing into it. Definitions
+/// live as long as the program, so boards can refer to them freely.
+static REGISTRY: OnceLock<RwLock<Vec<&'static Definition>>> = OnceLock::new();
+
+fn registry() -> &'static RwLock<Vec<&'static Definition>> {
+    REGISTRY.get_or_init(|| {
+        RwLock::new(vec![define("standard", "Three in a row wins", Vec::new())])
+    })
+}
+
+/// Register the variants coming with the crate
+pub fn register_builtins() {
+    Variant::register("misere", "Three in a row loses", vec![Box::new(Misere)]);
+    Variant::register("wrap", "Lines wrap around the edges", vec![Box::new(WrapAround)]);
+    Variant::register(
+        "misere-wrap",
+        "Lines wrap around the edges, completing one loses",
+        vec![Box::new(WrapAround), Box::new(Misere)],
+    );
+}
+
+fn define(
+    name: &'static str,
+    description: &'static str,
+    rules: Vec<Box<dyn Rule>>,
+) -> &'static Definition {
+    let standard = game::standard_lines();
+    let lines = rules.iter().fold(standard, |lines, rule| rule.lines(lines));
+
+    Box::leak(Box::new(Definition {
+        name,
+        description,
+        rules,
+        lines,
+    }))
+}
+
+/// The rules a board is played by
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Variant(usize);
+
+impl Variant {
+    pub const STANDARD: Variant = Variant(0);
+
+    /// Make a combination of rules playable under `name`, replacing any
+    /// variant of the same name
+    pub fn register(
+        name: &'static str,
+        description: &'static str,
+        rules: Vec<Box<dyn Rule>>,
+    ) -> Variant {
+        let definition = define(name, description, rules);
+        let mut registry = registry().write().unwrap();
+
+        match registry.iter().position(|d| d.name == name) {
+            Some(index) => {
+                registry[index] = definition;
+                Variant(index)
+            }
+            None => {
+                registry.push(definition);
+                Variant(registry.len() - 1)
+            }
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Variant> {
+        registry().read().unwrap().iter().position(|d| d.name == name).map(Variant)
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        registry().read().unwrap().iter().map(|d| d.name).collect()
+    }
+
+    fn definition(&self) -> &'static Definition {
+        registry().read().unwrap()[self.0]
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.definition().name
+    }
+
+    pub fn description(&self) -> &'static str {
+        self.definition().description
+    }
+
+    /// The lines completing the game
+    pub fn lines(&self) -> &'static [Line] {
+        &self.definition().lines
+    }
+
+    /// Who wins after `player` completed a line
+    pub fn winner(&self, player: Player) -> Player {
+        self.definition().rules.iter().fold(player, |winner, rule| rule.winner(winner))
+    }
+}
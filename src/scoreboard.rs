@@ -0,0 +1,89 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use controller::Ending;
+use events::{EventBus, GameEvent};
+use game::Player;
+
+/// The results and pace of a session's games, kept up to date from their
+/// events
+#[derive(Debug, Default)]
+pub struct Scoreboard {
+    /// Games won by X and O
+    wins: [u32; 2],
+    draws: u32,
+    /// The last result, `None` for a draw, and how many times in a row
+    streak: Option<(Option<Player>, u32)>,
+    /// Time X and O took for their moves, and how many they made
+    thinking: [Duration; 2],
+    moves: [u32; 2],
+    /// When the player to move was asked for their move
+    turn_started: Option<Instant>,
+}
+
+fn side(player: Player) -> usize {
+    match player {
+        Player::X => 0,
+        Player::O => 1,
+    }
+}
+
+impl Scoreboard {
+    /// A scoreboard following the games published on `events`
+    pub fn follow(events: &EventBus) -> Arc<Mutex<Scoreboard>> {
+        let scoreboard = Arc::new(Mutex::new(Scoreboard::default()));
+        let shared = scoreboard.clone();
+        events.subscribe(move |event| shared.lock().unwrap().update(event));
+
+        scoreboard
+    }
+
+    fn update(&mut self, event: &GameEvent) {
+        match *event {
+            GameEvent::TurnStarted { .. } => self.turn_started = Some(Instant::now()),
+            GameEvent::MoveMade { player, .. } => {
+                if let Some(start) = self.turn_started.take() {
+                    self.thinking[side(player)] += start.elapsed();
+                    self.moves[side(player)] += 1;
+                }
+            }
+            GameEvent::GameEnded { ending, .. } if ending != Ending::Quit => {
+                self.record(ending.winner())
+            }
+            _ => {}
+        }
+    }
+
+    fn record(&mut self, winner: Option<Player>) {
+        match winner {
+            Some(player) => self.wins[side(player)] += 1,
+            None => self.draws += 1,
+        }
+
+        self.streak = match self.streak {
+            Some((last, games)) if last == winner => Some((last, games + 1)),
+            _ => Some((winner, 1)),
+        };
+    }
+
+    /// The panel shown beside the board
+    pub fn lines(&self) -> Vec<String> {
+        let average = |side: usize| match self.moves[side] {
+            0 => "-".to_string(),
+            moves => format!("{:.1}s", (self.thinking[side] / moves).as_secs_f64()),
+        };
+        let streak = match self.streak {
+            None => "-".to_string(),
+            Some((None, 1)) => "last game drawn".to_string(),
+            Some((None, games)) => format!("{} draws in a row", games),
+            Some((Some(player), 1)) => format!("{} won the last game", player),
+            Some((Some(player), games)) => format!("{} won the last {}", player, games),
+        };
+
+        vec![
+            "Session".to_string(),
+            format!("X {} - {} O, {} drawn", self.wins[0], self.wins[1], self.draws),
+            format!("Streak: {}", streak),
+            format!("Avg move: X {}, O {}", average(0), average(1)),
+        ]
+    }
+}
@@ -0,0 +1,126 @@
+use std::ops::Add;
+use std::time::{Duration, Instant};
+use game::{Board, Player};
+use mcts::{Config, Limit, StopToken, MCTS};
+
+/// Results of a match from the first engine's point of view
+#[derive(Debug, Default, Copy, Clone)]
+pub struct MatchResult {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MatchResult {
+    /// Score between 0 and 1 (win = 1, draw = 0.5, loss = 0)
+    pub fn score(&self) -> f64 {
+        let games = self.wins + self.draws + self.losses;
+
+        (self.wins as f64 + self.draws as f64 / 2.) / games as f64
+    }
+
+    /// Points scored, a win counting 1 and a draw half
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.
+    }
+
+    /// Count a game the first engine played as `player`
+    pub fn record(&mut self, player: Player, winner: Option<Player>) {
+        match winner {
+            Some(winner) if winner == player => self.wins += 1,
+            Some(_) => self.losses += 1,
+            None => self.draws += 1,
+        }
+    }
+
+    /// The same match from the second engine's point of view
+    pub fn reversed(&self) -> MatchResult {
+        MatchResult {
+            wins: self.losses,
+            draws: self.draws,
+            losses: self.wins,
+        }
+    }
+}
+
+impl Add for MatchResult {
+    type Output = MatchResult;
+
+    /// Both matches as one
+    fn add(self, other: MatchResult) -> MatchResult {
+        MatchResult {
+            wins: self.wins + other.wins,
+            draws: self.draws + other.draws,
+            losses: self.losses + other.losses,
+        }
+    }
+}
+
+/// How a game went
+#[derive(Debug, Copy, Clone)]
+pub struct Played {
+    /// `None` on a draw
+    pub winner: Option<Player>,
+    pub moves: usize,
+    pub duration: Duration,
+}
+
+/// Play a game between two engines, each searching `iterations` times per move
+pub fn play_game(x: &Config, o: &Config, iterations: u32) -> Played {
+    let start = Instant::now();
+    let mut board = Board::new(Player::X);
+    let mut moves = 0;
+
+    while !board.is_ended() {
+        let player = board.next_player();
+        let config = if player == Player::X { x } else { o };
+
+        let mut engine = MCTS::from_board(player, board, *config);
+        let result = engine.search(Limit::Iterations(u64::from(iterations)), &StopToken::new());
+
+        board.perform_action(result.action.expect("Engine found no action"));
+        moves += 1;
+    }
+
+    Played {
+        winner: board.status().winner(),
+        moves,
+        duration: start.elapsed(),
+    }
+}
+
+/// The side the first engine of a match plays in its game `game`, as they
+/// take turns starting
+pub fn first_engine_player(game: u32) -> Player {
+    if game.is_multiple_of(2) {
+        Player::X
+    } else {
+        Player::O
+    }
+}
+
+/// Play a match of `games` games, alternating which engine starts. Returns
+/// the result along with every game.
+pub fn play_match(
+    a: &Config,
+    b: &Config,
+    games: u32,
+    iterations: u32,
+) -> (MatchResult, Vec<Played>) {
+    let mut result = MatchResult::default();
+    let mut played = Vec::new();
+
+    for game in 0..games {
+        let a_player = first_engine_player(game);
+        let game = if a_player == Player::X {
+            play_game(a, b, iterations)
+        } else {
+            play_game(b, a, iterations)
+        };
+
+        result.record(a_player, game.winner);
+        played.push(game);
+    }
+
+    (result, played)
+}
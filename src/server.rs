@@ -0,0 +1,2518 @@
+use std::collections::HashMap;
+use std::{fmt, fs, io};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+use rand::Rng;
+use rand::os::OsRng;
+use cli;
+use game::{Board, Coord, GameStatus, Player};
+use mcts::{Config, Limit, SearchResult, StopToken, MCTS};
+use metrics::{self, Exposition, Histogram};
+use selfplay::MatchResult;
+use storage::{self, Directory, FinishedGame, GameId, RunningGame, Storage};
+
+const DEFAULT_PORT: u16 = 7878;
+const DEFAULT_ADMIN_PORT: u16 = 7879;
+
+/// How long games wait for a disconnected player to come back
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+/// Connections a single address may hold open
+const DEFAULT_CONNECTIONS_PER_IP: usize = 8;
+/// Commands a client may send per second on average, and in a burst
+const DEFAULT_COMMAND_RATE: f64 = 5.;
+const COMMAND_BURST: f64 = 20.;
+/// How long a connection may stay silent before it's closed
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+/// Lines waiting to be sent to a client before it counts as not reading
+/// them and is disconnected
+const OUTBOX_SIZE: usize = 64;
+/// How long a player may think before forfeiting the game
+const DEFAULT_MOVE_TIMEOUT: Duration = Duration::from_secs(120);
+/// How long the server's engine searches for a move
+const ENGINE_MOVETIME: Duration = Duration::from_millis(500);
+/// How often the engine looks for games waiting on its move
+const ENGINE_POLL: Duration = Duration::from_millis(50);
+
+/// Finished games sent by HISTORY
+const HISTORY_LENGTH: usize = 20;
+
+/// Rating of players new to the ladder
+const LADDER_START: f64 = 1500.;
+/// How far a ladder game moves the ratings at most
+const LADDER_K: f64 = 32.;
+
+/// Version of the line protocol, bumped on incompatible changes
+const PROTOCOL_VERSION: u32 = 2;
+/// Game variants played on this server
+const VARIANTS: [&str; 1] = ["standard"];
+/// Optional protocol features a client can ask for in its handshake
+const CAPABILITIES: [&str; 3] = ["chat", "spectate", "diff"];
+
+const HELP: &str = "HELP Commands:
+HELP   HELLO <version> [variant=<name>] [capabilities=<a,b>] [session=<token>] [key=<key>]
+HELP                     Handshake, required before anything else. Pass the
+HELP                     token from SESSION to resume after losing the connection,
+HELP                     and a bot's API key to play under its name and limits.
+HELP   NAME <name>       Change your name
+HELP   LIST              Show open and running games
+HELP   HISTORY [<name>]  Show the last finished games, or those of a player
+HELP   CREATE <name>     Open a new game and wait for an opponent
+HELP   JOIN <id>         Join an open game
+HELP   WATCH <id>        Follow a game as a spectator
+HELP   PLAY [ai]         Get paired with the next player looking for a game,
+HELP                     or play the server's engine
+HELP   LADDER [leave]    Play rated games against whoever else is on the ladder,
+HELP                     paired again after every game until you leave
+HELP   STANDINGS         Show the ladder's ratings
+HELP   MOVE <action> [<number>]
+HELP                     Play a move, e.g. MOVE 2b. Given the move's number
+HELP                     in the game, e.g. MOVE 2b 3, a retried move isn't played twice.
+HELP   TAKEBACK          Ask your opponent to undo your last move
+HELP   TAKEBACK accept|decline
+HELP                     Answer your opponent's takeback request
+HELP   SAY <text>        Send a chat message to your opponent
+HELP   LEAVE             Leave the current game (or stop watching)
+HELP   QUIT              Disconnect";
+
+/// Protocol violations after which a client is disconnected
+const MAX_VIOLATIONS: u32 = 10;
+
+type ClientId = u32;
+type RoomId = GameId;
+
+/// Errors are sent as `ERROR <code> <message>`, the code being stable for
+/// clients to act on
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ErrorCode {
+    HandshakeRequired,
+    InvalidHandshake,
+    UnsupportedVersion,
+    UnsupportedVariant,
+    UnsupportedCapability,
+    UnknownSession,
+    SessionInUse,
+    UnknownKey,
+    UnknownCommand,
+    InvalidArgument,
+    CapabilityRequired,
+    NameTaken,
+    NoSuchGame,
+    GameFull,
+    AlreadyInGame,
+    /// The client's API key has as many games going as its tier allows
+    GameQuota,
+    NotInGame,
+    Spectator,
+    NoOpponent,
+    GameOver,
+    OutOfTurn,
+    IllegalMove,
+    InvalidMove,
+    /// A retried move that was played the first time
+    AlreadyPlayed,
+    /// A move numbered other than the next one
+    OutOfSequence,
+    NothingToTakeBack,
+    NoTakebackRequest,
+    TooManyViolations,
+    TooManyConnections,
+    RateLimited,
+    NoHistory,
+    Draining,
+}
+
+impl ErrorCode {
+    fn code(&self) -> &'static str {
+        match *self {
+            ErrorCode::HandshakeRequired => "handshake-required",
+            ErrorCode::InvalidHandshake => "invalid-handshake",
+            ErrorCode::UnsupportedVersion => "unsupported-version",
+            ErrorCode::UnsupportedVariant => "unsupported-variant",
+            ErrorCode::UnsupportedCapability => "unsupported-capability",
+            ErrorCode::UnknownSession => "unknown-session",
+            ErrorCode::SessionInUse => "session-in-use",
+            ErrorCode::UnknownKey => "unknown-key",
+            ErrorCode::UnknownCommand => "unknown-command",
+            ErrorCode::InvalidArgument => "invalid-argument",
+            ErrorCode::CapabilityRequired => "capability-required",
+            ErrorCode::NameTaken => "name-taken",
+            ErrorCode::NoSuchGame => "no-such-game",
+            ErrorCode::GameFull => "game-full",
+            ErrorCode::AlreadyInGame => "already-in-game",
+            ErrorCode::GameQuota => "game-quota",
+            ErrorCode::NotInGame => "not-in-game",
+            ErrorCode::Spectator => "spectator",
+            ErrorCode::NoOpponent => "no-opponent",
+            ErrorCode::GameOver => "game-over",
+            ErrorCode::OutOfTurn => "out-of-turn",
+            ErrorCode::IllegalMove => "illegal-move",
+            ErrorCode::InvalidMove => "invalid-move",
+            ErrorCode::AlreadyPlayed => "already-played",
+            ErrorCode::OutOfSequence => "out-of-sequence",
+            ErrorCode::NothingToTakeBack => "nothing-to-take-back",
+            ErrorCode::NoTakebackRequest => "no-takeback-request",
+            ErrorCode::TooManyViolations => "too-many-violations",
+            ErrorCode::TooManyConnections => "too-many-connections",
+            ErrorCode::RateLimited => "rate-limited",
+            ErrorCode::NoHistory => "no-history",
+            ErrorCode::Draining => "draining",
+        }
+    }
+
+    /// Errors a well-behaved client doesn't run into (as opposed to e.g.
+    /// joining a game that just filled up)
+    fn is_violation(&self) -> bool {
+        matches!(
+            *self,
+            ErrorCode::HandshakeRequired
+                | ErrorCode::InvalidHandshake
+                | ErrorCode::UnknownCommand
+                | ErrorCode::InvalidArgument
+                | ErrorCode::Spectator
+                | ErrorCode::GameOver
+                | ErrorCode::OutOfTurn
+                | ErrorCode::IllegalMove
+                | ErrorCode::InvalidMove
+                | ErrorCode::RateLimited
+        )
+    }
+}
+
+struct Client {
+    name: String,
+    /// `None` while the client is disconnected but may still resume
+    stream: Option<Connection>,
+    disconnected: Option<Instant>,
+    /// Secret needed to resume the session
+    token: String,
+    room: Option<RoomId>,
+    /// Whether the client completed the handshake
+    greeted: bool,
+    capabilities: Vec<String>,
+    /// The API key the client authenticated with, if any
+    key: Option<String>,
+    /// Whether the client is on the ladder, paired automatically
+    ladder: bool,
+    /// The server's engine, playing a game started with PLAY ai
+    engine: bool,
+    violations: u32,
+    /// Commands the client may still send right away, refilling over time
+    allowance: f64,
+    last_command: Instant,
+}
+
+/// A client's socket, written to from a thread of its own. The lobby only
+/// queues lines while it's locked, so a client that doesn't read them can't
+/// hold up everyone else.
+struct Connection {
+    stream: TcpStream,
+    outbox: SyncSender<String>,
+}
+
+impl Connection {
+    fn open(stream: TcpStream) -> io::Result<Connection> {
+        let mut writer = stream.try_clone()?;
+        let (outbox, lines) = sync_channel::<String>(OUTBOX_SIZE);
+
+        // Once the connection is dropped, the lines still queued are sent
+        // and the socket is closed, which also ends the client's reader
+        thread::spawn(move || {
+            for line in lines {
+                if writeln!(writer, "{}", line).is_err() {
+                    break;
+                }
+            }
+            let _ = writer.shutdown(Shutdown::Both);
+        });
+
+        Ok(Connection { stream, outbox })
+    }
+
+    /// Queue a line, `false` if the client fell too far behind reading
+    fn send(&self, line: &str) -> bool {
+        self.outbox.try_send(line.to_string()).is_ok()
+    }
+}
+
+struct Room {
+    name: String,
+    /// Players X and O
+    players: [Option<ClientId>; 2],
+    /// Clients following the game without playing
+    spectators: Vec<ClientId>,
+    board: Board,
+    /// The board as last sent to the room, for sending only what changed
+    sent: Board,
+    /// Moves played so far, to roll the board back on a takeback
+    moves: Vec<Coord>,
+    /// The player asking to undo their last move, waiting for an answer
+    takeback: Option<Player>,
+    /// When the player to move got their turn
+    turn_started: Instant,
+    /// A game of the ladder, rated once it's over
+    ladder: bool,
+}
+
+impl Room {
+    fn player_of(&self, client: ClientId) -> Option<Player> {
+        match self.players {
+            [Some(x), _] if x == client => Some(Player::X),
+            [_, Some(o)] if o == client => Some(Player::O),
+            _ => None,
+        }
+    }
+
+    fn players(&self) -> Vec<ClientId> {
+        self.players.iter().filter_map(|&p| p).collect()
+    }
+
+    /// Players and spectators
+    fn clients(&self) -> Vec<ClientId> {
+        let mut clients = self.players();
+        clients.extend(&self.spectators);
+        clients
+    }
+}
+
+/// Limits keeping a public server usable for everyone
+struct Limits {
+    connections_per_ip: usize,
+    /// Commands per second, 0 for no limit
+    command_rate: f64,
+    /// Zero durations disable the timeouts
+    idle_timeout: Duration,
+    move_timeout: Duration,
+}
+
+impl Limits {
+    /// Change a limit given as `<key>=<value>`, e.g. `move-timeout=60s`
+    fn set(&mut self, setting: &str) -> Result<(), String> {
+        let invalid = || format!("Invalid limit {}", setting);
+        let (key, value) = setting.split_once('=').ok_or_else(invalid)?;
+
+        match key {
+            "connections" => self.connections_per_ip = value.parse().map_err(|_| invalid())?,
+            "rate" => self.command_rate = value.parse().map_err(|_| invalid())?,
+            "idle-timeout" => self.idle_timeout = cli::parse_duration(value).ok_or_else(invalid)?,
+            "move-timeout" => self.move_timeout = cli::parse_duration(value).ok_or_else(invalid)?,
+            _ => return Err(format!("Unknown limit {}", key)),
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Limits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "LIMITS connections={} rate={} idle-timeout={}s move-timeout={}s",
+            self.connections_per_ip,
+            self.command_rate,
+            self.idle_timeout.as_secs_f64(),
+            self.move_timeout.as_secs_f64()
+        )
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            connections_per_ip: DEFAULT_CONNECTIONS_PER_IP,
+            command_rate: DEFAULT_COMMAND_RATE,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            move_timeout: DEFAULT_MOVE_TIMEOUT,
+        }
+    }
+}
+
+/// Limits for the clients of an API key
+#[derive(Debug)]
+struct Tier {
+    name: &'static str,
+    /// Commands per second, 0 for no limit
+    command_rate: f64,
+    /// Games the key's clients may play or wait in at a time, 0 for no limit
+    games: usize,
+}
+
+const TIERS: [Tier; 3] = [
+    Tier {
+        name: "basic",
+        command_rate: DEFAULT_COMMAND_RATE,
+        games: 1,
+    },
+    Tier {
+        name: "bot",
+        command_rate: 20.,
+        games: 4,
+    },
+    Tier {
+        name: "unlimited",
+        command_rate: 0.,
+        games: 0,
+    },
+];
+
+/// Tier of keys registered without naming one
+const DEFAULT_TIER: &str = "bot";
+
+/// A registered bot, playing under its name within its tier's limits
+#[derive(Debug)]
+struct ApiKey {
+    name: String,
+    tier: &'static Tier,
+}
+
+fn tier(name: &str) -> Result<&'static Tier, String> {
+    TIERS.iter().find(|tier| tier.name == name).ok_or_else(|| {
+        let names: Vec<&str> = TIERS.iter().map(|tier| tier.name).collect();
+        format!("Unknown tier {}, available: {}", name, names.join(", "))
+    })
+}
+
+/// Read the keys file, one `<key> <name> <tier>` per line. A missing file
+/// has no keys yet.
+fn load_keys(path: &str) -> Result<HashMap<String, ApiKey>, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(format!("Could not read {}: {}", path, e)),
+    };
+
+    text.lines()
+        .enumerate()
+        .filter(|&(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| match *line.split_whitespace().collect::<Vec<_>>() {
+            [key, name, tier_name] => Ok((
+                key.to_string(),
+                ApiKey {
+                    name: name.to_string(),
+                    tier: tier(tier_name).map_err(|e| format!("{}:{}: {}", path, i + 1, e))?,
+                },
+            )),
+            _ => Err(format!("{}:{}: expected <key> <name> <tier>", path, i + 1)),
+        })
+        .collect()
+}
+
+/// A player's standing on the ladder
+#[derive(Debug, Copy, Clone)]
+struct Rating {
+    elo: f64,
+    results: MatchResult,
+}
+
+impl Default for Rating {
+    fn default() -> Rating {
+        Rating {
+            elo: LADDER_START,
+            results: MatchResult::default(),
+        }
+    }
+}
+
+/// Totals since the server started, for the metrics endpoint
+struct Stats {
+    connections: u64,
+    rejected_connections: u64,
+    games_started: u64,
+    /// Finished games won by X, won by O and drawn
+    results: [u64; 3],
+    timeouts: u64,
+    moves: u64,
+    /// Time players took for their moves
+    move_time: Histogram,
+    /// Time the engine searched for its moves
+    search_time: Histogram,
+    /// Iterations of all the engine's searches
+    search_iterations: u64,
+    /// Iterations per second of the engine's last search
+    search_rate: usize,
+    errors: HashMap<&'static str, u64>,
+}
+
+impl Default for Stats {
+    fn default() -> Stats {
+        Stats {
+            connections: 0,
+            rejected_connections: 0,
+            games_started: 0,
+            results: [0; 3],
+            timeouts: 0,
+            moves: 0,
+            move_time: Histogram::new(&metrics::DURATION_BUCKETS),
+            search_time: Histogram::new(&metrics::DURATION_BUCKETS),
+            search_iterations: 0,
+            search_rate: 0,
+            errors: HashMap::new(),
+        }
+    }
+}
+
+impl Stats {
+    fn count_result(&mut self, winner: Option<Player>) {
+        match winner {
+            Some(Player::X) => self.results[0] += 1,
+            Some(Player::O) => self.results[1] += 1,
+            None => self.results[2] += 1,
+        }
+    }
+}
+
+/// Everyone connected to the server and the games they play
+#[derive(Default)]
+struct Lobby {
+    clients: HashMap<ClientId, Client>,
+    rooms: HashMap<RoomId, Room>,
+    /// The last client id handed out
+    next_id: u32,
+    /// Client waiting to be paired automatically
+    waiting: Option<ClientId>,
+    /// Clients by their session token
+    sessions: HashMap<String, ClientId>,
+    grace_period: Duration,
+    limits: Limits,
+    /// Open connections by address
+    connections: HashMap<IpAddr, usize>,
+    stats: Stats,
+    /// Where games are kept across restarts, if anywhere
+    storage: Option<Box<dyn Storage>>,
+    /// No new games are started, the server exits once the running ones end
+    draining: bool,
+    /// Registered bots by their API key
+    keys: HashMap<String, ApiKey>,
+    /// Where newly registered keys are added, if anywhere
+    keys_file: Option<String>,
+    /// Ratings of everyone who played on the ladder, by `rated_name`
+    ratings: HashMap<String, Rating>,
+}
+
+impl Lobby {
+    fn next_id(&mut self) -> ClientId {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Send a line to a client. A client with too many lines it hasn't read
+    /// yet loses its connection, which its reader then handles like any
+    /// other lost connection.
+    fn send(&mut self, client: ClientId, message: &str) {
+        if let Some(&mut Client { ref mut stream, .. }) = self.clients.get_mut(&client) {
+            if stream.as_ref().is_some_and(|connection| !connection.send(message)) {
+                if let Some(connection) = stream.take() {
+                    let _ = connection.stream.shutdown(Shutdown::Both);
+                }
+            }
+        }
+    }
+
+    /// Reject a client's request, counting protocol violations
+    fn error(&mut self, client: ClientId, code: ErrorCode, message: &str) {
+        self.send(client, &format!("ERROR {} {}", code.code(), message));
+        *self.stats.errors.entry(code.code()).or_insert(0) += 1;
+
+        if code.is_violation() {
+            if let Some(c) = self.clients.get_mut(&client) {
+                c.violations += 1;
+            }
+        }
+    }
+
+    fn send_room(&mut self, room: RoomId, message: &str) {
+        for client in self.rooms[&room].clients() {
+            self.send(client, message);
+        }
+    }
+
+    fn connect(&mut self, stream: Connection) -> ClientId {
+        let id = self.next_id();
+        let name = format!("guest{}", id);
+
+        let mut rng = OsRng::new().expect("No random source");
+        let token = format!("{:016x}{:016x}", rng.gen::<u64>(), rng.gen::<u64>());
+
+        self.sessions.insert(token.clone(), id);
+        self.clients.insert(
+            id,
+            Client {
+                name,
+                stream: Some(stream),
+                disconnected: None,
+                token,
+                room: None,
+                greeted: false,
+                capabilities: Vec::new(),
+                key: None,
+                ladder: false,
+                engine: false,
+                violations: 0,
+                allowance: COMMAND_BURST,
+                last_command: Instant::now(),
+            },
+        );
+        self.send(
+            id,
+            &format!(
+                "PROTOCOL tictactoe {} variants={} capabilities={}",
+                PROTOCOL_VERSION,
+                VARIANTS.join(","),
+                CAPABILITIES.join(",")
+            ),
+        );
+
+        id
+    }
+
+    /// Check the client's handshake. Returns the client to continue with
+    /// (which differs when resuming a session), `None` if it's incompatible.
+    fn hello(&mut self, client: ClientId, argument: &str) -> Option<ClientId> {
+        let mut parts = argument.split_whitespace();
+        let mut variant = VARIANTS[0];
+        let mut capabilities = Vec::new();
+        let mut session = None;
+        let mut key = None;
+
+        match parts.next().map(|v| v.parse::<u32>()) {
+            Some(Ok(PROTOCOL_VERSION)) => {}
+            Some(Ok(version)) => {
+                self.error(
+                    client,
+                    ErrorCode::UnsupportedVersion,
+                    &format!(
+                        "Protocol version {} is not supported, this server speaks version {}",
+                        version, PROTOCOL_VERSION
+                    ),
+                );
+                return None;
+            }
+            _ => {
+                self.error(client, ErrorCode::InvalidHandshake, "Expected HELLO <version>");
+                return None;
+            }
+        }
+
+        for part in parts {
+            match part.split_once('=') {
+                Some(("variant", v)) => variant = v,
+                Some(("capabilities", c)) => capabilities = c.split(',').map(|c| c.to_string()).collect(),
+                Some(("session", token)) => session = Some(token),
+                Some(("key", k)) => key = Some(k.to_string()),
+                _ => {
+                    self.error(client, ErrorCode::InvalidHandshake, &format!("Invalid handshake field {}", part));
+                    return None;
+                }
+            }
+        }
+
+        if !VARIANTS.contains(&variant) {
+            self.error(
+                client,
+                ErrorCode::UnsupportedVariant,
+                &format!("Variant {} is not supported, available: {}", variant, VARIANTS.join(",")),
+            );
+            return None;
+        }
+
+        if let Some(unknown) = capabilities.iter().find(|c| !CAPABILITIES.contains(&c.as_str())) {
+            self.error(
+                client,
+                ErrorCode::UnsupportedCapability,
+                &format!("Capability {} is not supported", unknown),
+            );
+            return None;
+        }
+
+        let bot = match key {
+            Some(ref key) => match self.keys.get(key) {
+                Some(api_key) => Some(api_key.name.clone()),
+                None => {
+                    self.error(client, ErrorCode::UnknownKey, "Unknown API key");
+                    return None;
+                }
+            },
+            None => None,
+        };
+
+        let client = match session {
+            Some(token) => match self.sessions.get(token).cloned() {
+                Some(old) if self.clients[&old].disconnected.is_some() => {
+                    // Continue the old session on the new connection
+                    let stream = self.remove(client).and_then(|c| c.stream);
+                    let c = self.clients.get_mut(&old).unwrap();
+                    c.stream = stream;
+                    c.disconnected = None;
+                    old
+                }
+                Some(_) => {
+                    self.error(client, ErrorCode::SessionInUse, "Session is still connected");
+                    return None;
+                }
+                None => {
+                    self.error(client, ErrorCode::UnknownSession, "Unknown or expired session");
+                    return None;
+                }
+            },
+            None => client,
+        };
+
+        // A bot's connections play under its name, numbered from the second
+        // one on so that names stay unique. A resumed one keeps its name.
+        let name = match bot {
+            Some(ref bot) if self.clients[&client].key != key => {
+                let numbered = (2..).map(|n| format!("{}-{}", bot, n));
+                let mut names = Some(bot.clone()).into_iter().chain(numbered);
+                names.find(|name| self.clients.values().all(|c| c.name != *name))
+            }
+            _ => None,
+        };
+
+        let c = self.clients.get_mut(&client).unwrap();
+        let resumed = c.greeted;
+        c.greeted = true;
+        c.capabilities = capabilities;
+        if let Some(name) = name {
+            c.name = name;
+        }
+        if bot.is_some() {
+            c.key = key;
+        }
+
+        let (name, token, room) = (c.name.clone(), c.token.clone(), c.room);
+        self.send(client, &format!("WELCOME {}", name));
+        self.send(client, &format!("SESSION {}", token));
+        self.send(client, "INFO Type HELP for a list of commands");
+
+        if resumed {
+            if let Some(room) = room {
+                let player = self.rooms[&room].player_of(client).expect("Resumed a spectator");
+
+                self.send(client, &format!("RESUMED {} {}", room, player));
+                for other in self.rooms[&room].clients() {
+                    if other != client {
+                        self.send(other, &format!("RETURNED {}", name));
+                    }
+                }
+                self.send_state(room);
+            } else {
+                self.send(client, "LOBBY");
+            }
+        }
+
+        Some(client)
+    }
+
+    /// The client lost its connection. Players of running games may resume
+    /// within the grace period, everyone else is removed right away.
+    fn disconnect(&mut self, client: ClientId) {
+        if !self.clients.contains_key(&client) {
+            return;
+        }
+
+        let playing = self.clients[&client].room.is_some_and(|room| {
+            let room = &self.rooms[&room];
+            room.player_of(client).is_some() && room.players[1].is_some() && !room.board.is_ended()
+        });
+
+        if !playing || self.grace_period == Duration::from_secs(0) {
+            self.remove(client);
+            return;
+        }
+
+        let c = self.clients.get_mut(&client).unwrap();
+        c.stream = None;
+        c.disconnected = Some(Instant::now());
+
+        let (name, room) = (c.name.clone(), c.room.unwrap());
+        let message = format!("PAUSED {} {}", name, self.grace_period.as_secs());
+        for other in self.rooms[&room].clients() {
+            self.send(other, &message);
+        }
+    }
+
+    fn remove(&mut self, client: ClientId) -> Option<Client> {
+        self.leave(client);
+
+        let removed = self.clients.remove(&client);
+        if let Some(ref c) = removed {
+            self.sessions.remove(&c.token);
+        }
+        removed
+    }
+
+    /// Give up on clients that didn't come back within the grace period
+    fn expire_sessions(&mut self) {
+        let grace_period = self.grace_period;
+        let expired: Vec<ClientId> = self
+            .clients
+            .iter()
+            .filter(|&(_, c)| c.disconnected.is_some_and(|t| t.elapsed() >= grace_period))
+            .map(|(&id, _)| id)
+            .collect();
+
+        for client in expired {
+            self.remove(client);
+        }
+    }
+
+    /// The tier of the client's API key, `None` without one
+    fn tier(&self, client: ClientId) -> Option<&'static Tier> {
+        let key = self.clients[&client].key.as_ref()?;
+        self.keys.get(key).map(|api_key| api_key.tier)
+    }
+
+    fn has_capability(&self, client: ClientId, capability: &str) -> bool {
+        self.clients[&client].capabilities.iter().any(|c| c == capability)
+    }
+
+    /// Handle a line sent by a client. Returns the client to continue with,
+    /// `None` if the client quits.
+    fn handle(&mut self, client: ClientId, line: &str) -> Option<ClientId> {
+        let mut parts = line.trim().splitn(2, ' ');
+        let command = parts.next().unwrap_or("").to_uppercase();
+        let argument = parts.next().unwrap_or("").trim();
+
+        // Kicked by an admin while the line was on its way
+        if !self.clients.contains_key(&client) {
+            return None;
+        }
+
+        // Commands before the handshake count against the same budget as
+        // the ones after it
+        if !self.take_allowance(client) {
+            self.error(client, ErrorCode::RateLimited, "Too many commands, slow down");
+            return self.check_violations(client);
+        }
+
+        if !self.clients[&client].greeted {
+            return match command.as_str() {
+                "HELLO" => self.hello(client, argument),
+                "QUIT" => None,
+                _ => {
+                    self.error(
+                        client,
+                        ErrorCode::HandshakeRequired,
+                        "Handshake required, send HELLO <version> first",
+                    );
+                    self.check_violations(client)
+                }
+            };
+        }
+
+        match command.as_str() {
+            "" => {}
+            "HELLO" => self.error(client, ErrorCode::InvalidHandshake, "Handshake already done"),
+            "HELP" => self.send(client, HELP),
+            "NAME" => self.rename(client, argument),
+            "LIST" => self.list(client),
+            "HISTORY" => self.history(client, argument),
+            "CREATE" => self.create(client, argument),
+            "JOIN" => match argument.parse() {
+                Ok(room) => self.join(client, room),
+                Err(_) => self.error(client, ErrorCode::InvalidArgument, "Expected a game id"),
+            },
+            "PLAY" => match argument.to_lowercase().as_str() {
+                "" => self.pair(client),
+                "ai" => self.play_engine(client),
+                _ => self.error(client, ErrorCode::InvalidArgument, "Expected PLAY or PLAY ai"),
+            },
+            "LADDER" => match argument.to_lowercase().as_str() {
+                "" => self.join_ladder(client),
+                "leave" => {
+                    self.clients.get_mut(&client).unwrap().ladder = false;
+                    self.send(client, "LOBBY");
+                }
+                _ => self.error(client, ErrorCode::InvalidArgument, "Expected leave or nothing"),
+            },
+            "STANDINGS" => self.standings(client),
+            "WATCH" if !self.has_capability(client, "spectate") => {
+                self.error(
+                    client,
+                    ErrorCode::CapabilityRequired,
+                    "WATCH requires the spectate capability",
+                )
+            }
+            "WATCH" => match argument.parse() {
+                Ok(room) => self.watch(client, room),
+                Err(_) => self.error(client, ErrorCode::InvalidArgument, "Expected a game id"),
+            },
+            "MOVE" => self.perform_move(client, argument),
+            "TAKEBACK" => match argument.to_lowercase().as_str() {
+                "" => self.request_takeback(client),
+                "accept" => self.answer_takeback(client, true),
+                "decline" => self.answer_takeback(client, false),
+                _ => self.error(client, ErrorCode::InvalidArgument, "Expected accept or decline"),
+            },
+            "SAY" => self.say(client, argument),
+            "LEAVE" => {
+                // Leaving a game leaves the ladder too, or the next one would start
+                self.clients.get_mut(&client).unwrap().ladder = false;
+                if self.clients[&client].room.is_some() || self.waiting == Some(client) {
+                    self.leave(client);
+                    self.send(client, "LOBBY");
+                } else {
+                    self.error(client, ErrorCode::NotInGame, "You are not in a game");
+                }
+            }
+            "QUIT" => return None,
+            _ => self.error(
+                client,
+                ErrorCode::UnknownCommand,
+                &format!("Unknown command {}, try HELP", command),
+            ),
+        }
+
+        self.check_violations(client)
+    }
+
+    /// Disconnect clients that keep breaking the protocol
+    fn check_violations(&mut self, client: ClientId) -> Option<ClientId> {
+        if self.clients[&client].violations >= MAX_VIOLATIONS {
+            self.send(
+                client,
+                &format!(
+                    "ERROR {} Disconnected after {} protocol violations",
+                    ErrorCode::TooManyViolations.code(),
+                    MAX_VIOLATIONS
+                ),
+            );
+            return None;
+        }
+
+        Some(client)
+    }
+
+    /// Count a command against the client's rate limit, refilling the
+    /// allowance for the time since its last one
+    fn take_allowance(&mut self, client: ClientId) -> bool {
+        let rate = match self.tier(client) {
+            Some(tier) => tier.command_rate,
+            None => self.limits.command_rate,
+        };
+        let c = self.clients.get_mut(&client).unwrap();
+        if rate <= 0. {
+            return true;
+        }
+
+        let elapsed = c.last_command.elapsed().as_secs_f64();
+        c.last_command = Instant::now();
+        c.allowance = (c.allowance + elapsed * rate).min(COMMAND_BURST);
+
+        if c.allowance < 1. {
+            return false;
+        }
+
+        c.allowance -= 1.;
+        true
+    }
+
+    /// Take a new connection from `ip` into account, `false` if the address
+    /// has too many open already
+    fn open_connection(&mut self, ip: IpAddr) -> bool {
+        let count = self.connections.entry(ip).or_insert(0);
+        if *count >= self.limits.connections_per_ip {
+            self.stats.rejected_connections += 1;
+            return false;
+        }
+
+        *count += 1;
+        self.stats.connections += 1;
+        true
+    }
+
+    fn close_connection(&mut self, ip: IpAddr) {
+        if let Some(count) = self.connections.get_mut(&ip) {
+            *count -= 1;
+            if *count == 0 {
+                self.connections.remove(&ip);
+            }
+        }
+    }
+
+    /// End games whose player to move let their time run out, the opponent
+    /// winning. Disconnected players are covered by the grace period instead.
+    fn expire_turns(&mut self) {
+        let timeout = self.limits.move_timeout;
+        if timeout == Duration::from_secs(0) {
+            return;
+        }
+
+        let expired: Vec<(RoomId, ClientId)> = self
+            .rooms
+            .iter()
+            .filter(|&(_, r)| {
+                r.players[1].is_some()
+                    && !r.board.is_ended()
+                    && r.turn_started.elapsed() >= timeout
+            })
+            .filter_map(|(&id, r)| {
+                let index = if r.board.next_player() == Player::X { 0 } else { 1 };
+                r.players[index].map(|client| (id, client))
+            })
+            .filter(|&(_, client)| self.clients[&client].stream.is_some())
+            .collect();
+
+        for (room, client) in expired {
+            let player = self.rooms[&room].player_of(client).unwrap();
+            self.stats.timeouts += 1;
+            self.stats.count_result(Some(player.opponent()));
+            self.store_finished(room, Some(player.opponent()));
+            self.rate(room, Some(player.opponent()));
+            self.send_room(room, &format!("TIMEOUT {}", player));
+            self.send_room(room, &format!("RESULT {}", player.opponent()));
+
+            for other in self.rooms.remove(&room).unwrap().clients() {
+                self.clients.get_mut(&other).unwrap().room = None;
+                self.send(other, "LOBBY");
+            }
+        }
+    }
+
+    /// The server's state and totals in the Prometheus text format
+    fn metrics(&self) -> String {
+        let mut out = Exposition::default();
+        let stats = &self.stats;
+        let connected = self.clients.values().filter(|c| c.stream.is_some()).count();
+        let disconnected = self.clients.values().filter(|c| c.disconnected.is_some()).count();
+        let running = self.rooms.values().filter(|r| r.players[1].is_some()).count();
+
+        out.gauge("tictactoe_clients", "Connected clients", connected);
+        out.gauge(
+            "tictactoe_clients_disconnected",
+            "Players who lost their connection and may still resume",
+            disconnected,
+        );
+        out.gauge("tictactoe_games_active", "Games being played", running);
+        out.gauge(
+            "tictactoe_games_open",
+            "Games waiting for an opponent",
+            self.rooms.len() - running,
+        );
+        out.counter("tictactoe_connections_total", "Accepted connections", stats.connections);
+        out.counter(
+            "tictactoe_connections_rejected_total",
+            "Connections refused for exceeding the limit per address",
+            stats.rejected_connections,
+        );
+        out.counter("tictactoe_games_started_total", "Games started", stats.games_started);
+        out.labeled_counter(
+            "tictactoe_games_finished_total",
+            "Finished games by result",
+            "result",
+            &[("x", stats.results[0]), ("o", stats.results[1]), ("draw", stats.results[2])],
+        );
+        out.counter(
+            "tictactoe_move_timeouts_total",
+            "Games lost by running out of time",
+            stats.timeouts,
+        );
+        out.counter("tictactoe_moves_total", "Moves played", stats.moves);
+        out.histogram(
+            "tictactoe_move_seconds",
+            "Time players took for a move",
+            &stats.move_time,
+        );
+        out.histogram(
+            "tictactoe_search_seconds",
+            "Time the server's engine searched per move",
+            &stats.search_time,
+        );
+        out.counter(
+            "tictactoe_search_iterations_total",
+            "Iterations the server's engine searched",
+            stats.search_iterations,
+        );
+        out.gauge(
+            "tictactoe_search_iterations_per_second",
+            "Iterations per second of the engine's last search",
+            stats.search_rate,
+        );
+
+        let mut errors: Vec<(&str, u64)> = stats.errors.iter().map(|(&k, &v)| (k, v)).collect();
+        errors.sort();
+        out.labeled_counter("tictactoe_errors_total", "Errors sent by code", "code", &errors);
+
+        out.finish()
+    }
+
+    /// Run a change against the storage. Failing to store a game shouldn't
+    /// take down the server, the error is only logged.
+    fn store<F>(&mut self, change: F)
+    where
+        F: FnOnce(&mut dyn Storage) -> Result<(), String>,
+    {
+        if let Some(ref mut storage) = self.storage {
+            if let Err(e) = change(storage.as_mut()) {
+                eprintln!("Storage error: {}", e);
+            }
+        }
+    }
+
+    /// Keep a game once it came to a result, with `winner` being `None` for
+    /// a draw
+    fn store_finished(&mut self, room: RoomId, winner: Option<Player>) {
+        let r = &self.rooms[&room];
+        let game = FinishedGame {
+            id: room,
+            players: r.players.map(|p| p.map_or(String::new(), |p| self.clients[&p].name.clone())),
+            winner,
+            moves: r.moves.clone(),
+        };
+
+        self.store(|storage| {
+            storage.save_finished(&game)?;
+            storage.remove_running(room)
+        });
+    }
+
+    /// Keep a running game for a restart. Games against the engine are left
+    /// out, an engine doesn't come back to resume them.
+    fn store_running(&mut self, room: RoomId) {
+        let r = &self.rooms[&room];
+        if r.players.iter().flatten().any(|p| self.clients[p].engine) {
+            return;
+        }
+        let player = |i: usize| {
+            r.players[i].map_or((String::new(), String::new()), |p| {
+                (self.clients[&p].name.clone(), self.clients[&p].token.clone())
+            })
+        };
+        let key = |i: usize| r.players[i].and_then(|p| self.clients[&p].key.clone());
+        let game = RunningGame {
+            id: room,
+            name: r.name.clone(),
+            players: [player(0), player(1)],
+            keys: [key(0), key(1)],
+            ladder: r.ladder,
+            moves: r.moves.clone(),
+        };
+
+        self.store(|storage| storage.save_running(&game));
+    }
+
+    /// Bring back the games that were running when the server stopped. Their
+    /// players are treated like lost connections, resuming with their session
+    /// tokens within the grace period. Games with moves that aren't legal are
+    /// left out.
+    fn restore(&mut self) -> Result<usize, String> {
+        let running = match self.storage {
+            Some(ref storage) => storage.running()?,
+            None => return Ok(0),
+        };
+
+        let mut restored = 0;
+        for game in &running {
+            let mut board = Board::new(Player::X);
+            let illegal = game.moves.iter().position(|&action| {
+                let legal = !board.is_ended() && board.is_legal_action(action);
+                if legal {
+                    board.perform_action(action);
+                }
+                !legal
+            });
+            if let Some(i) = illegal {
+                eprintln!(
+                    "Not restoring game {}: move {} ({}) is illegal",
+                    game.id,
+                    i + 1,
+                    game.moves[i]
+                );
+                continue;
+            }
+
+            let mut players = [None, None];
+            for (i, (name, token)) in game.players.iter().enumerate() {
+                let id = self.next_id();
+                self.sessions.insert(token.clone(), id);
+                self.clients.insert(
+                    id,
+                    Client {
+                        name: name.clone(),
+                        stream: None,
+                        disconnected: Some(Instant::now()),
+                        token: token.clone(),
+                        room: Some(game.id),
+                        greeted: true,
+                        capabilities: Vec::new(),
+                        // A key removed from the keys file since no longer counts
+                        key: game.keys[i].clone().filter(|key| self.keys.contains_key(key)),
+                        ladder: false,
+                        engine: false,
+                        violations: 0,
+                        allowance: COMMAND_BURST,
+                        last_command: Instant::now(),
+                    },
+                );
+                players[i] = Some(id);
+            }
+
+            self.rooms.insert(
+                game.id,
+                Room {
+                    name: game.name.clone(),
+                    players,
+                    spectators: Vec::new(),
+                    board,
+                    sent: board,
+                    moves: game.moves.clone(),
+                    takeback: None,
+                    turn_started: Instant::now(),
+                    ladder: game.ladder,
+                },
+            );
+            restored += 1;
+        }
+
+        Ok(restored)
+    }
+
+    /// Send the last finished games, those of the player named `name` if
+    /// given
+    fn history(&mut self, client: ClientId, name: &str) {
+        let player = if name.is_empty() { None } else { Some(name) };
+        let games = match self.storage {
+            Some(ref storage) => storage.history(player, HISTORY_LENGTH),
+            None => {
+                return self.error(client, ErrorCode::NoHistory, "This server keeps no history")
+            }
+        };
+
+        match games {
+            Ok(games) => {
+                for game in &games {
+                    self.send(client, &format!("PLAYED {}", storage::summary(game)));
+                }
+                self.send(client, "END");
+            }
+            Err(e) => {
+                eprintln!("Storage error: {}", e);
+                self.error(client, ErrorCode::NoHistory, "History is unavailable");
+            }
+        }
+    }
+
+    /// Run a command of the admin channel, returning the lines to answer
+    fn admin(&mut self, line: &str) -> Result<Vec<String>, String> {
+        let mut parts = line.trim().splitn(2, ' ');
+        let command = parts.next().unwrap_or("").to_uppercase();
+        let argument = parts.next().unwrap_or("").trim();
+
+        match command.as_str() {
+            "GAMES" => {
+                let mut games: Vec<(RoomId, String)> = self
+                    .rooms
+                    .iter()
+                    .map(|(&id, room)| {
+                        let names: Vec<String> = room
+                            .players
+                            .iter()
+                            .map(|p| p.map_or("-".to_string(), |p| self.clients[&p].name.clone()))
+                            .collect();
+                        let line = format!(
+                            "GAME {} {} {} moves={} spectators={}",
+                            id,
+                            names.join(" "),
+                            room.board.fen().replace(' ', ":"),
+                            room.moves.len(),
+                            room.spectators.len()
+                        );
+                        (id, line)
+                    })
+                    .collect();
+                games.sort();
+                Ok(games.into_iter().map(|(_, line)| line).collect())
+            }
+            "CLIENTS" => {
+                let mut clients: Vec<(ClientId, String)> = self
+                    .clients
+                    .iter()
+                    .map(|(&id, c)| {
+                        let address = c
+                            .stream
+                            .as_ref()
+                            .and_then(|connection| connection.stream.peer_addr().ok())
+                            .map_or("disconnected".to_string(), |a| a.to_string());
+                        let address = if c.engine { "engine".to_string() } else { address };
+                        let room = c.room.map_or("-".to_string(), |r| r.to_string());
+                        (id, format!("CLIENT {} {} {} room={}", id, c.name, address, room))
+                    })
+                    .collect();
+                clients.sort();
+                Ok(clients.into_iter().map(|(_, line)| line).collect())
+            }
+            "KICK" => {
+                let client = self
+                    .clients
+                    .iter()
+                    .find(|&(&id, c)| c.name == argument || id.to_string() == argument)
+                    .map(|(&id, _)| id)
+                    .ok_or_else(|| format!("No client {}", argument))?;
+
+                // The connection closes once KICKED is sent
+                self.send(client, "KICKED");
+                self.remove(client);
+                Ok(vec![])
+            }
+            "ANNOUNCE" if argument.is_empty() => Err("Expected a message".to_string()),
+            "ANNOUNCE" => {
+                let clients: Vec<ClientId> = self.clients.keys().cloned().collect();
+                for client in clients {
+                    self.send(client, &format!("ANNOUNCE {}", argument));
+                }
+                Ok(vec![])
+            }
+            "LIMITS" => {
+                for setting in argument.split_whitespace() {
+                    self.limits.set(setting)?;
+                }
+                Ok(vec![self.limits.to_string()])
+            }
+            "REGISTER" => {
+                let mut words = argument.split_whitespace();
+                let name = words.next().ok_or("Expected a bot name")?;
+                let tier = tier(words.next().unwrap_or(DEFAULT_TIER))?;
+                if self.keys.values().any(|api_key| api_key.name == name) {
+                    return Err(format!("A bot named {} is registered already", name));
+                }
+
+                let mut rng = OsRng::new().map_err(|e| format!("No random source: {}", e))?;
+                let key = format!("{:016x}{:016x}", rng.gen::<u64>(), rng.gen::<u64>());
+
+                if let Some(ref path) = self.keys_file {
+                    fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(path)
+                        .and_then(|mut file| writeln!(file, "{} {} {}", key, name, tier.name))
+                        .map_err(|e| format!("Could not write {}: {}", path, e))?;
+                }
+
+                let line = format!("KEY {} {} {}", key, name, tier.name);
+                self.keys.insert(
+                    key,
+                    ApiKey {
+                        name: name.to_string(),
+                        tier,
+                    },
+                );
+                Ok(vec![line])
+            }
+            "DRAIN" => {
+                self.drain();
+                Ok(vec![format!("DRAINING {}", self.rooms.len())])
+            }
+            _ => Err(format!(
+                "Unknown command {}, expected GAMES, CLIENTS, KICK <name|id>, ANNOUNCE <text>, \
+                 LIMITS [<key>=<value>]..., REGISTER <name> [<tier>] or DRAIN",
+                command
+            )),
+        }
+    }
+
+    /// Stop starting games and close the ones still waiting for an opponent,
+    /// so the server can exit once the running ones are over
+    fn drain(&mut self) {
+        self.draining = true;
+        self.waiting = None;
+
+        let open: Vec<RoomId> =
+            self.rooms.iter().filter(|&(_, r)| r.players[1].is_none()).map(|(&id, _)| id).collect();
+        for room in open {
+            for client in self.rooms.remove(&room).unwrap().clients() {
+                self.clients.get_mut(&client).unwrap().room = None;
+                self.send(client, "LOBBY");
+            }
+        }
+
+        let clients: Vec<ClientId> = self.clients.keys().cloned().collect();
+        for client in clients {
+            self.send(client, "ANNOUNCE Server is shutting down once the running games are over");
+        }
+    }
+
+    /// The name a client is rated under: a bot's registered name, shared by
+    /// all its connections, otherwise the client's own
+    fn rated_name(&self, client: ClientId) -> String {
+        let c = &self.clients[&client];
+        let bot = c.key.as_ref().and_then(|key| self.keys.get(key));
+        bot.map_or(&c.name, |api_key| &api_key.name).clone()
+    }
+
+    /// Whether `name` belongs to a registered bot or a rated ladder player,
+    /// who keep it while they are away
+    fn reserved(&self, name: &str) -> bool {
+        self.keys.values().any(|api_key| api_key.name == name) || self.ratings.contains_key(name)
+    }
+
+    fn rename(&mut self, client: ClientId, name: &str) {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return self.error(client, ErrorCode::InvalidArgument, "Names must be a single word");
+        }
+
+        if self.clients[&client].key.is_some() {
+            let message = "Bots play under their registered name";
+            return self.error(client, ErrorCode::InvalidArgument, message);
+        }
+
+        if self.clients.values().any(|c| c.name == name) || self.reserved(name) {
+            return self.error(client, ErrorCode::NameTaken, "Name is taken");
+        }
+
+        self.clients.get_mut(&client).unwrap().name = name.to_string();
+        self.send(client, &format!("NAME {}", name));
+    }
+
+    fn list(&mut self, client: ClientId) {
+        let mut lines: Vec<String> = self
+            .rooms
+            .iter()
+            .filter_map(|(id, room)| match room.players {
+                [Some(host), None] => Some(format!("GAME {} {} {}", id, self.clients[&host].name, room.name)),
+                [Some(x), Some(o)] => Some(format!(
+                    "LIVE {} {} {} {}",
+                    id, self.clients[&x].name, self.clients[&o].name, room.name
+                )),
+                _ => None,
+            })
+            .collect();
+        lines.sort();
+        lines.push("END".to_string());
+
+        for line in lines {
+            self.send(client, &line);
+        }
+    }
+
+    /// Whether new games may be started, sending an error if not
+    fn accepts_games(&mut self, client: ClientId) -> bool {
+        if self.draining {
+            self.error(client, ErrorCode::Draining, "Server is shutting down, no new games");
+        }
+
+        !self.draining
+    }
+
+    fn can_enter_game(&mut self, client: ClientId) -> bool {
+        if self.clients[&client].room.is_some() {
+            self.error(client, ErrorCode::AlreadyInGame, "You are already in a game, LEAVE it first");
+            return false;
+        }
+
+        if let Some(games) = self.quota_reached(client) {
+            let message = format!("Your key's limit of games at a time is {}", games);
+            self.error(client, ErrorCode::GameQuota, &message);
+            return false;
+        }
+
+        if self.waiting == Some(client) {
+            self.waiting = None;
+        }
+
+        true
+    }
+
+    /// The limit of games at a time of the client's API key if the key's
+    /// other connections reached it, counting those playing and waiting
+    fn quota_reached(&self, client: ClientId) -> Option<usize> {
+        let tier = self.tier(client).filter(|tier| tier.games > 0)?;
+        let key = &self.clients[&client].key;
+        let playing = self
+            .clients
+            .iter()
+            .filter(|&(&id, c)| id != client && c.key == *key)
+            .filter(|&(&id, c)| {
+                self.waiting == Some(id)
+                    || c.room.is_some_and(|room| self.rooms[&room].player_of(id).is_some())
+            })
+            .count();
+
+        if playing >= tier.games { Some(tier.games) } else { None }
+    }
+
+    fn create(&mut self, client: ClientId, name: &str) {
+        if !self.accepts_games(client) || !self.can_enter_game(client) {
+            return;
+        }
+
+        let id = GameId::random();
+        let name = if name.is_empty() {
+            format!("{}'s game", self.clients[&client].name)
+        } else {
+            name.to_string()
+        };
+
+        self.rooms.insert(
+            id,
+            Room {
+                name,
+                players: [Some(client), None],
+                spectators: Vec::new(),
+                board: Board::new(Player::X),
+                sent: Board::new(Player::X),
+                moves: Vec::new(),
+                takeback: None,
+                turn_started: Instant::now(),
+                ladder: false,
+            },
+        );
+        self.clients.get_mut(&client).unwrap().room = Some(id);
+        self.send(client, &format!("CREATED {}", id));
+    }
+
+    fn join(&mut self, client: ClientId, room: RoomId) {
+        if !self.accepts_games(client) || !self.can_enter_game(client) {
+            return;
+        }
+
+        match self.rooms.get_mut(&room) {
+            Some(r) if r.players[1].is_none() => r.players[1] = Some(client),
+            Some(_) => return self.error(client, ErrorCode::GameFull, "Game is full"),
+            None => return self.error(client, ErrorCode::NoSuchGame, "No such game"),
+        }
+
+        self.clients.get_mut(&client).unwrap().room = Some(room);
+        self.start(room);
+    }
+
+    /// Pair the client with whoever waits for a game, or let it wait itself
+    fn pair(&mut self, client: ClientId) {
+        if !self.accepts_games(client) || !self.can_enter_game(client) {
+            return;
+        }
+
+        match self.waiting.take() {
+            Some(opponent) => {
+                let id = GameId::random();
+                let name = format!("{} vs {}", self.clients[&opponent].name, self.clients[&client].name);
+
+                self.rooms.insert(
+                    id,
+                    Room {
+                        name,
+                        players: [Some(opponent), Some(client)],
+                        spectators: Vec::new(),
+                        board: Board::new(Player::X),
+                        sent: Board::new(Player::X),
+                        moves: Vec::new(),
+                        takeback: None,
+                        turn_started: Instant::now(),
+                        ladder: false,
+                    },
+                );
+                self.clients.get_mut(&opponent).unwrap().room = Some(id);
+                self.clients.get_mut(&client).unwrap().room = Some(id);
+                self.start(id);
+            }
+            None => {
+                self.waiting = Some(client);
+                self.send(client, "WAITING");
+            }
+        }
+    }
+
+    /// Start a game against the server's engine, the client moving first
+    fn play_engine(&mut self, client: ClientId) {
+        if !self.accepts_games(client) || !self.can_enter_game(client) {
+            return;
+        }
+
+        let engine = self.next_id();
+        let id = GameId::random();
+        let name = format!("engine{}", engine);
+        let room_name = format!("{} vs {}", self.clients[&client].name, name);
+
+        self.clients.insert(
+            engine,
+            Client {
+                name,
+                stream: None,
+                disconnected: None,
+                token: String::new(),
+                room: Some(id),
+                greeted: true,
+                capabilities: Vec::new(),
+                key: None,
+                ladder: false,
+                engine: true,
+                violations: 0,
+                allowance: COMMAND_BURST,
+                last_command: Instant::now(),
+            },
+        );
+        self.rooms.insert(
+            id,
+            Room {
+                name: room_name,
+                players: [Some(client), Some(engine)],
+                spectators: Vec::new(),
+                board: Board::new(Player::X),
+                sent: Board::new(Player::X),
+                moves: Vec::new(),
+                takeback: None,
+                turn_started: Instant::now(),
+                ladder: false,
+            },
+        );
+        self.clients.get_mut(&client).unwrap().room = Some(id);
+        self.start(id);
+    }
+
+    /// The games where it's the engine's move, by their position. Engines
+    /// whose game is over are removed.
+    fn engine_turns(&mut self) -> Vec<(RoomId, Board)> {
+        self.clients.retain(|_, c| !c.engine || c.room.is_some());
+
+        self.rooms
+            .iter()
+            .filter(|&(_, r)| r.players[1].is_some() && !r.board.is_ended())
+            .filter(|&(_, r)| {
+                let index = if r.board.next_player() == Player::X { 0 } else { 1 };
+                r.players[index].is_some_and(|p| self.clients[&p].engine)
+            })
+            .map(|(&id, r)| (id, r.board))
+            .collect()
+    }
+
+    /// Play the move the engine found on `board`, unless the game moved on
+    /// while it was searching
+    fn engine_move(&mut self, room: RoomId, board: Board, result: &SearchResult) {
+        self.stats.search_time.observe_duration(result.elapsed);
+        self.stats.search_iterations += result.nodes;
+        self.stats.search_rate = (result.nodes as f64 / result.elapsed.as_secs_f64()) as usize;
+
+        let engine = match self.rooms.get(&room) {
+            Some(r) if r.board == board => {
+                let index = if board.next_player() == Player::X { 0 } else { 1 };
+                r.players[index].expect("Engine's game without the engine")
+            }
+            _ => return,
+        };
+
+        if let Some(action) = result.action {
+            self.perform_move(engine, &action.to_string());
+        }
+    }
+
+    fn join_ladder(&mut self, client: ClientId) {
+        if !self.accepts_games(client) || !self.can_enter_game(client) {
+            return;
+        }
+
+        self.clients.get_mut(&client).unwrap().ladder = true;
+        let rating = self.ratings.get(&self.rated_name(client)).cloned().unwrap_or_default();
+        self.send(client, &format!("LADDER {:.0}", rating.elo));
+
+        self.pair_ladder();
+    }
+
+    /// Pair the ladder's players who aren't in a game, each with the closest
+    /// rated one left, other than another connection of the same bot
+    fn pair_ladder(&mut self) {
+        if self.draining {
+            return;
+        }
+
+        let elo =
+            |id: ClientId| self.ratings.get(&self.rated_name(id)).map_or(LADDER_START, |r| r.elo);
+        let mut idle: Vec<(f64, ClientId)> = self
+            .clients
+            .iter()
+            .filter(|&(_, c)| c.ladder && c.room.is_none() && c.stream.is_some())
+            .filter(|&(&id, _)| self.waiting != Some(id) && self.quota_reached(id).is_none())
+            .map(|(&id, _)| (elo(id), id))
+            .collect();
+        idle.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+
+        while !idle.is_empty() {
+            let (_, a) = idle.remove(0);
+            let name = self.rated_name(a);
+            let b = match idle.iter().position(|&(_, b)| self.rated_name(b) != name) {
+                Some(index) => idle.remove(index).1,
+                None => continue,
+            };
+            let (x, o) = if rand::random() { (a, b) } else { (b, a) };
+
+            let id = GameId::random();
+            let name = format!("Ladder: {} vs {}", self.clients[&x].name, self.clients[&o].name);
+            self.rooms.insert(
+                id,
+                Room {
+                    name,
+                    players: [Some(x), Some(o)],
+                    spectators: Vec::new(),
+                    board: Board::new(Player::X),
+                    sent: Board::new(Player::X),
+                    moves: Vec::new(),
+                    takeback: None,
+                    turn_started: Instant::now(),
+                    ladder: true,
+                },
+            );
+            self.clients.get_mut(&x).unwrap().room = Some(id);
+            self.clients.get_mut(&o).unwrap().room = Some(id);
+            self.start(id);
+        }
+    }
+
+    /// Move the ratings of a finished ladder game's players towards the
+    /// result, by how unexpected it was
+    fn rate(&mut self, room: RoomId, winner: Option<Player>) {
+        let r = &self.rooms[&room];
+        if !r.ladder {
+            return;
+        }
+
+        let names = r.players.map(|p| p.map_or(String::new(), |p| self.rated_name(p)));
+        let elo = |name: &String| self.ratings.get(name).map_or(LADDER_START, |r| r.elo);
+        let expected = 1. / (1. + 10f64.powf((elo(&names[1]) - elo(&names[0])) / 400.));
+
+        for (name, player, expected) in [
+            (&names[0], Player::X, expected),
+            (&names[1], Player::O, 1. - expected),
+        ] {
+            let rating = self.ratings.entry(name.clone()).or_default();
+            let score = match winner {
+                Some(winner) if winner == player => 1.,
+                Some(_) => 0.,
+                None => 0.5,
+            };
+            rating.elo += LADDER_K * (score - expected);
+            rating.results.record(player, winner);
+        }
+    }
+
+    /// The ladder's players from the highest rated down, e.g.
+    /// `RANKED 1 alice 1532 +3 =1 -0`
+    fn standings(&mut self, client: ClientId) {
+        let mut ratings: Vec<(String, Rating)> =
+            self.ratings.iter().map(|(name, &rating)| (name.clone(), rating)).collect();
+        ratings.sort_by(|a, b| b.1.elo.total_cmp(&a.1.elo).then(a.0.cmp(&b.0)));
+
+        for (rank, (name, rating)) in ratings.iter().enumerate() {
+            let results = rating.results;
+            self.send(
+                client,
+                &format!(
+                    "RANKED {} {} {:.0} +{} ={} -{}",
+                    rank + 1,
+                    name,
+                    rating.elo,
+                    results.wins,
+                    results.draws,
+                    results.losses
+                ),
+            );
+        }
+        self.send(client, "END");
+    }
+
+    fn watch(&mut self, client: ClientId, room: RoomId) {
+        if !self.can_enter_game(client) {
+            return;
+        }
+
+        match self.rooms.get_mut(&room) {
+            Some(r) => r.spectators.push(client),
+            None => return self.error(client, ErrorCode::NoSuchGame, "No such game"),
+        }
+
+        self.clients.get_mut(&client).unwrap().room = Some(room);
+
+        let players: Vec<String> = self.rooms[&room]
+            .players
+            .iter()
+            .map(|p| p.map_or("-".to_string(), |p| self.clients[&p].name.clone()))
+            .collect();
+        self.send(client, &format!("WATCHING {} {}", room, players.join(" ")));
+
+        let board = self.rooms[&room].board;
+        self.send(client, &format!("BOARD {}", board.fen()));
+    }
+
+    fn start(&mut self, room: RoomId) {
+        let [x, o] = self.rooms[&room].players;
+        let (x, o) = (x.unwrap(), o.unwrap());
+        let names = (self.clients[&x].name.clone(), self.clients[&o].name.clone());
+
+        self.stats.games_started += 1;
+        self.send(x, &format!("START {} X {}", room, names.1));
+        self.send(o, &format!("START {} O {}", room, names.0));
+
+        for spectator in self.rooms[&room].spectators.clone() {
+            self.send(spectator, &format!("START {} - {} {}", room, names.0, names.1));
+        }
+
+        self.send_state(room);
+    }
+
+    /// Send the board, as the cells changed since it was last sent to
+    /// clients with the diff capability, e.g. `CHANGED 2b x 1a o`
+    fn send_state(&mut self, room: RoomId) {
+        let board = self.rooms[&room].board;
+        let changes = self.rooms[&room].sent.diff(&board);
+        self.rooms.get_mut(&room).unwrap().sent = board;
+
+        let changed: Vec<String> = changes.iter().map(|change| change.to_string()).collect();
+        for client in self.rooms[&room].clients() {
+            if self.has_capability(client, "diff") && !changes.is_empty() {
+                self.send(client, &format!("CHANGED {}", changed.join(" ")));
+            } else {
+                self.send(client, &format!("BOARD {}", board.fen()));
+            }
+        }
+
+        let status = board.status();
+        if status != GameStatus::InProgress {
+            self.stats.count_result(status.winner());
+            self.store_finished(room, status.winner());
+            self.rate(room, status.winner());
+            match status.winner() {
+                Some(player) => self.send_room(room, &format!("RESULT {}", player)),
+                None => self.send_room(room, "RESULT draw"),
+            }
+        } else {
+            self.store_running(room);
+            self.rooms.get_mut(&room).unwrap().turn_started = Instant::now();
+            self.send_room(room, &format!("TURN {}", board.next_player()));
+        }
+    }
+
+    fn perform_move(&mut self, client: ClientId, argument: &str) {
+        let room_id = match self.clients[&client].room {
+            Some(room) => room,
+            None => return self.error(client, ErrorCode::NotInGame, "You are not in a game"),
+        };
+
+        let (action, number) = match argument.split_once(' ') {
+            Some((action, number)) => match number.trim().parse::<usize>() {
+                Ok(number) if number > 0 => (action, Some(number)),
+                _ => {
+                    return self.error(client, ErrorCode::InvalidArgument, "Expected a move number")
+                }
+            },
+            None => (argument, None),
+        };
+
+        // A numbered move is only played once, a client retrying it after
+        // losing the connection finds out it went through
+        if let Some(number) = number {
+            let room = &self.rooms[&room_id];
+            let played = room.moves.len();
+            // Games start with X, who makes the odd-numbered moves
+            let mover = if number % 2 == 1 { Player::X } else { Player::O };
+
+            if number <= played
+                && action.parse::<Coord>().ok() == Some(room.moves[number - 1])
+                && room.player_of(client) == Some(mover)
+            {
+                let message = format!("Move {} was already played", number);
+                return self.error(client, ErrorCode::AlreadyPlayed, &message);
+            }
+            if number != played + 1 {
+                let message = format!("Expected move {}", played + 1);
+                return self.error(client, ErrorCode::OutOfSequence, &message);
+            }
+        }
+
+        // The server's board is authoritative, every move is checked against it
+        let room = self.rooms.get_mut(&room_id).unwrap();
+        let result = match (room.player_of(client), action.parse::<Coord>().ok()) {
+            (None, _) => Err((ErrorCode::Spectator, "You are only watching")),
+            _ if room.players[1].is_none() => Err((ErrorCode::NoOpponent, "Waiting for an opponent")),
+            _ if room.board.is_ended() => Err((ErrorCode::GameOver, "Game is over")),
+            (Some(player), _) if player != room.board.next_player() => {
+                Err((ErrorCode::OutOfTurn, "Not your turn"))
+            }
+            (_, Some(action)) if room.board.is_legal_action(action) => {
+                self.stats.moves += 1;
+                self.stats.move_time.observe_duration(room.turn_started.elapsed());
+                room.board.perform_action(action);
+                room.moves.push(action);
+                // A takeback request is about the position it was made in
+                room.takeback = None;
+                Ok(action)
+            }
+            (_, Some(_)) => Err((ErrorCode::IllegalMove, "Cell is taken or off the board")),
+            (_, None) => Err((ErrorCode::InvalidMove, "Expected a move like 2b")),
+        };
+
+        let action = match result {
+            Ok(action) => action,
+            Err((code, message)) => return self.error(client, code, message),
+        };
+
+        let player = self.rooms[&room_id].player_of(client).unwrap();
+        self.send_room(
+            room_id,
+            &format!("MOVED {} {}", player, action),
+        );
+        self.send_state(room_id);
+
+        // Finished games are closed, the players return to the lobby
+        if self.rooms[&room_id].board.is_ended() {
+            for client in self.rooms.remove(&room_id).unwrap().clients() {
+                self.clients.get_mut(&client).unwrap().room = None;
+                self.send(client, "LOBBY");
+            }
+        }
+    }
+
+    /// The room the client plays in and its side, or `None` after sending
+    /// an error if it is watching or not in a running game
+    fn playing_room(&mut self, client: ClientId) -> Option<(RoomId, Player)> {
+        let room = match self.clients[&client].room {
+            Some(room) => room,
+            None => {
+                self.error(client, ErrorCode::NotInGame, "You are not in a game");
+                return None;
+            }
+        };
+
+        match self.rooms[&room].player_of(client) {
+            _ if self.rooms[&room].players[1].is_none() => {
+                self.error(client, ErrorCode::NoOpponent, "Waiting for an opponent")
+            }
+            Some(player) => return Some((room, player)),
+            None => self.error(client, ErrorCode::Spectator, "You are only watching"),
+        }
+
+        None
+    }
+
+    /// Ask the opponent to undo the client's last move
+    fn request_takeback(&mut self, client: ClientId) {
+        let (room_id, player) = match self.playing_room(client) {
+            Some(playing) => playing,
+            None => return,
+        };
+
+        let room = self.rooms.get_mut(&room_id).unwrap();
+        let undone = if room.board.next_player() == player { 2 } else { 1 };
+        if room.moves.len() < undone {
+            return self.error(client, ErrorCode::NothingToTakeBack, "You haven't moved yet");
+        }
+
+        room.takeback = Some(player);
+        let opponent = room.players[if player == Player::X { 1 } else { 0 }];
+        self.send_room(room_id, &format!("TAKEBACK {}", player));
+
+        // The engine always agrees
+        if let Some(engine) = opponent.filter(|p| self.clients[p].engine) {
+            self.answer_takeback(engine, true);
+        }
+    }
+
+    /// Accept or decline the opponent's takeback request. Accepting rolls the
+    /// board back to before the opponent's last move.
+    fn answer_takeback(&mut self, client: ClientId, accept: bool) {
+        let (room_id, player) = match self.playing_room(client) {
+            Some(playing) => playing,
+            None => return,
+        };
+
+        let room = self.rooms.get_mut(&room_id).unwrap();
+        let requester = match room.takeback {
+            Some(requester) if requester != player => requester,
+            _ => {
+                return self.error(
+                    client,
+                    ErrorCode::NoTakebackRequest,
+                    "Your opponent didn't ask for a takeback",
+                )
+            }
+        };
+        room.takeback = None;
+
+        if !accept {
+            return self.send_room(room_id, &format!("DECLINED {}", player));
+        }
+
+        // Undo the requester's last move and whatever the opponent replied,
+        // replaying the rest on the authoritative board
+        let undone = if room.board.next_player() == requester { 2 } else { 1 };
+        let remaining = room.moves.len() - undone;
+        room.moves.truncate(remaining);
+        room.board = Board::new(Player::X);
+        for &action in &room.moves {
+            room.board.perform_action(action);
+        }
+
+        self.send_room(room_id, &format!("ACCEPTED {} {}", player, undone));
+        self.send_state(room_id);
+    }
+
+    fn say(&mut self, client: ClientId, text: &str) {
+        let room = match self.clients[&client].room {
+            Some(room) => room,
+            None => return self.error(client, ErrorCode::NotInGame, "You are not in a game"),
+        };
+
+        if self.rooms[&room].player_of(client).is_none() {
+            return self.error(client, ErrorCode::Spectator, "Spectators can't chat");
+        }
+
+        let message = format!("CHAT {} {}", self.clients[&client].name, text);
+        for other in self.rooms[&room].players() {
+            // Only clients that asked for chat get the messages
+            if other != client && self.has_capability(other, "chat") {
+                self.send(other, &message);
+            }
+        }
+    }
+
+    /// Leave the current game (or stop waiting for one or watching). If a
+    /// player leaves, everyone else is sent back to the lobby, and a running
+    /// ladder game is lost.
+    fn leave(&mut self, client: ClientId) {
+        if self.waiting == Some(client) {
+            self.waiting = None;
+        }
+
+        let room = match self.clients.get_mut(&client).and_then(|c| c.room.take()) {
+            Some(room) => room,
+            None => return,
+        };
+
+        if self.rooms[&room].player_of(client).is_none() {
+            self.rooms.get_mut(&room).unwrap().spectators.retain(|&s| s != client);
+            return;
+        }
+
+        // Leaving a running ladder game loses it, like running out of time.
+        // Otherwise an abandoned game has no result worth keeping.
+        let r = &self.rooms[&room];
+        if r.ladder && r.players[1].is_some() && !r.board.is_ended() {
+            let winner = r.player_of(client).unwrap().opponent();
+            self.stats.count_result(Some(winner));
+            self.store_finished(room, Some(winner));
+            self.rate(room, Some(winner));
+        } else {
+            self.store(|storage| storage.remove_running(room));
+        }
+
+        let name = self.clients[&client].name.clone();
+        for other in self.rooms.remove(&room).unwrap().clients() {
+            if other != client {
+                self.clients.get_mut(&other).unwrap().room = None;
+                self.send(other, &format!("LEFT {}", name));
+                self.send(other, "LOBBY");
+            }
+        }
+    }
+}
+
+fn serve_client(lobby: &Mutex<Lobby>, stream: TcpStream) {
+    // Reading fails once the connection stays silent for too long, which is
+    // handled like losing it
+    let idle_timeout = lobby.lock().unwrap().limits.idle_timeout;
+    if idle_timeout > Duration::from_secs(0) {
+        let _ = stream.set_read_timeout(Some(idle_timeout));
+    }
+
+    let reader = match stream.try_clone() {
+        Ok(reader) => BufReader::new(reader),
+        Err(_) => return,
+    };
+    let connection = match Connection::open(stream) {
+        Ok(connection) => connection,
+        Err(_) => return,
+    };
+    let mut client = lobby.lock().unwrap().connect(connection);
+
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        let next = lobby.lock().unwrap().handle(client, &line);
+        match next {
+            Some(id) => client = id,
+            // Leaving on purpose (or failed handshake): nothing to resume
+            None => {
+                lobby.lock().unwrap().remove(client);
+                return;
+            }
+        }
+    }
+
+    lobby.lock().unwrap().disconnect(client);
+}
+
+/// Answer admin commands, one per line. Every answer ends with `OK` or
+/// `ERROR <message>`.
+fn serve_admin(lobby: &Mutex<Lobby>, stream: TcpStream) {
+    let reader = match stream.try_clone() {
+        Ok(reader) => BufReader::new(reader),
+        Err(_) => return,
+    };
+    let mut stream = stream;
+
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+
+        let answer = lobby.lock().unwrap().admin(&line);
+        let written = match answer {
+            Ok(lines) => lines
+                .iter()
+                .try_for_each(|l| writeln!(stream, "{}", l))
+                .and_then(|_| writeln!(stream, "OK")),
+            Err(e) => writeln!(stream, "ERROR {}", e),
+        };
+        if written.is_err() {
+            return;
+        }
+    }
+}
+
+/// Send a command to the admin channel of a running server and print the
+/// answer
+pub fn admin(args: &[String]) {
+    let mut port = DEFAULT_ADMIN_PORT;
+    let mut words = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => port = cli::value(arg, args.next()),
+            _ => words.push(arg.as_str()),
+        }
+    }
+
+    if words.is_empty() {
+        cli::fail("Missing admin command");
+    }
+
+    let mut stream = TcpStream::connect(("127.0.0.1", port))
+        .unwrap_or_else(|e| cli::fail(&format!("Could not connect to port {}: {}", port, e)));
+    writeln!(stream, "{}", words.join(" "))
+        .unwrap_or_else(|e| cli::fail(&format!("Could not send the command: {}", e)));
+
+    for line in BufReader::new(stream).lines() {
+        let line = line.unwrap_or_else(|e| cli::fail(&format!("Connection lost: {}", e)));
+
+        if line == "OK" {
+            return;
+        }
+        if let Some(message) = line.strip_prefix("ERROR ") {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+        println!("{}", line);
+    }
+}
+
+/// Run a game server that players connect to with a line-based protocol
+/// (e.g. using telnet)
+pub fn run(args: &[String]) {
+    let mut port = DEFAULT_PORT;
+    let mut grace_period = DEFAULT_GRACE_PERIOD;
+    let mut limits = Limits::default();
+    let mut metrics_port: Option<u16> = None;
+    let mut data: Option<String> = None;
+    let mut admin_port: Option<u16> = None;
+    let mut keys_file: Option<String> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => port = cli::value(arg, args.next()),
+            "--grace" => grace_period = cli::duration(arg, args.next()),
+            "--max-connections" => limits.connections_per_ip = cli::value(arg, args.next()),
+            "--max-rate" => limits.command_rate = cli::value(arg, args.next()),
+            "--idle-timeout" => limits.idle_timeout = cli::duration(arg, args.next()),
+            "--move-timeout" => limits.move_timeout = cli::duration(arg, args.next()),
+            "--metrics-port" => metrics_port = Some(cli::value(arg, args.next())),
+            "--data" => data = Some(cli::value(arg, args.next())),
+            "--admin-port" => admin_port = Some(cli::value(arg, args.next())),
+            "--keys" => keys_file = Some(cli::value(arg, args.next())),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .unwrap_or_else(|e| cli::fail(&format!("Could not listen on port {}: {}", port, e)));
+    let storage = data.map(|path| {
+        let storage: Box<dyn Storage> =
+            Box::new(Directory::open(&path).unwrap_or_else(|e| cli::fail(&e)));
+        storage
+    });
+    let keys = match keys_file {
+        Some(ref path) => load_keys(path).unwrap_or_else(|e| cli::fail(&e)),
+        None => HashMap::new(),
+    };
+    let mut lobby = Lobby {
+        grace_period,
+        limits,
+        storage,
+        keys,
+        keys_file,
+        ..Lobby::default()
+    };
+    match lobby.restore() {
+        Ok(0) => {}
+        Ok(games) => println!("Restored {} running games", games),
+        Err(e) => cli::fail(&e),
+    }
+    let lobby = Arc::new(Mutex::new(lobby));
+
+    let reaper = lobby.clone();
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+        let mut lobby = reaper.lock().unwrap();
+        lobby.expire_sessions();
+        lobby.expire_turns();
+        lobby.pair_ladder();
+
+        if lobby.draining && lobby.rooms.is_empty() {
+            println!("Drained, shutting down");
+            process::exit(0);
+        }
+    });
+
+    // The engine searches without holding the lock, only taking it to read
+    // the positions and to play its moves
+    let engine = lobby.clone();
+    thread::spawn(move || loop {
+        thread::sleep(ENGINE_POLL);
+        let turns = engine.lock().unwrap().engine_turns();
+
+        for (room, board) in turns {
+            let mut search = MCTS::from_board(board.next_player(), board, Config::default());
+            let result = search.search(Limit::Time(ENGINE_MOVETIME), &StopToken::new());
+            engine.lock().unwrap().engine_move(room, board, &result);
+        }
+    });
+
+    if let Some(metrics_port) = metrics_port {
+        let lobby = lobby.clone();
+        metrics::serve(metrics_port, move || lobby.lock().unwrap().metrics())
+            .unwrap_or_else(|e| cli::fail(&e));
+        println!("Serving metrics on port {} at /metrics", metrics_port);
+    }
+
+    if let Some(admin_port) = admin_port {
+        // Only reachable from the machine itself, which is all the
+        // authentication there is
+        let listener = TcpListener::bind(("127.0.0.1", admin_port)).unwrap_or_else(|e| {
+            cli::fail(&format!("Could not listen on port {}: {}", admin_port, e))
+        });
+        let lobby = lobby.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                serve_admin(&lobby, stream);
+            }
+        });
+        println!("Admin channel on 127.0.0.1:{}", admin_port);
+    }
+
+    println!("Listening on port {}", port);
+
+    for stream in listener.incoming() {
+        let (mut stream, addr) = match stream.and_then(|s| s.peer_addr().map(|a| (s, a))) {
+            Ok(connection) => connection,
+            Err(_) => continue,
+        };
+        let lobby = lobby.clone();
+
+        if !lobby.lock().unwrap().open_connection(addr.ip()) {
+            let _ = writeln!(
+                stream,
+                "ERROR {} Too many connections from your address",
+                ErrorCode::TooManyConnections.code()
+            );
+            continue;
+        }
+        println!("{} connected", addr);
+
+        thread::spawn(move || {
+            serve_client(&lobby, stream);
+            lobby.lock().unwrap().close_connection(addr.ip());
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+    use std::net::{TcpListener, TcpStream};
+    use game::Coord;
+    use storage::{Directory, GameId};
+    use super::{ApiKey, Connection, ClientId, ErrorCode, Lobby, MAX_VIOLATIONS};
+
+    /// A client connected over loopback. The returned socket is its end of
+    /// the connection, which has to stay open.
+    fn open(lobby: &mut Lobby) -> (ClientId, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let stream = listener.accept().unwrap().0;
+
+        (lobby.connect(Connection::open(stream).unwrap()), peer)
+    }
+
+    /// A client connected and through the handshake
+    fn connect(lobby: &mut Lobby, hello: &str) -> (ClientId, TcpStream) {
+        let (client, peer) = open(lobby);
+        assert_eq!(lobby.handle(client, hello), Some(client));
+        (client, peer)
+    }
+
+    /// How often the lobby sent an error with `code`
+    fn errors(lobby: &Lobby, code: ErrorCode) -> u64 {
+        lobby.stats.errors.get(code.code()).cloned().unwrap_or(0)
+    }
+
+    /// Players X and O of the game `client` plays in
+    fn players(lobby: &Lobby, client: ClientId) -> (ClientId, ClientId) {
+        let room = &lobby.rooms[&lobby.clients[&client].room.unwrap()];
+        (room.players[0].unwrap(), room.players[1].unwrap())
+    }
+
+    /// Two clients playing a game against each other
+    fn game(lobby: &mut Lobby) -> (ClientId, ClientId, Vec<TcpStream>) {
+        let (a, peer_a) = connect(lobby, "HELLO 2");
+        let (b, peer_b) = connect(lobby, "HELLO 2");
+        lobby.handle(a, "CREATE");
+        let room = lobby.clients[&a].room.unwrap();
+        lobby.handle(b, &format!("JOIN {}", room));
+
+        let (x, o) = players(lobby, a);
+        (x, o, vec![peer_a, peer_b])
+    }
+
+    fn moves(lobby: &Lobby, client: ClientId) -> Vec<String> {
+        let room = &lobby.rooms[&lobby.clients[&client].room.unwrap()];
+        room.moves.iter().map(Coord::to_string).collect()
+    }
+
+    #[test]
+    fn checks_moves_against_the_board() {
+        let mut lobby = Lobby::default();
+        let (x, o, _peers) = game(&mut lobby);
+
+        lobby.handle(o, "MOVE 2b");
+        assert_eq!(errors(&lobby, ErrorCode::OutOfTurn), 1);
+
+        lobby.handle(x, "MOVE 2b");
+        lobby.handle(o, "MOVE 2b");
+        assert_eq!(errors(&lobby, ErrorCode::IllegalMove), 1);
+        lobby.handle(o, "MOVE 4d");
+        lobby.handle(o, "MOVE center");
+        assert_eq!(errors(&lobby, ErrorCode::InvalidMove), 2);
+
+        lobby.handle(o, "MOVE 1a");
+        assert_eq!(moves(&lobby, x), ["2b", "1a"]);
+        assert_eq!(lobby.clients[&o].violations, 4);
+    }
+
+    #[test]
+    fn disconnects_repeat_offenders() {
+        let mut lobby = Lobby::default();
+        let (client, _peer) = connect(&mut lobby, "HELLO 2");
+
+        for _ in 1..MAX_VIOLATIONS {
+            assert_eq!(lobby.handle(client, "DANCE"), Some(client));
+        }
+        assert_eq!(lobby.handle(client, "DANCE"), None);
+        assert_eq!(errors(&lobby, ErrorCode::UnknownCommand), u64::from(MAX_VIOLATIONS));
+    }
+
+    #[test]
+    fn plays_numbered_moves_once() {
+        let mut lobby = Lobby::default();
+        let (x, o, _peers) = game(&mut lobby);
+
+        lobby.handle(x, "MOVE 2b 1");
+        lobby.handle(x, "MOVE 2b 1");
+        assert_eq!(errors(&lobby, ErrorCode::AlreadyPlayed), 1);
+        // Move 1 was X's, so O sending it is no retry
+        lobby.handle(o, "MOVE 2b 1");
+        lobby.handle(o, "MOVE 1a 3");
+        assert_eq!(errors(&lobby, ErrorCode::OutOfSequence), 2);
+
+        lobby.handle(o, "MOVE 1a 2");
+        lobby.handle(x, "MOVE 3c");
+        assert_eq!(moves(&lobby, x), ["2b", "1a", "3c"]);
+        assert_eq!(lobby.clients[&o].violations, 0);
+    }
+
+    /// A lobby with the key `k` of the bot `bot`, in `tier`
+    fn lobby_with_key(tier: &str) -> Lobby {
+        let mut lobby = Lobby::default();
+        let api_key = ApiKey {
+            name: "bot".to_string(),
+            tier: super::tier(tier).unwrap(),
+        };
+        lobby.keys.insert("k".to_string(), api_key);
+        lobby
+    }
+
+    #[test]
+    fn limits_the_games_of_a_key() {
+        let mut lobby = lobby_with_key("basic");
+        let (a, _peer_a) = connect(&mut lobby, "HELLO 2 key=k");
+        let (b, _peer_b) = connect(&mut lobby, "HELLO 2 key=k");
+        let (guest, _peer_guest) = connect(&mut lobby, "HELLO 2");
+        assert_eq!(lobby.clients[&a].name, "bot");
+        assert_eq!(lobby.clients[&b].name, "bot-2");
+
+        lobby.handle(a, "PLAY");
+        lobby.handle(b, "PLAY");
+        lobby.handle(b, "CREATE");
+        assert_eq!(errors(&lobby, ErrorCode::GameQuota), 2);
+
+        // Playing counts like waiting
+        lobby.handle(guest, "PLAY");
+        assert_eq!(players(&lobby, guest), (a, guest));
+        lobby.handle(b, "PLAY");
+        assert_eq!(errors(&lobby, ErrorCode::GameQuota), 3);
+
+        lobby.handle(a, "LEAVE");
+        lobby.handle(b, "PLAY");
+        assert_eq!(lobby.waiting, Some(b));
+        assert_eq!(errors(&lobby, ErrorCode::GameQuota), 3);
+    }
+
+    #[test]
+    fn keeps_bot_names() {
+        let mut lobby = lobby_with_key("bot");
+        let (bot, _peer_bot) = connect(&mut lobby, "HELLO 2 key=k");
+        let (guest, _peer_guest) = connect(&mut lobby, "HELLO 2");
+
+        lobby.handle(bot, "NAME robot");
+        assert_eq!(errors(&lobby, ErrorCode::InvalidArgument), 1);
+        lobby.handle(guest, "NAME bot");
+        lobby.handle(guest, "NAME bot-2");
+        assert_eq!(errors(&lobby, ErrorCode::NameTaken), 1);
+        assert_eq!(lobby.clients[&bot].name, "bot");
+        assert_eq!(lobby.clients[&guest].name, "bot-2");
+
+        // The bot's next connection skips the name the guest took
+        let (second, _peer_second) = connect(&mut lobby, "HELLO 2 key=k");
+        assert_eq!(lobby.clients[&second].name, "bot-3");
+
+        let (unknown, _peer_unknown) = open(&mut lobby);
+        assert_eq!(lobby.handle(unknown, "HELLO 2 key=nope"), None);
+        assert_eq!(errors(&lobby, ErrorCode::UnknownKey), 1);
+    }
+
+    /// Alice and Bob paired by the ladder, as players X and O
+    fn ladder_game(lobby: &mut Lobby) -> (ClientId, ClientId, Vec<TcpStream>) {
+        let (alice, peer_alice) = connect(lobby, "HELLO 2");
+        let (bob, peer_bob) = connect(lobby, "HELLO 2");
+        lobby.handle(alice, "NAME alice");
+        lobby.handle(bob, "NAME bob");
+        lobby.handle(alice, "LADDER");
+        lobby.handle(bob, "LADDER");
+
+        assert!(lobby.rooms[&lobby.clients[&alice].room.unwrap()].ladder);
+        let (x, o) = players(lobby, alice);
+        (x, o, vec![peer_alice, peer_bob])
+    }
+
+    fn rating(lobby: &Lobby, client: ClientId) -> (f64, u32, u32) {
+        let rating = lobby.ratings[&lobby.clients[&client].name];
+        (rating.elo, rating.results.wins, rating.results.losses)
+    }
+
+    #[test]
+    fn rates_ladder_games() {
+        let mut lobby = Lobby::default();
+        let (x, o, _peers) = ladder_game(&mut lobby);
+
+        for &(client, cell) in &[(x, "1a"), (o, "2a"), (x, "1b"), (o, "2b"), (x, "1c")] {
+            lobby.handle(client, &format!("MOVE {}", cell));
+        }
+
+        assert!(lobby.rooms.is_empty());
+        assert_eq!(rating(&lobby, x), (1516., 1, 0));
+        assert_eq!(rating(&lobby, o), (1484., 0, 1));
+        assert_eq!(lobby.stats.results, [1, 0, 0]);
+
+        // A rated player's name stays theirs while they are away
+        let name = lobby.clients[&x].name.clone();
+        lobby.disconnect(x);
+        let (guest, _peer_guest) = connect(&mut lobby, "HELLO 2");
+        lobby.handle(guest, &format!("NAME {}", name));
+        assert_eq!(errors(&lobby, ErrorCode::NameTaken), 1);
+    }
+
+    #[test]
+    fn rates_leaving_a_ladder_game_as_a_loss() {
+        let mut lobby = Lobby::default();
+        let (x, o, _peers) = ladder_game(&mut lobby);
+
+        lobby.handle(x, "MOVE 2b");
+        lobby.handle(x, "LEAVE");
+
+        assert!(lobby.rooms.is_empty());
+        assert!(!lobby.clients[&x].ladder && lobby.clients[&o].ladder);
+        assert_eq!(rating(&lobby, x), (1484., 0, 1));
+        assert_eq!(rating(&lobby, o), (1516., 1, 0));
+        assert_eq!(lobby.stats.results, [0, 1, 0]);
+    }
+
+    /// A lobby keeping its games in `path`
+    fn lobby_in(path: &str) -> Lobby {
+        Lobby {
+            storage: Some(Box::new(Directory::open(path).unwrap())),
+            ..lobby_with_key("bot")
+        }
+    }
+
+    #[test]
+    fn restores_running_games() {
+        let path = env::temp_dir().join(format!("mcts-server-{}", process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut lobby = lobby_in(path);
+        let (bot, _peer_bot) = connect(&mut lobby, "HELLO 2 key=k");
+        let (guest, _peer_guest) = connect(&mut lobby, "HELLO 2");
+        lobby.handle(bot, "LADDER");
+        lobby.handle(guest, "LADDER");
+        let (x, o) = players(&lobby, bot);
+        lobby.handle(x, "MOVE 2b");
+        lobby.handle(o, "MOVE 1a");
+
+        let room = lobby.clients[&bot].room.unwrap();
+        let token = lobby.clients[&bot].token.clone();
+        let broken = GameId::random();
+        fs::write(format!("{}/running/{}.txt", path, broken), "# x alice\n4d\n").unwrap();
+
+        let mut restarted = lobby_in(path);
+        let restored = restarted.restore();
+        let mut keyless = Lobby {
+            storage: Some(Box::new(Directory::open(path).unwrap())),
+            ..Lobby::default()
+        };
+        let keyless_restored = keyless.restore();
+        fs::remove_dir_all(path).unwrap();
+
+        // The broken game is left out
+        assert_eq!(restored, Ok(1));
+        assert_eq!(restarted.rooms.len(), 1);
+        let r = &restarted.rooms[&room];
+        assert!(r.ladder);
+        assert_eq!(r.board.fen(), "o../.x./... x");
+
+        let resumed = restarted.sessions[&token];
+        let c = &restarted.clients[&resumed];
+        assert_eq!((c.name.as_str(), c.key.as_deref()), ("bot", Some("k")));
+        assert!(c.disconnected.is_some() && c.room == Some(room));
+        assert_eq!(r.player_of(resumed), lobby.rooms[&room].player_of(bot));
+
+        // A key that's no longer registered doesn't count
+        assert_eq!(keyless_restored, Ok(1));
+        assert_eq!(keyless.clients[&keyless.sessions[&token]].key, None);
+    }
+}
@@ -0,0 +1,43 @@
+use cli;
+use record::GameRecord;
+
+/// Print the share code of a saved game, or a link with it, or turn a link or code back into a game
+pub fn run(args: &[String]) {
+    let mut file: Option<&String> = None;
+    let mut base: Option<String> = None;
+    let mut decode: Option<String> = None;
+    let mut save: Option<String> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--base" => base = Some(cli::value(arg, args.next())),
+            "--decode" => decode = Some(cli::value(arg, args.next())),
+            "--save" => save = Some(cli::value(arg, args.next())),
+            _ if file.is_none() && !arg.starts_with("--") => file = Some(arg),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    match (file, decode) {
+        (Some(file), None) => {
+            let record = GameRecord::load(file).unwrap_or_else(|e| cli::fail(&e));
+            match base {
+                Some(base) => println!("{}?game={}", base, record.share_code()),
+                None => println!("{}", record.share_code()),
+            }
+        }
+        (None, Some(link)) => {
+            // Accept the bare code as well as a whole link
+            let code = link.rsplit("game=").next().unwrap_or("");
+            let code = code.split('&').next().unwrap_or("");
+            let record = GameRecord::from_share_code(code).unwrap_or_else(|e| cli::fail(&e));
+
+            match save {
+                Some(path) => record.save(&path).unwrap_or_else(|e| cli::fail(&e)),
+                None => print!("{}", record),
+            }
+        }
+        _ => cli::fail("Expected either a game file or --decode"),
+    }
+}
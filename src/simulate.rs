@@ -0,0 +1,265 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+use rand::{self, Rng};
+use cli;
+use engine::Engine;
+use game::{Board, Coord, Player};
+use mcts::{self, Config, Limit, StopToken, MCTS};
+use minimax;
+use parallel;
+use pns;
+use rules::Variant;
+use selfplay::{self, MatchResult};
+use table;
+
+/// An engine playing the simulated games
+#[derive(Debug, Clone)]
+enum Contestant {
+    /// Uniformly random moves
+    Random,
+    /// The heuristic of the rollouts: win, block, fork, otherwise random
+    Heuristic,
+    Mcts(Config),
+    Pns,
+    Negamax,
+    Perfect,
+}
+
+impl Contestant {
+    /// Parse `random`, `heuristic`, an engine name, or `mcts:<config>` with a
+    /// configuration like `--entrant` takes
+    fn parse(spec: &str) -> Result<Contestant, String> {
+        match spec {
+            "random" => return Ok(Contestant::Random),
+            "heuristic" => return Ok(Contestant::Heuristic),
+            _ => {}
+        }
+        if let Some(config) = spec.strip_prefix("mcts:") {
+            return Config::parse(config).map(Contestant::Mcts);
+        }
+
+        match Engine::by_name(spec) {
+            Some(Engine::Mcts) => Ok(Contestant::Mcts(Config::default())),
+            Some(Engine::Pns) => Ok(Contestant::Pns),
+            Some(Engine::Negamax) => Ok(Contestant::Negamax),
+            Some(Engine::Perfect) => Ok(Contestant::Perfect),
+            Some(Engine::QLearning) => Err("The qlearning engine can't be simulated".to_string()),
+            None => {
+                let engines: Vec<&str> =
+                    Engine::names().into_iter().filter(|&name| name != "qlearning").collect();
+                Err(format!(
+                    "Unknown engine {}, available: random, heuristic, mcts:<config>, {}",
+                    spec,
+                    engines.join(", ")
+                ))
+            }
+        }
+    }
+
+    fn choose(&self, board: &Board, iterations: u32) -> Coord {
+        let mut rng = rand::thread_rng();
+        let action = match *self {
+            Contestant::Random => None,
+            Contestant::Heuristic => mcts::heuristic_action(board),
+            Contestant::Mcts(config) => {
+                let mut engine = MCTS::from_board(board.next_player(), *board, config);
+                engine.search(Limit::Iterations(u64::from(iterations)), &StopToken::new()).action
+            }
+            Contestant::Pns => pns::solve(board).action,
+            Contestant::Negamax => rng.choose(&minimax::shared().best_actions(board)).cloned(),
+            Contestant::Perfect => rng.choose(&table::perfect(board).1).cloned(),
+        };
+
+        action.unwrap_or_else(|| *rng.choose(&board.get_actions()).expect("No legal action"))
+    }
+}
+
+impl fmt::Display for Contestant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Contestant::Random => write!(f, "random"),
+            Contestant::Heuristic => write!(f, "heuristic"),
+            Contestant::Mcts(config) => write!(f, "mcts [{}]", config),
+            Contestant::Pns => write!(f, "pns"),
+            Contestant::Negamax => write!(f, "negamax"),
+            Contestant::Perfect => write!(f, "perfect"),
+        }
+    }
+}
+
+/// How a simulated game went
+#[derive(Debug, Copy, Clone)]
+struct Game {
+    /// The side engine A played
+    a_player: Player,
+    winner: Option<Player>,
+    moves: usize,
+    first_move: Option<Coord>,
+    /// Time engines A and B spent choosing their moves
+    thinking: [Duration; 2],
+}
+
+fn play(a: &Contestant, b: &Contestant, game: u32, variant: Variant, iterations: u32) -> Game {
+    let a_player = selfplay::first_engine_player(game);
+    let mut board = Board::with_variant(Player::X, variant);
+    let mut played = Game {
+        a_player,
+        winner: None,
+        moves: 0,
+        first_move: None,
+        thinking: [Duration::from_secs(0); 2],
+    };
+
+    while !board.is_ended() {
+        let side = if board.next_player() == a_player { 0 } else { 1 };
+        let start = Instant::now();
+        let action = [a, b][side].choose(&board, iterations);
+        played.thinking[side] += start.elapsed();
+
+        played.first_move = played.first_move.or(Some(action));
+        board.perform_action(action);
+        played.moves += 1;
+    }
+
+    played.winner = board.status().winner();
+    played
+}
+
+fn percent(count: u32, total: usize) -> String {
+    format!("{:5.1}%", f64::from(count) * 100. / total as f64)
+}
+
+fn report(games: &[Game], elapsed: Duration) {
+    let total = games.len();
+
+    let mut result = MatchResult::default();
+    let mut sides = MatchResult::default();
+    for game in games {
+        result.record(game.a_player, game.winner);
+        sides.record(Player::X, game.winner);
+    }
+
+    println!("Results");
+    println!("  A wins      {:>7}  {}", result.wins, percent(result.wins, total));
+    println!("  Draws       {:>7}  {}", result.draws, percent(result.draws, total));
+    println!("  B wins      {:>7}  {}", result.losses, percent(result.losses, total));
+    println!("  X wins      {:>7}  {}", sides.wins, percent(sides.wins, total));
+    println!("  O wins      {:>7}  {}", sides.losses, percent(sides.losses, total));
+    println!("  Score of A  {:>7.1}%", result.score() * 100.);
+
+    let mut lengths = vec![0u32; games.iter().map(|g| g.moves).max().unwrap_or(0) + 1];
+    for game in games {
+        lengths[game.moves] += 1;
+    }
+    let average = games.iter().map(|g| g.moves).sum::<usize>() as f64 / total as f64;
+
+    println!();
+    println!("Game length, {:.2} moves on average", average);
+    for (moves, &count) in lengths.iter().enumerate().filter(|&(_, &count)| count > 0) {
+        println!("  {:>2} moves    {:>7}  {}", moves, count, percent(count, total));
+    }
+
+    // The first mover's results after each opening move
+    let mut first_moves: Vec<(Coord, MatchResult)> = Vec::new();
+    for game in games {
+        let action = match game.first_move {
+            Some(action) => action,
+            None => continue,
+        };
+        let index = first_moves.iter().position(|&(a, _)| a == action).unwrap_or_else(|| {
+            first_moves.push((action, MatchResult::default()));
+            first_moves.len() - 1
+        });
+        first_moves[index].1.record(Player::X, game.winner);
+    }
+    first_moves.sort_by_key(|&(action, _)| action.index());
+
+    println!();
+    println!("First moves, with X's results after them");
+    for (action, result) in first_moves {
+        let count = result.wins + result.draws + result.losses;
+        println!(
+            "  {}          {:>7}  {}  +{} ={} -{}",
+            action,
+            count,
+            percent(count, total),
+            result.wins,
+            result.draws,
+            result.losses
+        );
+    }
+
+    println!();
+    println!("Timing");
+    println!(
+        "  {} games in {:.2}s, {:.1} games/s",
+        total,
+        elapsed.as_secs_f64(),
+        total as f64 / elapsed.as_secs_f64()
+    );
+    for (side, name) in ["A", "B"].iter().enumerate() {
+        let moves: usize = games
+            .iter()
+            .map(|g| {
+                // A moves first in the games it plays as X
+                let first = (g.a_player == Player::X) == (side == 0);
+                if first { g.moves.div_ceil(2) } else { g.moves / 2 }
+            })
+            .sum();
+        let thinking: Duration = games.iter().map(|g| g.thinking[side]).sum();
+        println!(
+            "  {} thinks {:.3} ms per move",
+            name,
+            thinking.as_secs_f64() * 1000. / moves.max(1) as f64
+        );
+    }
+}
+
+/// Play many games between two engines without showing them and print
+/// statistics about the results
+pub fn run(args: &[String]) {
+    let mut games = 1000;
+    let mut a = Contestant::Mcts(Config::default());
+    let mut b = Contestant::Random;
+    let mut iterations = 100;
+    let mut variant = Variant::STANDARD;
+    let mut jobs = parallel::default_jobs();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => games = cli::value(arg, args.next()),
+            "--engine-a" | "--engine-b" => {
+                let spec: String = cli::value(arg, args.next());
+                let contestant = Contestant::parse(&spec).unwrap_or_else(|e| cli::fail(&e));
+                if arg == "--engine-a" {
+                    a = contestant;
+                } else {
+                    b = contestant;
+                }
+            }
+            "--iterations" => iterations = cli::value(arg, args.next()),
+            "--variant" => variant = cli::variant(args.next()),
+            "--jobs" => jobs = cli::value(arg, args.next()),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    if games == 0 || iterations == 0 || jobs == 0 {
+        cli::fail("--games, --iterations and --jobs must be positive");
+    }
+
+    println!("A: {}", a);
+    println!("B: {}", b);
+    println!(
+        "Playing {} games on the {} board, taking turns to start, {} at a time",
+        games, variant.name(), jobs
+    );
+    println!();
+
+    let start = Instant::now();
+    let numbers: Vec<u32> = (0..games).collect();
+    let played =
+        parallel::map(&numbers, jobs, || (), |_, &game| play(&a, &b, game, variant, iterations));
+    report(&played, start.elapsed());
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use cli;
+use game;
+
+const EMPTY: u8 = 0;
+const X: u8 = 1;
+const O: u8 = 2;
+
+/// Exhaustive solver for m,n,k-games (k in a row on an m×n board)
+struct Solver {
+    /// Cell indices of every line of k cells
+    lines: Vec<Vec<usize>>,
+    /// Cell permutations mapping a position onto its symmetric ones
+    symmetries: Vec<Vec<usize>>,
+    /// Values of all reachable positions (for the player to move), by canonical key
+    values: HashMap<u64, i8>,
+}
+
+impl Solver {
+    fn new(rows: usize, cols: usize, k: usize) -> Solver {
+        let index = move |r: usize, c: usize| r * cols + c;
+        let lines = game::lines(rows, cols, k)
+            .map(|line| line.into_iter().map(|(r, c)| index(r as usize, c as usize)).collect())
+            .collect();
+
+        // Mirroring works on every board, rotating by 90° and transposing only
+        // on square ones
+        let mut transforms: Vec<Box<dyn Fn(usize, usize) -> usize>> = vec![
+            Box::new(index),
+            Box::new(move |r, c| index(rows - 1 - r, c)),
+            Box::new(move |r, c| index(r, cols - 1 - c)),
+            Box::new(move |r, c| index(rows - 1 - r, cols - 1 - c)),
+        ];
+
+        if rows == cols {
+            transforms.push(Box::new(move |r, c| index(c, r)));
+            transforms.push(Box::new(move |r, c| index(cols - 1 - c, rows - 1 - r)));
+            transforms.push(Box::new(move |r, c| index(c, rows - 1 - r)));
+            transforms.push(Box::new(move |r, c| index(cols - 1 - c, r)));
+        }
+
+        let symmetries = transforms
+            .iter()
+            .map(|transform| {
+                let mut permutation = vec![0; rows * cols];
+                for r in 0..rows {
+                    for c in 0..cols {
+                        permutation[transform(r, c)] = index(r, c);
+                    }
+                }
+                permutation
+            })
+            .collect();
+
+        Solver {
+            lines,
+            symmetries,
+            values: HashMap::new(),
+        }
+    }
+
+    /// Smallest encoding among all symmetric versions of the position
+    fn canonical(&self, cells: &[u8]) -> u64 {
+        self.symmetries
+            .iter()
+            .map(|permutation| {
+                permutation
+                    .iter()
+                    .fold(0, |key, &i| key * 3 + u64::from(cells[i]))
+            })
+            .min()
+            .expect("No symmetries")
+    }
+
+    fn has_line(&self, cells: &[u8], player: u8) -> bool {
+        self.lines
+            .iter()
+            .any(|line| line.iter().all(|&i| cells[i] == player))
+    }
+
+    /// Game value for `player` (to move): 1 (win), 0 (draw) or -1 (loss).
+    /// Visits every reachable position so they can be counted.
+    fn solve(&mut self, cells: &mut Vec<u8>, player: u8) -> i8 {
+        let key = self.canonical(cells);
+        if let Some(&value) = self.values.get(&key) {
+            return value;
+        }
+
+        let opponent = if player == X { O } else { X };
+
+        let value = if self.has_line(cells, opponent) {
+            -1
+        } else if cells.iter().all(|&cell| cell != EMPTY) {
+            0
+        } else {
+            let mut value = -1;
+
+            for i in 0..cells.len() {
+                if cells[i] == EMPTY {
+                    cells[i] = player;
+                    value = value.max(-self.solve(cells, opponent));
+                    cells[i] = EMPTY;
+                }
+            }
+
+            value
+        };
+
+        self.values.insert(key, value);
+        value
+    }
+}
+
+pub fn run(args: &[String]) {
+    let (mut rows, mut cols, mut k) = (3, 3, 3);
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => {
+                let size: Vec<usize> = args
+                    .next()
+                    .map(|s| s.split('x').filter_map(|n| n.parse().ok()).collect())
+                    .unwrap_or_default();
+
+                match size[..] {
+                    [r, c] => {
+                        rows = r;
+                        cols = c;
+                    }
+                    _ => cli::fail("Invalid value for --size, expected e.g. 3x3"),
+                }
+            }
+            "-k" => k = cli::value(arg, args.next()),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    if rows == 0 || cols == 0 || rows > 4 || cols > 4 {
+        cli::fail("Board sizes from 1x1 up to 4x4 are supported");
+    }
+
+    if k == 0 || k > rows.max(cols) {
+        cli::fail("-k must fit onto the board");
+    }
+
+    let mut solver = Solver::new(rows, cols, k);
+    let value = solver.solve(&mut vec![EMPTY; rows * cols], X);
+
+    println!("{}x{}, {} in a row", rows, cols, k);
+    println!(
+        "Game value: {}",
+        match value {
+            1 => "first player wins",
+            0 => "draw",
+            _ => "second player wins",
+        }
+    );
+    println!("Distinct positions (up to symmetry): {}", solver.values.len());
+}
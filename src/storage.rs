@@ -0,0 +1,361 @@
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::SystemTime;
+use rand::Rng;
+use rand::os::OsRng;
+use game::{Board, Coord, Player};
+use record::GameRecord;
+
+/// A game's id: a random UUID, so games can't be found to join or watch by
+/// guessing, e.g. `0f8e2b6a-41c3-4d2e-9a7b-5c1d3e8f6a20`
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct GameId(u128);
+
+impl GameId {
+    /// A new id, as a version 4 UUID
+    pub fn random() -> GameId {
+        let mut rng = OsRng::new().expect("No random source");
+        let bits = (rng.gen::<u64>() as u128) << 64 | rng.gen::<u64>() as u128;
+        let bits = bits & !(0xf << 76) | 0x4 << 76;
+        GameId(bits & !(0x3 << 62) | 0x2 << 62)
+    }
+}
+
+impl Display for GameId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hex = format!("{:032x}", self.0);
+        write!(f, "{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+    }
+}
+
+impl FromStr for GameId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<GameId, String> {
+        let groups: Vec<&str> = s.split('-').collect();
+        let lengths: Vec<usize> = groups.iter().map(|group| group.len()).collect();
+        let hex = groups.concat();
+
+        match u128::from_str_radix(&hex, 16) {
+            // Parsing allows a sign, which isn't part of an id
+            Ok(bits) if lengths == [8, 4, 4, 4, 12] && !hex.starts_with('+') => {
+                Ok(GameId(bits))
+            }
+            _ => Err(format!("Invalid game id {}", s)),
+        }
+    }
+}
+
+/// A game that came to a result, stored for its players' history
+#[derive(Debug, Clone)]
+pub struct FinishedGame {
+    pub id: GameId,
+    /// The names of players X and O
+    pub players: [String; 2],
+    /// `None` for a draw
+    pub winner: Option<Player>,
+    pub moves: Vec<Coord>,
+}
+
+/// A game being played, along with what its players need to resume it
+#[derive(Debug, Clone)]
+pub struct RunningGame {
+    pub id: GameId,
+    pub name: String,
+    /// The names and session tokens of players X and O
+    pub players: [(String, String); 2],
+    /// The API keys players X and O authenticated with, if any
+    pub keys: [Option<String>; 2],
+    /// The game was paired by the ladder and is rated
+    pub ladder: bool,
+    pub moves: Vec<Coord>,
+}
+
+/// Where the server keeps its games, so they outlive a restart
+pub trait Storage: Send {
+    fn save_finished(&mut self, game: &FinishedGame) -> Result<(), String>;
+
+    /// The last `limit` finished games, newest first, optionally only those
+    /// of one player
+    fn history(&self, player: Option<&str>, limit: usize) -> Result<Vec<FinishedGame>, String>;
+
+    /// Store a running game, replacing its previous state
+    fn save_running(&mut self, game: &RunningGame) -> Result<(), String>;
+
+    fn remove_running(&mut self, id: GameId) -> Result<(), String>;
+
+    fn running(&self) -> Result<Vec<RunningGame>, String>;
+}
+
+/// Games as files in a directory, in the format of saved games with the
+/// players in comments:
+///
+/// ```text
+/// <dir>/finished/<id>.txt     # x alice, # o bob, # winner x, then the moves
+/// <dir>/running/<id>.txt      # name <room>, # x alice <token>, ..., the moves
+///                             # key x <key> for players with an API key
+///                             # ladder for rated games
+/// ```
+pub struct Directory {
+    path: PathBuf,
+}
+
+impl Directory {
+    pub fn open(path: &str) -> Result<Directory, String> {
+        let path = PathBuf::from(path);
+        for sub in &["finished", "running"] {
+            fs::create_dir_all(path.join(sub))
+                .map_err(|e| format!("Could not create {}/{}: {}", path.display(), sub, e))?;
+        }
+
+        Ok(Directory { path })
+    }
+
+    fn file(&self, kind: &str, id: GameId) -> PathBuf {
+        self.path.join(kind).join(format!("{}.txt", id))
+    }
+
+    /// The contents of every file of `kind`, the oldest written first
+    fn read_all(&self, kind: &str) -> Result<Vec<(GameId, String)>, String> {
+        let dir = self.path.join(kind);
+        let entries =
+            fs::read_dir(&dir).map_err(|e| format!("Could not read {}: {}", dir.display(), e))?;
+
+        let mut files = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let id = path.file_stem().and_then(|s| s.to_str()).and_then(|s| s.parse().ok());
+
+            if let Some(id) = id {
+                let text = fs::read_to_string(&path)
+                    .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+                let written = entry.metadata().and_then(|m| m.modified()).ok();
+                files.push((written.unwrap_or(SystemTime::UNIX_EPOCH), id, text));
+            }
+        }
+
+        files.sort_by_key(|&(written, id, _)| (written, id));
+        Ok(files.into_iter().map(|(_, id, text)| (id, text)).collect())
+    }
+
+    fn write(&self, kind: &str, id: GameId, text: &str) -> Result<(), String> {
+        // Write next to the file and rename, a crash never leaves half a game
+        let path = self.file(kind, id);
+        let temporary = path.with_extension("tmp");
+
+        fs::write(&temporary, text)
+            .and_then(|_| fs::rename(&temporary, &path))
+            .map_err(|e| format!("Could not write {}: {}", path.display(), e))
+    }
+}
+
+/// The moves as a saved game, below the `header` comments
+fn format(header: &[String], moves: &[Coord]) -> String {
+    let mut record = GameRecord::new(Board::new(Player::X));
+    for &action in moves {
+        record.push(action);
+    }
+
+    let mut text: String = header.iter().map(|line| format!("# {}\n", line)).collect();
+    text.push_str(&record.to_string());
+    text
+}
+
+/// The words of each header comment
+type Header<'a> = Vec<Vec<&'a str>>;
+
+/// The header comments and the moves of a stored game
+fn parse(text: &str) -> Result<(Header<'_>, Vec<Coord>), String> {
+    let header = text
+        .lines()
+        .filter_map(|line| line.strip_prefix("# "))
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    let record = GameRecord::parse(text)?;
+
+    Ok((header, record.moves.iter().map(|m| m.action).collect()))
+}
+
+fn parse_finished(id: GameId, text: &str) -> Result<FinishedGame, String> {
+    let (header, moves) = parse(text).map_err(|e| format!("Game {}: {}", id, e))?;
+    let mut game = FinishedGame {
+        id,
+        players: [String::new(), String::new()],
+        winner: None,
+        moves,
+    };
+
+    for fields in header {
+        match fields[..] {
+            ["x", name] => game.players[0] = name.to_string(),
+            ["o", name] => game.players[1] = name.to_string(),
+            ["winner", "x"] => game.winner = Some(Player::X),
+            ["winner", "o"] => game.winner = Some(Player::O),
+            ["winner", "draw"] => game.winner = None,
+            _ => return Err(format!("Game {}: invalid header {}", id, fields.join(" "))),
+        }
+    }
+
+    Ok(game)
+}
+
+fn parse_running(id: GameId, text: &str) -> Result<RunningGame, String> {
+    let (header, moves) = parse(text).map_err(|e| format!("Game {}: {}", id, e))?;
+    let mut game = RunningGame {
+        id,
+        name: String::new(),
+        players: Default::default(),
+        keys: Default::default(),
+        ladder: false,
+        moves,
+    };
+
+    for fields in header {
+        match fields[..] {
+            ["name", ..] => game.name = fields[1..].join(" "),
+            ["x", name, token] => game.players[0] = (name.to_string(), token.to_string()),
+            ["o", name, token] => game.players[1] = (name.to_string(), token.to_string()),
+            ["key", "x", key] => game.keys[0] = Some(key.to_string()),
+            ["key", "o", key] => game.keys[1] = Some(key.to_string()),
+            ["ladder"] => game.ladder = true,
+            _ => return Err(format!("Game {}: invalid header {}", id, fields.join(" "))),
+        }
+    }
+
+    Ok(game)
+}
+
+impl Storage for Directory {
+    fn save_finished(&mut self, game: &FinishedGame) -> Result<(), String> {
+        let winner = match game.winner {
+            Some(player) => player.to_string().to_lowercase(),
+            None => "draw".to_string(),
+        };
+        let header = [
+            format!("x {}", game.players[0]),
+            format!("o {}", game.players[1]),
+            format!("winner {}", winner),
+        ];
+
+        self.write("finished", game.id, &format(&header, &game.moves))
+    }
+
+    fn history(&self, player: Option<&str>, limit: usize) -> Result<Vec<FinishedGame>, String> {
+        let mut games = Vec::new();
+
+        for (id, text) in self.read_all("finished")?.into_iter().rev() {
+            let game = match parse_finished(id, &text) {
+                Ok(game) => game,
+                Err(e) => {
+                    eprintln!("Could not read a finished game, leaving it out: {}", e);
+                    continue;
+                }
+            };
+            if player.is_none_or(|name| game.players.iter().any(|p| p == name)) {
+                games.push(game);
+            }
+            if games.len() == limit {
+                break;
+            }
+        }
+
+        Ok(games)
+    }
+
+    fn save_running(&mut self, game: &RunningGame) -> Result<(), String> {
+        let mut header = vec![
+            format!("name {}", game.name),
+            format!("x {} {}", game.players[0].0, game.players[0].1),
+            format!("o {} {}", game.players[1].0, game.players[1].1),
+        ];
+        for (player, key) in ["x", "o"].iter().zip(&game.keys) {
+            if let Some(ref key) = *key {
+                header.push(format!("key {} {}", player, key));
+            }
+        }
+        if game.ladder {
+            header.push("ladder".to_string());
+        }
+
+        self.write("running", game.id, &format(&header, &game.moves))
+    }
+
+    fn remove_running(&mut self, id: GameId) -> Result<(), String> {
+        let path = self.file("running", id);
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(ref e) if e.kind() == ::std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("Could not remove {}: {}", path.display(), e)),
+        }
+    }
+
+    fn running(&self) -> Result<Vec<RunningGame>, String> {
+        let mut games = Vec::new();
+
+        // A broken game is left out rather than keeping the server from starting
+        for (id, text) in self.read_all("running")? {
+            match parse_running(id, &text) {
+                Ok(game) => games.push(game),
+                Err(e) => eprintln!("Could not read a running game, not restoring it: {}", e),
+            }
+        }
+
+        Ok(games)
+    }
+}
+
+/// A finished game in one line of the protocol, e.g. `<id> alice bob x 2b
+/// 1a ...`
+pub fn summary(game: &FinishedGame) -> String {
+    let winner = match game.winner {
+        Some(player) => player.to_string(),
+        None => "draw".to_string(),
+    };
+    let moves: Vec<String> = game.moves.iter().cloned().map(|action| action.to_string()).collect();
+
+    format!(
+        "{} {} {} {} {}",
+        game.id,
+        game.players[0],
+        game.players[1],
+        winner,
+        moves.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GameId;
+
+    #[test]
+    fn game_ids_are_version_4_uuids() {
+        for _ in 0..100 {
+            let id = GameId::random();
+            let text = id.to_string();
+
+            assert_eq!(text.parse::<GameId>(), Ok(id));
+            assert_eq!(text.len(), 36);
+            assert_eq!(&text[14..15], "4");
+            assert!("89ab".contains(&text[19..20]));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_game_ids() {
+        for id in &[
+            "",
+            "42",
+            "0f8e2b6a41c34d2e9a7b5c1d3e8f6a20",
+            "0f8e2b6a-41c3-4d2e-9a7b5c1d-3e8f6a20",
+            "0f8e2b6a-41c3-4d2e-9a7b-5c1d3e8f6a2g",
+            "+f8e2b6a-41c3-4d2e-9a7b-5c1d3e8f6a20",
+        ] {
+            assert_eq!(id.parse::<GameId>(), Err(format!("Invalid game id {}", id)));
+        }
+
+        let id = "0f8e2b6a-41c3-4d2e-9a7b-5c1d3e8f6a20";
+        assert_eq!(id.parse::<GameId>().unwrap().to_string(), id);
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt::Write;
+use std::fs;
+use std::iter;
+use cli;
+use game::{Board, Coord, Player};
+use record::GameRecord;
+
+/// Colors of a board drawn as SVG
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Style {
+    pub background: &'static str,
+    pub grid: &'static str,
+    pub x: &'static str,
+    pub o: &'static str,
+    /// Behind the cell of the last move
+    pub highlight: &'static str,
+}
+
+pub const LIGHT: Style = Style {
+    background: "#ffffff",
+    grid: "#333333",
+    x: "#d62728",
+    o: "#1f77b4",
+    highlight: "#fff3b0",
+};
+
+const DARK: Style = Style {
+    background: "#1e1e1e",
+    grid: "#bbbbbb",
+    x: "#ff6b6b",
+    o: "#6bb8ff",
+    highlight: "#4a4520",
+};
+
+/// Orange and sky blue from the Okabe-Ito palette, as in the terminal theme
+const COLORBLIND: Style = Style {
+    background: "#ffffff",
+    grid: "#333333",
+    x: "#e69f00",
+    o: "#56b4e9",
+    highlight: "#eeeeee",
+};
+
+/// Black on white, for print
+const MONOCHROME: Style = Style {
+    background: "#ffffff",
+    grid: "#000000",
+    x: "#000000",
+    o: "#000000",
+    highlight: "#dddddd",
+};
+
+const STYLES: [(&str, Style); 4] = [
+    ("light", LIGHT),
+    ("dark", DARK),
+    ("colorblind", COLORBLIND),
+    ("monochrome", MONOCHROME),
+];
+
+impl Style {
+    pub fn by_name(name: &str) -> Option<Style> {
+        STYLES.iter().find(|&&(n, _)| n == name).map(|&(_, style)| style)
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        STYLES.iter().map(|&(name, _)| name).collect()
+    }
+}
+
+/// Draw `board` with cells of `size` pixels, highlighting `last_move`
+pub fn board(board: &Board, style: &Style, size: u32, last_move: Option<Coord>) -> String {
+    let width = size * 3;
+    // Stroke widths and margins scale with the cells
+    let stroke = (size / 20).max(1);
+    let margin = size / 5;
+    let mut svg = String::new();
+
+    writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{0}\" \
+         viewBox=\"0 0 {0} {0}\">",
+        width
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        "  <rect width=\"{0}\" height=\"{0}\" fill=\"{1}\"/>",
+        width, style.background
+    )
+    .unwrap();
+
+    if let Some(action) = last_move {
+        writeln!(
+            svg,
+            "  <rect x=\"{}\" y=\"{}\" width=\"{2}\" height=\"{2}\" fill=\"{3}\"/>",
+            action.col() as u32 * size,
+            action.row() as u32 * size,
+            size,
+            style.highlight
+        )
+        .unwrap();
+    }
+
+    for i in 1..3 {
+        writeln!(
+            svg,
+            "  <line x1=\"{0}\" y1=\"0\" x2=\"{0}\" y2=\"{1}\" stroke=\"{2}\" \
+             stroke-width=\"{3}\"/>",
+            i * size,
+            width,
+            style.grid,
+            stroke
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            "  <line x1=\"0\" y1=\"{0}\" x2=\"{1}\" y2=\"{0}\" stroke=\"{2}\" \
+             stroke-width=\"{3}\"/>",
+            i * size,
+            width,
+            style.grid,
+            stroke
+        )
+        .unwrap();
+    }
+
+    for (row, cells) in board.fields.iter().enumerate() {
+        for (col, cell) in cells.iter().enumerate() {
+            let (x, y) = (col as u32 * size, row as u32 * size);
+            let (low, high) = (margin, size - margin);
+
+            match *cell {
+                Some(Player::X) => {
+                    for &(y1, y2) in &[(low, high), (high, low)] {
+                        writeln!(
+                            svg,
+                            "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" \
+                             stroke-width=\"{}\" stroke-linecap=\"round\"/>",
+                            x + low,
+                            y + y1,
+                            x + high,
+                            y + y2,
+                            style.x,
+                            stroke * 2
+                        )
+                        .unwrap();
+                    }
+                }
+                Some(Player::O) => {
+                    writeln!(
+                        svg,
+                        "  <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"none\" stroke=\"{}\" \
+                         stroke-width=\"{}\"/>",
+                        x + size / 2,
+                        y + size / 2,
+                        size / 2 - margin,
+                        style.o,
+                        stroke * 2
+                    )
+                    .unwrap();
+                }
+                None => {}
+            }
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn write(path: &str, svg: &str) {
+    fs::write(path, svg).unwrap_or_else(|e| cli::fail(&format!("Could not write {}: {}", path, e)));
+    println!("Wrote {}", path);
+}
+
+/// Export a position, or every position of a saved game, as SVG
+pub fn run(args: &[String]) {
+    let mut file: Option<&String> = None;
+    let mut position = None;
+    let mut out: Option<String> = None;
+    let mut style = LIGHT;
+    let mut size = 100;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--position" => position = Some(cli::position(args.next())),
+            "--out" => out = Some(cli::value(arg, args.next())),
+            "--style" => style = cli::style(args.next()),
+            "--size" => size = cli::value(arg, args.next()),
+            _ if file.is_none() && !arg.starts_with("--") => file = Some(arg),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    if size < 20 {
+        cli::fail("--size must be at least 20 pixels");
+    }
+
+    match (file, position) {
+        (None, Some(position)) => {
+            let path = out.unwrap_or_else(|| "board.svg".to_string());
+            write(&path, &self::board(&position, &style, size, None));
+        }
+        // One file per position, named by the number of moves played
+        (Some(file), None) => {
+            let record = GameRecord::load(file).unwrap_or_else(|e| cli::fail(&e));
+            let directory = out.unwrap_or_else(|| ".".to_string());
+            fs::create_dir_all(&directory)
+                .unwrap_or_else(|e| cli::fail(&format!("Could not create {}: {}", directory, e)));
+
+            let last_moves =
+                iter::once(None).chain(record.moves.iter().map(|m| Some(m.action)));
+            for (i, (board, last_move)) in record.boards().iter().zip(last_moves).enumerate() {
+                let path = format!("{}/move-{:02}.svg", directory, i);
+                write(&path, &self::board(board, &style, size, last_move));
+            }
+        }
+        _ => cli::fail("Expected either a game file or --position"),
+    }
+}
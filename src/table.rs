@@ -0,0 +1,40 @@
+//! Perfect play for the standard board, looked up in a table that build.rs
+//! generates at compile time. `ENTRIES` holds every unfinished position
+//! reachable with either player starting, up to symmetry.
+
+use game::{Board, Coord, Player};
+use minimax;
+use rules::Variant;
+
+include!(concat!(env!("OUT_DIR"), "/table.rs"));
+
+/// The value of `board` for the player to move (1, 0 or -1) and the moves
+/// keeping it, without any search. `None` for variants and finished games.
+pub fn lookup(board: &Board) -> Option<(i32, Vec<Coord>)> {
+    if board.variant != Variant::STANDARD {
+        return None;
+    }
+
+    let (canonical, symmetry) = board.canonical();
+    let index = canonical.key() * 2 + if board.next_player() == Player::O { 1 } else { 0 };
+    let entry = ENTRIES.binary_search_by_key(&index, |&(index, _, _)| index).ok()?;
+    let (_, value, best) = ENTRIES[entry];
+
+    // The moves are stored for the canonical board, map them back
+    let mut actions: Vec<Coord> = Coord::ALL
+        .iter()
+        .filter(|cell| best & 1 << cell.index() != 0)
+        .map(|&cell| symmetry.inverse().apply(cell))
+        .collect();
+    actions.sort();
+
+    Some((i32::from(value), actions))
+}
+
+/// Like `lookup`, but searching what the table doesn't cover
+pub fn perfect(board: &Board) -> (i32, Vec<Coord>) {
+    lookup(board).unwrap_or_else(|| {
+        let mut cache = minimax::shared();
+        (cache.value(board), cache.best_actions(board))
+    })
+}
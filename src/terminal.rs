@@ -0,0 +1,46 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+/// Whether stdout understands ANSI escape sequences (colors, clearing the
+/// screen). Respects `NO_COLOR` and `TERM=dumb`, and on Windows switches
+/// the console into virtual terminal mode first.
+pub fn supports_ansi() -> bool {
+    if !io::stdout().is_terminal() || env::var_os("NO_COLOR").is_some() {
+        return false;
+    }
+
+    if env::var("TERM").map(|term| term == "dumb").unwrap_or(false) {
+        return false;
+    }
+
+    enable_virtual_terminal()
+}
+
+#[cfg(windows)]
+fn enable_virtual_terminal() -> bool {
+    use std::ffi::c_void;
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetStdHandle(std_handle: u32) -> *mut c_void;
+        fn GetConsoleMode(console: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: *mut c_void, mode: u32) -> i32;
+    }
+
+    // Older consoles (before Windows 10) don't support the mode and refuse it
+    unsafe {
+        let console = GetStdHandle(STD_OUTPUT_HANDLE);
+        let mut mode = 0;
+
+        GetConsoleMode(console, &mut mode) != 0
+            && SetConsoleMode(console, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+    }
+}
+
+#[cfg(not(windows))]
+fn enable_virtual_terminal() -> bool {
+    true
+}
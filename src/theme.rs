@@ -0,0 +1,63 @@
+use game::Player;
+
+/// How the cells of the board are drawn
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Theme {
+    pub x: &'static str,
+    pub o: &'static str,
+    pub empty: &'static str,
+}
+
+/// Uncolored, the classic look
+pub const PLAIN: Theme = Theme {
+    x: "x",
+    o: "o",
+    empty: ".",
+};
+
+/// Red and blue players
+const COLOR: Theme = Theme {
+    x: "\x1b[1;31mx\x1b[0m",
+    o: "\x1b[1;34mo\x1b[0m",
+    empty: "\x1b[2m.\x1b[0m",
+};
+
+/// Orange and sky blue (from the Okabe-Ito palette), which stay apart with
+/// the common kinds of color blindness
+const COLORBLIND: Theme = Theme {
+    x: "\x1b[1;38;5;208mx\x1b[0m",
+    o: "\x1b[1;38;5;39mo\x1b[0m",
+    empty: "\x1b[2m.\x1b[0m",
+};
+
+/// No hue at all: the players differ in shape and emphasis
+const HIGH_CONTRAST: Theme = Theme {
+    x: "\x1b[1mX\x1b[0m",
+    o: "\x1b[1;4mO\x1b[0m",
+    empty: "-",
+};
+
+const THEMES: [(&str, Theme); 4] = [
+    ("plain", PLAIN),
+    ("color", COLOR),
+    ("colorblind", COLORBLIND),
+    ("high-contrast", HIGH_CONTRAST),
+];
+
+impl Theme {
+    pub fn by_name(name: &str) -> Option<Theme> {
+        THEMES.iter().find(|&&(n, _)| n == name).map(|&(_, theme)| theme)
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        THEMES.iter().map(|&(name, _)| name).collect()
+    }
+
+    pub fn cell(&self, cell: Option<Player>) -> &'static str {
+        match cell {
+            Some(Player::X) => self.x,
+            Some(Player::O) => self.o,
+            None => self.empty,
+        }
+    }
+}
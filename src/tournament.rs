@@ -0,0 +1,646 @@
+use std::cmp::Ordering;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use cli;
+use external::External;
+use game::{Board, Coord, Player};
+use handicap::Handicap;
+use mcts::{Config, Limit, Rollout, Selection, StopToken, MCTS};
+use parallel;
+use results::{self, Results};
+use selfplay::{self, MatchResult, Played};
+
+/// What plays for an entrant
+pub enum Kind {
+    Mcts(Config),
+    /// The command starting an engine speaking the engine protocol
+    External(String),
+}
+
+/// A named engine taking part in a tournament
+pub struct Entrant {
+    pub name: String,
+    pub kind: Kind,
+}
+
+impl Entrant {
+    /// Parse an entrant like `tuned:selection=ucb1-tuned,exploration=1`
+    fn parse(spec: &str) -> Result<Entrant, String> {
+        let (name, config) = split_name(spec)?;
+
+        Ok(Entrant {
+            name,
+            kind: Kind::Mcts(Config::parse(config)?),
+        })
+    }
+
+    /// Parse an external entrant like `other:./other-engine --quiet`
+    fn parse_external(spec: &str) -> Result<Entrant, String> {
+        let (name, command) = split_name(spec)?;
+
+        Ok(Entrant {
+            name,
+            kind: Kind::External(command.to_string()),
+        })
+    }
+
+    /// What plays: the MCTS configuration or the engine's command
+    fn engine(&self) -> String {
+        match self.kind {
+            Kind::Mcts(config) => config.to_string(),
+            Kind::External(ref command) => command.clone(),
+        }
+    }
+
+    /// Get the entrant ready for a match, starting its process if external
+    fn side(&self) -> Result<Side, String> {
+        match self.kind {
+            Kind::Mcts(config) => Ok(Side::Mcts(config)),
+            Kind::External(ref command) => External::spawn(command).map(Side::External),
+        }
+    }
+}
+
+fn split_name(spec: &str) -> Result<(String, &str), String> {
+    spec.split_once(':')
+        .map(|(name, rest)| (name.to_string(), rest))
+        .ok_or_else(|| format!("Expected <name>:<...>: {}", spec))
+}
+
+/// An entrant taking part in a match
+enum Side {
+    Mcts(Config),
+    External(External),
+}
+
+impl Side {
+    fn best_move(&mut self, board: &Board, iterations: u32) -> Result<Coord, String> {
+        match *self {
+            Side::Mcts(config) => {
+                let mut engine = MCTS::from_board(board.next_player(), *board, config);
+                let limit = Limit::Iterations(u64::from(iterations));
+                let result = engine.search(limit, &StopToken::new());
+
+                result.action.ok_or_else(|| "Engine found no action".to_string())
+            }
+            Side::External(ref mut engine) => engine.best_move(board, iterations),
+        }
+    }
+}
+
+/// Play a game, with `handicap` for the weaker side's player if given. A side
+/// that fails to come up with a legal move forfeits.
+fn play_game(
+    x: &mut Side,
+    o: &mut Side,
+    iterations: u32,
+    handicap: Option<(Player, &Handicap)>,
+) -> Played {
+    let start = Instant::now();
+    let mut board = Board::new(Player::X);
+    let mut moves = 0;
+    let mut stronger_moves = 0;
+
+    if let Some((weaker, handicap)) = handicap {
+        handicap.place(&mut board, weaker).expect("Handicap checked when parsed");
+    }
+
+    while !board.is_ended() {
+        let player = board.next_player();
+        let side = if player == Player::X { &mut *x } else { &mut *o };
+
+        let iterations = match handicap {
+            Some((weaker, handicap)) if player != weaker => {
+                stronger_moves += 1;
+                let budget = handicap.budget(stronger_moves);
+                ((f64::from(iterations) * budget) as u32).max(1)
+            }
+            _ => iterations,
+        };
+
+        match side.best_move(&board, iterations) {
+            Ok(action) => board.perform_action(action),
+            Err(e) => {
+                println!("Player {} forfeits: {}", player, e);
+                return Played {
+                    winner: Some(player.opponent()),
+                    moves,
+                    duration: start.elapsed(),
+                };
+            }
+        }
+        moves += 1;
+    }
+
+    Played {
+        winner: board.status().winner(),
+        moves,
+        duration: start.elapsed(),
+    }
+}
+
+/// Entrants playing when none are given
+fn default_entrants() -> Vec<Entrant> {
+    let config = Config::default();
+
+    vec![
+        Entrant {
+            name: "default".to_string(),
+            kind: Kind::Mcts(config),
+        },
+        Entrant {
+            name: "heuristic".to_string(),
+            kind: Kind::Mcts(Config {
+                rollout: Rollout::Heuristic,
+                ..config
+            }),
+        },
+        Entrant {
+            name: "tuned".to_string(),
+            kind: Kind::Mcts(Config {
+                selection: Selection::Ucb1Tuned,
+                ..config
+            }),
+        },
+        Entrant {
+            name: "puct".to_string(),
+            kind: Kind::Mcts(Config {
+                selection: Selection::Puct,
+                ..config
+            }),
+        },
+    ]
+}
+
+/// Rounds of refining the ratings from the results
+const ELO_ROUNDS: usize = 200;
+/// How far a rating moves per round for the share of points it is off by
+const ELO_STEP: f64 = 200.;
+/// Ratings are at most this far from the average
+const ELO_LIMIT: f64 = 800.;
+
+/// Results of every pairing played so far
+struct Table {
+    entrants: Vec<Entrant>,
+    /// `results[a][b]` is the match between `a` and `b` from `a`'s point of
+    /// view, `None` if they haven't met
+    results: Vec<Vec<Option<MatchResult>>>,
+    /// Points awarded for sitting out a round
+    byes: Vec<f64>,
+    games: u32,
+    iterations: u32,
+    /// Handicaps of pairings, for the weaker and the stronger entrant
+    handicaps: Vec<(usize, usize, Handicap)>,
+    /// Number of games of a match played at the same time
+    jobs: usize,
+    /// Every game played, for `--output`
+    log: Results,
+}
+
+impl Table {
+    fn new(
+        entrants: Vec<Entrant>,
+        games: u32,
+        iterations: u32,
+        handicaps: Vec<(usize, usize, Handicap)>,
+        jobs: usize,
+    ) -> Table {
+        let n = entrants.len();
+
+        Table {
+            entrants,
+            results: vec![vec![None; n]; n],
+            byes: vec![0.; n],
+            games,
+            iterations,
+            handicaps,
+            jobs,
+            log: Results::new(&[
+                "round",
+                "game",
+                "iterations",
+                "x",
+                "o",
+                "x_engine",
+                "o_engine",
+                "winner",
+                "moves",
+                "seconds",
+                "handicap",
+            ]),
+        }
+    }
+
+    /// The handicap between `a` and `b`, and whether `a` is the weaker side
+    fn handicap(&self, a: usize, b: usize) -> Option<(bool, &Handicap)> {
+        self.handicaps.iter().find_map(|&(weaker, stronger, ref handicap)| {
+            if (weaker, stronger) == (a, b) {
+                Some((true, handicap))
+            } else if (weaker, stronger) == (b, a) {
+                Some((false, handicap))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn play(&mut self, round: usize, a: usize, b: usize) {
+        let mut result = MatchResult::default();
+        let handicap = match self.handicap(a, b) {
+            Some((a_weaker, handicap)) => {
+                let weaker = if a_weaker { a } else { b };
+                format!("{}: {}", self.entrants[weaker].name, handicap)
+            }
+            None => String::new(),
+        };
+
+        for (game, played) in self.play_match(a, b).into_iter().enumerate() {
+            let a_player = selfplay::first_engine_player(game as u32);
+            result.record(a_player, played.winner);
+
+            let (x, o) = if a_player == Player::X { (a, b) } else { (b, a) };
+            self.log.push(vec![
+                round.into(),
+                (game + 1).into(),
+                self.iterations.into(),
+                self.entrants[x].name.as_str().into(),
+                self.entrants[o].name.as_str().into(),
+                self.entrants[x].engine().into(),
+                self.entrants[o].engine().into(),
+                results::winner(played.winner),
+                played.moves.into(),
+                played.duration.into(),
+                handicap.as_str().into(),
+            ]);
+        }
+
+        println!(
+            "Round {}: {} - {}: +{} ={} -{}",
+            round, self.entrants[a].name, self.entrants[b].name,
+            result.wins, result.draws, result.losses
+        );
+
+        // Swiss pairings repeat once everyone has met, add up the matches
+        let total = match self.results[a][b] {
+            Some(previous) => previous + result,
+            None => result,
+        };
+        self.results[a][b] = Some(total);
+        self.results[b][a] = Some(total.reversed());
+    }
+
+    /// Play `games` games between `a` and `b`, alternating who starts. The
+    /// games are spread over the workers, each with engines of its own.
+    fn play_match(&self, a: usize, b: usize) -> Vec<Played> {
+        let games: Vec<u32> = (0..self.games).collect();
+        // A game an engine forfeits without a move being played
+        let forfeit = |game: u32, a_wins: Option<bool>| Played {
+            winner: a_wins.map(|a_wins| {
+                let a_player = selfplay::first_engine_player(game);
+                if a_wins { a_player } else { a_player.opponent() }
+            }),
+            moves: 0,
+            duration: Duration::default(),
+        };
+
+        // An engine that doesn't start forfeits the whole match
+        let sides = match (self.entrants[a].side(), self.entrants[b].side()) {
+            (Ok(a_side), Ok(b_side)) => (a_side, b_side),
+            (Err(e), Ok(_)) => {
+                println!("{}", e);
+                return games.iter().map(|&game| forfeit(game, Some(false))).collect();
+            }
+            (Ok(_), Err(e)) => {
+                println!("{}", e);
+                return games.iter().map(|&game| forfeit(game, Some(true))).collect();
+            }
+            (Err(a_error), Err(b_error)) => {
+                println!("{}\n{}", a_error, b_error);
+                return games.iter().map(|&game| forfeit(game, None)).collect();
+            }
+        };
+
+        // The first worker gets the engines started above, the others start
+        // their own
+        let started = Mutex::new(Some(sides));
+        let start = || match started.lock().unwrap().take() {
+            Some((a_side, b_side)) => (Ok(a_side), Ok(b_side)),
+            None => (self.entrants[a].side(), self.entrants[b].side()),
+        };
+
+        parallel::map(&games, self.jobs, start, |sides, &game| {
+            let a_player = selfplay::first_engine_player(game);
+            let handicap = self.handicap(a, b).map(|(a_weaker, handicap)| {
+                (if a_weaker { a_player } else { a_player.opponent() }, handicap)
+            });
+
+            match *sides {
+                (Ok(ref mut a_side), Ok(ref mut b_side)) if a_player == Player::X => {
+                    play_game(a_side, b_side, self.iterations, handicap)
+                }
+                (Ok(ref mut a_side), Ok(ref mut b_side)) => {
+                    play_game(b_side, a_side, self.iterations, handicap)
+                }
+                // An engine failing to start for a worker forfeits its games
+                (Err(_), Ok(_)) => forfeit(game, Some(false)),
+                (Ok(_), Err(_)) => forfeit(game, Some(true)),
+                (Err(_), Err(_)) => forfeit(game, None),
+            }
+        })
+    }
+
+    fn bye(&mut self, round: usize, entrant: usize) {
+        println!("Round {}: {} has a bye", round, self.entrants[entrant].name);
+
+        // A bye counts as winning every game of the match
+        self.byes[entrant] += f64::from(self.games);
+    }
+
+    fn opponents(&self, entrant: usize) -> impl Iterator<Item = (usize, MatchResult)> + '_ {
+        self.results[entrant]
+            .iter()
+            .enumerate()
+            .filter_map(|(opponent, result)| result.map(|result| (opponent, result)))
+    }
+
+    fn points(&self, entrant: usize) -> f64 {
+        self.byes[entrant] + self.opponents(entrant).map(|(_, r)| r.points()).sum::<f64>()
+    }
+
+    fn wins(&self, entrant: usize) -> u32 {
+        self.opponents(entrant).map(|(_, result)| result.wins).sum()
+    }
+
+    /// Tie-break: the opponents' points, weighted by the share of the points
+    /// scored against each
+    fn sonneborn_berger(&self, entrant: usize) -> f64 {
+        self.opponents(entrant)
+            .map(|(opponent, result)| result.score() * self.points(opponent))
+            .sum()
+    }
+
+    /// Tie-break: the sum of the opponents' points
+    fn buchholz(&self, entrant: usize) -> f64 {
+        self.opponents(entrant).map(|(opponent, _)| self.points(opponent)).sum()
+    }
+
+    /// Entrants from first to last: by points, then Sonneborn-Berger, then
+    /// Buchholz, then the number of games won
+    fn standings(&self) -> Vec<usize> {
+        let key = |entrant: usize| {
+            (
+                self.points(entrant),
+                self.sonneborn_berger(entrant),
+                self.buchholz(entrant),
+                f64::from(self.wins(entrant)),
+            )
+        };
+
+        let mut standings: Vec<usize> = (0..self.entrants.len()).collect();
+        standings.sort_by(|&a, &b| key(b).partial_cmp(&key(a)).unwrap_or(Ordering::Equal));
+        standings
+    }
+
+    /// Ratings fitting the results, centered on 0. A handicap counts as its
+    /// rating points for the weaker side, so that an entrant scoring evenly
+    /// with a handicap is rated that much below its opponent.
+    fn elo(&self) -> Vec<f64> {
+        let n = self.entrants.len();
+        let mut ratings = vec![0.; n];
+
+        for _ in 0..ELO_ROUNDS {
+            let mut next = ratings.clone();
+
+            for (entrant, rating) in next.iter_mut().enumerate() {
+                let (mut actual, mut expected, mut games) = (0., 0., 0.);
+                for (opponent, result) in self.opponents(entrant) {
+                    let bonus = match self.handicap(entrant, opponent) {
+                        Some((true, handicap)) => handicap.elo(),
+                        Some((false, handicap)) => -handicap.elo(),
+                        None => 0.,
+                    };
+                    let difference = ratings[opponent] - ratings[entrant] - bonus;
+                    let count = f64::from(result.wins + result.draws + result.losses);
+
+                    actual += result.points();
+                    expected += count / (1. + 10f64.powf(difference / 400.));
+                    games += count;
+                }
+
+                if games > 0. {
+                    *rating += ELO_STEP * (actual - expected) / games;
+                }
+            }
+
+            // Only differences count, keep the average at 0 and a clean
+            // sweep from running off
+            let mean = next.iter().sum::<f64>() / n as f64;
+            ratings = next.iter().map(|r| (r - mean).clamp(-ELO_LIMIT, ELO_LIMIT)).collect();
+        }
+
+        ratings
+    }
+
+    /// The standings with the points scored against every opponent
+    fn print(&self) {
+        let standings = self.standings();
+        let width = self.entrants.iter().map(|e| e.name.len()).max().unwrap_or(0).max(4);
+
+        print!("{:>3}  {:<width$}", "#", "Name", width = width);
+        for rank in 1..=standings.len() {
+            print!(" {:>5}", rank);
+        }
+        println!(" {:>7} {:>7} {:>8} {:>5}", "Points", "SB", "Buchholz", "Elo");
+        let elo = self.elo();
+
+        for (rank, &entrant) in standings.iter().enumerate() {
+            print!("{:>3}  {:<width$}", rank + 1, self.entrants[entrant].name, width = width);
+
+            for &opponent in &standings {
+                match self.results[entrant][opponent] {
+                    _ if opponent == entrant => print!(" {:>5}", "-"),
+                    Some(result) => print!(" {:>5.1}", result.points()),
+                    None => print!(" {:>5}", "."),
+                }
+            }
+
+            println!(
+                " {:>7.1} {:>7.2} {:>8.1} {:>+5.0}",
+                self.points(entrant),
+                self.sonneborn_berger(entrant),
+                self.buchholz(entrant),
+                elo[entrant]
+            );
+        }
+    }
+}
+
+/// Parse a handicap like `default/tuned:stones=2b`, for the weaker entrant
+/// against the stronger one
+fn parse_handicap(spec: &str) -> Result<(String, String, Handicap), String> {
+    let (pairing, handicap) = split_name(spec)?;
+    let (weaker, stronger) = pairing
+        .split_once('/')
+        .ok_or_else(|| format!("Expected <weaker>/<stronger>:<handicap>: {}", spec))?;
+
+    Ok((weaker.to_string(), stronger.to_string(), Handicap::parse(handicap)?))
+}
+
+/// Every entrant plays every other once, scheduled in rounds by the circle
+/// method: one entrant stays put while the others rotate around it. With an
+/// odd number of entrants, whoever is paired with the empty seat sits out.
+fn round_robin(table: &mut Table) {
+    let mut seats: Vec<Option<usize>> = (0..table.entrants.len()).map(Some).collect();
+    if seats.len() % 2 == 1 {
+        seats.push(None);
+    }
+    let n = seats.len();
+
+    for round in 1..n {
+        for i in 0..n / 2 {
+            match (seats[i], seats[n - 1 - i]) {
+                (Some(a), Some(b)) => table.play(round, a, b),
+                (Some(entrant), None) | (None, Some(entrant)) => {
+                    println!("Round {}: {} sits out", round, table.entrants[entrant].name)
+                }
+                (None, None) => (),
+            }
+        }
+
+        let last = seats.pop().expect("No seats");
+        seats.insert(1, last);
+    }
+}
+
+/// Each round pairs entrants with similar points that haven't met yet. With
+/// an odd number of entrants the lowest ranked one without a bye sits out.
+fn swiss(table: &mut Table, rounds: usize) {
+    for round in 1..=rounds {
+        let mut unpaired = table.standings();
+
+        if unpaired.len() % 2 == 1 {
+            let bye = unpaired
+                .iter()
+                .rposition(|&entrant| table.byes[entrant] == 0.)
+                .unwrap_or(unpaired.len() - 1);
+            let entrant = unpaired.remove(bye);
+            table.bye(round, entrant);
+        }
+
+        while !unpaired.is_empty() {
+            let a = unpaired.remove(0);
+            let b = unpaired
+                .iter()
+                .position(|&b| table.results[a][b].is_none())
+                .unwrap_or(0);
+            let b = unpaired.remove(b);
+
+            table.play(round, a, b);
+        }
+    }
+}
+
+/// Play a round-robin or Swiss tournament among engine configurations and
+/// external engines and print the cross-table
+pub fn run(args: &[String]) {
+    let mut entrants = Vec::new();
+    let mut games = 10;
+    let mut iterations = 100;
+    let mut swiss_rounds = None;
+    let mut jobs = parallel::default_jobs();
+    let mut output: Option<String> = None;
+    let mut handicaps: Vec<(String, String, Handicap)> = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--entrant" => {
+                let spec: String = cli::value(arg, args.next());
+                entrants.push(Entrant::parse(&spec).unwrap_or_else(|e| cli::fail(&e)));
+            }
+            "--external" => {
+                let spec: String = cli::value(arg, args.next());
+                entrants.push(Entrant::parse_external(&spec).unwrap_or_else(|e| cli::fail(&e)));
+            }
+            "--games" => games = cli::value(arg, args.next()),
+            "--iterations" => iterations = cli::value(arg, args.next()),
+            "--swiss" => swiss_rounds = Some(cli::value(arg, args.next())),
+            "--jobs" => jobs = cli::value(arg, args.next()),
+            "--output" => output = Some(cli::value(arg, args.next())),
+            "--handicap" => {
+                let spec: String = cli::value(arg, args.next());
+                handicaps.push(parse_handicap(&spec).unwrap_or_else(|e| cli::fail(&e)));
+            }
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    if entrants.is_empty() {
+        entrants = default_entrants();
+    }
+
+    if entrants.len() < 2 {
+        cli::fail("A tournament needs at least two entrants");
+    }
+
+    if games == 0 || iterations == 0 || swiss_rounds == Some(0) || jobs == 0 {
+        cli::fail("--games, --iterations, --swiss and --jobs must be positive");
+    }
+
+    if let Some(ref path) = output {
+        results::check_path(path).unwrap_or_else(|e| cli::fail(&e));
+    }
+
+    let handicaps: Vec<(usize, usize, Handicap)> = handicaps
+        .into_iter()
+        .map(|(weaker, stronger, handicap)| {
+            let find = |name: &str| {
+                entrants.iter().position(|e| e.name == name).unwrap_or_else(|| {
+                    cli::fail(&format!("Unknown entrant in --handicap: {}", name))
+                })
+            };
+            let (weaker, stronger) = (find(&weaker), find(&stronger));
+
+            // External engines only take positions that can come up in a game
+            let external = |i: usize| matches!(entrants[i].kind, Kind::External(_));
+            if !handicap.stones.is_empty() && (external(weaker) || external(stronger)) {
+                cli::fail("External engines can't play with handicap stones");
+            }
+
+            (weaker, stronger, handicap)
+        })
+        .collect();
+
+    for entrant in &entrants {
+        match entrant.kind {
+            Kind::Mcts(config) => println!("{}: [{}]", entrant.name, config),
+            Kind::External(ref command) => println!("{}: `{}`", entrant.name, command),
+        }
+    }
+    println!(
+        "Playing {} games per pairing at {} iterations per move, {} at a time",
+        games, iterations, jobs
+    );
+    for &(weaker, stronger, ref handicap) in &handicaps {
+        println!(
+            "Handicap for {} against {}: {}",
+            entrants[weaker].name, entrants[stronger].name, handicap
+        );
+    }
+    println!();
+
+    let mut table = Table::new(entrants, games, iterations, handicaps, jobs);
+    match swiss_rounds {
+        Some(rounds) => swiss(&mut table, rounds),
+        None => round_robin(&mut table),
+    }
+
+    println!();
+    table.print();
+
+    if let Some(path) = output {
+        table.log.write(&path).unwrap_or_else(|e| cli::fail(&e));
+        println!();
+        println!("Wrote the games to {}", path);
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::time::Instant;
+use rand::{self, Rng};
+use cli;
+use game::{Board, Player};
+use mcts::{Config, Limit, StopToken, MCTS};
+use parallel;
+use results::{self, Results};
+
+/// Moves at the start of each game picked in proportion to the visits
+/// instead of the best one, so the games differ
+const EXPLORATION_MOVES: usize = 2;
+
+/// A training example: a position, how the search divided its visits over
+/// the cells (row by row) and the final result for the player to move
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub board: Board,
+    pub policy: [f64; 9],
+    pub outcome: i32,
+}
+
+impl Sample {
+    fn to_json(&self) -> String {
+        let policy: Vec<String> = self.policy.iter().map(|p| format!("{:.4}", p)).collect();
+
+        format!(
+            "{{\"position\":\"{}\",\"policy\":[{}],\"outcome\":{}}}",
+            self.board.fen(),
+            policy.join(","),
+            self.outcome
+        )
+    }
+}
+
+/// The most recent samples, older ones making room for new ones
+pub struct ReplayBuffer {
+    samples: VecDeque<Sample>,
+    capacity: usize,
+}
+
+impl ReplayBuffer {
+    pub fn new(capacity: usize) -> ReplayBuffer {
+        ReplayBuffer {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, sample: Sample) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// `size` samples drawn at random, with replacement
+    pub fn batch(&self, size: usize) -> Vec<&Sample> {
+        let mut rng = rand::thread_rng();
+
+        (0..size)
+            .map(|_| &self.samples[rng.gen_range(0, self.samples.len())])
+            .collect()
+    }
+}
+
+/// Learns from batches of self-play samples
+pub trait Trainer {
+    fn train(&mut self, batch: &[&Sample]) -> Result<(), String>;
+}
+
+/// Writes each batch to a JSON Lines file for training outside the crate
+pub struct Exporter {
+    directory: String,
+    batches: usize,
+}
+
+impl Trainer for Exporter {
+    fn train(&mut self, batch: &[&Sample]) -> Result<(), String> {
+        self.batches += 1;
+
+        let path = format!("{}/batch-{:05}.jsonl", self.directory, self.batches);
+        let lines: Vec<String> = batch.iter().map(|sample| sample.to_json()).collect();
+
+        fs::write(&path, lines.join("\n") + "\n")
+            .map_err(|e| format!("Could not write {}: {}", path, e))
+    }
+}
+
+/// Play a game against itself, returning a sample for every position
+pub fn self_play(config: &Config, iterations: u64) -> Vec<Sample> {
+    let mut rng = rand::thread_rng();
+    let mut board = Board::new(Player::X);
+    let mut samples = Vec::new();
+
+    while !board.is_ended() {
+        let player = board.next_player();
+        let mut engine = MCTS::from_board(player, board, *config);
+        let result = engine.search(Limit::Iterations(iterations), &StopToken::new());
+
+        let total: i32 = result.stats.iter().map(|stats| stats.visits).sum();
+        let mut policy = [0.; 9];
+        for stats in &result.stats {
+            policy[stats.action.index()] = f64::from(stats.visits) / f64::from(total.max(1));
+        }
+
+        let action = if samples.len() < EXPLORATION_MOVES {
+            let mut pick = rng.gen_range(0, total.max(1));
+            result
+                .stats
+                .iter()
+                .find(|stats| {
+                    pick -= stats.visits;
+                    pick < 0
+                })
+                .map(|stats| stats.action)
+                .or(result.action)
+        } else {
+            result.action
+        };
+
+        samples.push(Sample {
+            board,
+            policy,
+            outcome: 0,
+        });
+        board.perform_action(action.expect("Engine found no action"));
+    }
+
+    for sample in &mut samples {
+        sample.outcome = board.get_reward(sample.board.next_player()).unwrap_or(0);
+    }
+
+    samples
+}
+
+/// Generate self-play games into the replay buffer and hand batches of it to
+/// the trainer after every game
+pub fn run(args: &[String]) {
+    let mut games = 100;
+    let mut iterations = 400;
+    let mut capacity = 10000;
+    let mut batch_size = 256;
+    let mut directory = "training".to_string();
+    let mut jobs = parallel::default_jobs();
+    let mut output: Option<String> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => games = cli::value(arg, args.next()),
+            "--iterations" => iterations = cli::value(arg, args.next()),
+            "--buffer" => capacity = cli::value(arg, args.next()),
+            "--batch-size" => batch_size = cli::value(arg, args.next()),
+            "--out" => directory = cli::value(arg, args.next()),
+            "--jobs" => jobs = cli::value(arg, args.next()),
+            "--output" => output = Some(cli::value(arg, args.next())),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    if games == 0 || iterations == 0 || capacity == 0 || batch_size == 0 || jobs == 0 {
+        cli::fail("--games, --iterations, --buffer, --batch-size and --jobs must be positive");
+    }
+
+    if let Some(ref path) = output {
+        results::check_path(path).unwrap_or_else(|e| cli::fail(&e));
+    }
+
+    fs::create_dir_all(&directory)
+        .unwrap_or_else(|e| cli::fail(&format!("Could not create {}: {}", directory, e)));
+
+    let config = Config::default();
+    let mut buffer = ReplayBuffer::new(capacity);
+    let mut rows = Results::new(&["game", "config", "winner", "moves", "seconds"]);
+    let mut trainer = Exporter {
+        directory: directory.clone(),
+        batches: 0,
+    };
+
+    // Play as many games at once as there are workers, then learn from
+    // them in order, as if they had been played one after another
+    let numbers: Vec<u32> = (1..=games).collect();
+    for chunk in numbers.chunks(jobs) {
+        let played = parallel::map(chunk, jobs, || (), |_, _| {
+            let start = Instant::now();
+            (self_play(&config, iterations), start.elapsed())
+        });
+
+        for (&game, (samples, duration)) in chunk.iter().zip(played) {
+            // The first position has X to move, its outcome tells who won
+            let winner = match samples[0].outcome {
+                1 => Some(Player::X),
+                -1 => Some(Player::O),
+                _ => None,
+            };
+            rows.push(vec![
+                game.into(),
+                config.to_string().into(),
+                results::winner(winner),
+                samples.len().into(),
+                duration.into(),
+            ]);
+
+            for sample in samples {
+                buffer.push(sample);
+            }
+
+            trainer.train(&buffer.batch(batch_size)).unwrap_or_else(|e| cli::fail(&e));
+
+            println!("Game {}/{}: {} samples in the buffer", game, games, buffer.len());
+        }
+    }
+
+    println!("Wrote {} batches to {}", trainer.batches, directory);
+
+    if let Some(path) = output {
+        rows.write(&path).unwrap_or_else(|e| cli::fail(&e));
+        println!("Wrote the games to {}", path);
+    }
+}
@@ -0,0 +1,117 @@
+use std::f64;
+use cli;
+use mcts::{Config, Rollout, Selection};
+use results::{self, Results};
+use selfplay;
+
+const SELECTION: [Selection; 3] = [Selection::Ucb1, Selection::Ucb1Tuned, Selection::Puct];
+const EXPLORATION: [f64; 5] = [0.5, 1., f64::consts::SQRT_2, 2., 3.];
+const ROLLOUT: [Rollout; 4] = [
+    Rollout::Random,
+    Rollout::Heuristic,
+    Rollout::EpsilonGreedy(0.2),
+    Rollout::Cutoff(4),
+];
+const FPU: [f64; 4] = [f64::INFINITY, 1., 0.5, 0.];
+const PROGRESSIVE_BIAS: [f64; 2] = [0., 1.];
+
+/// Grid search over the MCTS parameters, scoring each configuration in
+/// self-play against the default one
+pub fn run(args: &[String]) {
+    let mut games = 50;
+    let mut iterations = 50;
+    let mut output: Option<String> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => games = cli::value(arg, args.next()),
+            "--iterations" => iterations = cli::value(arg, args.next()),
+            "--output" => output = Some(cli::value(arg, args.next())),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    if games == 0 || iterations == 0 {
+        cli::fail("--games and --iterations must be positive");
+    }
+
+    if let Some(ref path) = output {
+        results::check_path(path).unwrap_or_else(|e| cli::fail(&e));
+    }
+    let mut rows = Results::new(&[
+        "config",
+        "baseline",
+        "game",
+        "iterations",
+        "config_player",
+        "winner",
+        "moves",
+        "seconds",
+    ]);
+
+    let baseline = Config::default();
+    let mut best = (baseline, 0.5);
+
+    println!(
+        "Playing {} games per configuration against [{}] at {} iterations per move",
+        games, baseline, iterations
+    );
+
+    let mut configs = Vec::new();
+    for &selection in &SELECTION {
+        for &exploration in &EXPLORATION {
+            for &rollout in &ROLLOUT {
+                for &fpu in &FPU {
+                    for &progressive_bias in &PROGRESSIVE_BIAS {
+                        configs.push(Config {
+                            selection,
+                            exploration,
+                            rollout,
+                            fpu,
+                            progressive_bias,
+                            ..baseline
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    for config in configs {
+        let (result, played) = selfplay::play_match(&config, &baseline, games, iterations);
+        for (game, played) in played.iter().enumerate() {
+            rows.push(vec![
+                config.to_string().into(),
+                baseline.to_string().into(),
+                (game + 1).into(),
+                iterations.into(),
+                results::player(selfplay::first_engine_player(game as u32)),
+                results::winner(played.winner),
+                played.moves.into(),
+                played.duration.into(),
+            ]);
+        }
+
+        println!(
+            "[{}]: {:.1}% (+{} ={} -{})",
+            config,
+            result.score() * 100.,
+            result.wins,
+            result.draws,
+            result.losses
+        );
+
+        if result.score() > best.1 {
+            best = (config, result.score());
+        }
+    }
+
+    println!();
+    println!("Best configuration: [{}] ({:.1}%)", best.0, best.1 * 100.);
+
+    if let Some(path) = output {
+        rows.write(&path).unwrap_or_else(|e| cli::fail(&e));
+        println!("Wrote the games to {}", path);
+    }
+}
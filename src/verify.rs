@@ -0,0 +1,100 @@
+use std::process;
+use cli;
+use game::{Board, Coord, Player};
+use mcts::{Config, Limit, StopToken, MCTS};
+use minimax::Minimax;
+
+/// Plays the engine against every line of perfect play and collects the
+/// games it lost
+struct Verifier {
+    ai: Player,
+    iterations: u32,
+    minimax: Minimax,
+    games: u32,
+    losses: Vec<Vec<Coord>>,
+}
+
+impl Verifier {
+    fn explore(&mut self, board: Board, moves: &mut Vec<Coord>) {
+        if board.is_ended() {
+            self.games += 1;
+
+            if board.status().winner() == Some(self.ai.opponent()) {
+                self.losses.push(moves.clone());
+            }
+
+            return;
+        }
+
+        let actions = if board.next_player() == self.ai {
+            let mut engine = MCTS::from_board(self.ai, board, Config::default());
+            let limit = Limit::Iterations(u64::from(self.iterations));
+            let result = engine.search(limit, &StopToken::new());
+
+            vec![result.action.expect("Engine found no action")]
+        } else {
+            // Follow every perfect reply of the opponent
+            self.minimax.best_actions(&board)
+        };
+
+        for action in actions {
+            let mut child = board;
+            child.perform_action(action);
+
+            moves.push(action);
+            self.explore(child, moves);
+            moves.pop();
+        }
+    }
+}
+
+/// Verify the engine never loses against perfect play, neither as the
+/// starting nor as the second player
+pub fn run(args: &[String]) {
+    let mut iterations = 1000;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--iterations" => iterations = cli::value(arg, args.next()),
+            _ => cli::fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    if iterations == 0 {
+        cli::fail("--iterations must be positive");
+    }
+
+    let mut minimax = Minimax::new();
+    let mut lost = false;
+
+    for &ai in &[Player::X, Player::O] {
+        let mut verifier = Verifier {
+            ai,
+            iterations,
+            minimax,
+            games: 0,
+            losses: Vec::new(),
+        };
+        verifier.explore(Board::new(Player::X), &mut Vec::new());
+
+        println!(
+            "AI as {}: {} games against perfect play, {} lost",
+            ai,
+            verifier.games,
+            verifier.losses.len()
+        );
+
+        for line in &verifier.losses {
+            let moves: Vec<String> = line.iter().map(|&a| a.to_string()).collect();
+            println!("    Lost: {}", moves.join(" "));
+        }
+
+        lost |= !verifier.losses.is_empty();
+        minimax = verifier.minimax;
+    }
+
+    if lost {
+        process::exit(1);
+    }
+}